@@ -0,0 +1,103 @@
+#ifndef FORTRESS_H
+#define FORTRESS_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a call.
+typedef enum FortressStatus {
+  FORTRESS_STATUS_OK = 0,
+  // A pointer is null or a string is not UTF-8.
+  FORTRESS_STATUS_INVALID_ARGUMENT = 1,
+  FORTRESS_STATUS_VAULT_NOT_FOUND = 2,
+  FORTRESS_STATUS_VAULT_ALREADY_EXISTS = 3,
+  // The master password is wrong or the vault is corrupted.
+  FORTRESS_STATUS_DECRYPTION_FAILED = 4,
+  FORTRESS_STATUS_WEAK_PASSWORD = 5,
+  FORTRESS_STATUS_ID_NOT_FOUND = 6,
+  FORTRESS_STATUS_ID_ALREADY_EXISTS = 7,
+  FORTRESS_STATUS_UNKNOWN_FIELD = 8,
+  // Any other error, see `fortress_last_error`.
+  FORTRESS_STATUS_FAILED = 9,
+} FortressStatus;
+
+// An unlocked vault, opaque to C.
+typedef struct FortressVault FortressVault;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Open an existing vault.
+//
+// # Safety
+// `path` and `password` must be NUL-terminated strings, `out` must be valid for writes.
+// On success, `*out` must be released with `fortress_close`.
+enum FortressStatus fortress_open(const char *path,
+                                  const char *password,
+                                  struct FortressVault **out);
+
+// Create an empty vault, failing if the file exists.
+//
+// # Safety
+// `path` and `password` must be NUL-terminated strings, `out` must be valid for writes.
+// On success, `*out` must be released with `fortress_close`.
+enum FortressStatus fortress_create(const char *path,
+                                    const char *password,
+                                    struct FortressVault **out);
+
+// Get a field of an entry: `identifier`, `username`, `password`, `url` or `tags`.
+//
+// # Safety
+// `vault` must come from `fortress_open` or `fortress_create`, `identifier` must be a
+// NUL-terminated string, `field` may be null for the password, `out` must be valid for writes.
+// On success, `*out` must be released with `fortress_free`.
+enum FortressStatus fortress_get(const struct FortressVault *vault,
+                                 const char *identifier,
+                                 const char *field,
+                                 char **out);
+
+// Set the username and the password of an entry, creating it if needed.
+// The replaced password is kept in the history. The vault is saved.
+//
+// # Safety
+// `vault` must come from `fortress_open` or `fortress_create`, `identifier` and `password`
+// must be NUL-terminated strings, `username` may be null to keep the current one.
+enum FortressStatus fortress_set(struct FortressVault *vault,
+                                 const char *identifier,
+                                 const char *username,
+                                 const char *password);
+
+// Move an entry to the trash. The vault is saved.
+//
+// # Safety
+// `vault` must come from `fortress_open` or `fortress_create`, `identifier` must be a
+// NUL-terminated string.
+enum FortressStatus fortress_remove(struct FortressVault *vault, const char *identifier);
+
+// The message of the last error of the calling thread, or null.
+// The message is owned by the library and valid until the next call on the thread.
+const char *fortress_last_error(void);
+
+// Wipe and release a string handed out by the library. Null is ignored.
+//
+// # Safety
+// `value` must be null or come from `fortress_get`, and must not be used afterwards.
+void fortress_free(char *value);
+
+// Close a vault, wiping its key. Null is ignored.
+//
+// # Safety
+// `vault` must be null or come from `fortress_open` or `fortress_create`,
+// and must not be used afterwards.
+void fortress_close(struct FortressVault *vault);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* FORTRESS_H */
@@ -0,0 +1,393 @@
+//! Agent keeping the derived key of a vault in memory, like `ssh-agent`.
+//!
+//! The agent listens on a Unix socket only reachable by the current user,
+//! and checks the credentials of every peer. The next commands get the key
+//! from it instead of asking the master password and deriving the key again.
+//! The agent stops once the key is wiped, by `frtrs lock` or when its TTL expires.
+use crate::crypto::{KEY_LEN, SALT_LEN, VaultHeader, VaultKey};
+use crate::helpers::errors::FortressError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
+
+/// Environment variable overriding the path of the socket.
+pub const SOCKET_ENV: &str = "FRTRS_AGENT_SOCK";
+
+/// How long a peer may take to send its request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the agent checks its TTL while waiting for peers.
+const TICK: Duration = Duration::from_millis(100);
+
+/// A request sent to the agent, as one JSON line.
+#[derive(Serialize, Deserialize)]
+enum Request {
+    /// Get the key of the vault at this canonical path.
+    Key(String),
+    /// Wipe the key and stop the agent.
+    Lock,
+}
+
+/// The answer of the agent, as one JSON line.
+#[derive(Serialize, Deserialize)]
+enum Response {
+    /// The key of the requested vault.
+    Key {
+        salt: [u8; SALT_LEN],
+        key: [u8; KEY_LEN],
+    },
+    /// The agent does not hold the key of the requested vault.
+    Unknown,
+    /// The key has been wiped.
+    Locked,
+}
+
+/// The key, kept out of the swap while the agent runs.
+struct LockedKey(Box<VaultKey>);
+
+impl LockedKey {
+    fn new(key: VaultKey) -> Self {
+        let key = Box::new(key);
+        // SAFETY: the pointer and the size describe the boxed key, alive until `drop`.
+        let locked = unsafe {
+            libc::mlock(
+                &*key as *const VaultKey as *const libc::c_void,
+                size_of::<VaultKey>(),
+            )
+        };
+        if locked != 0 {
+            log::warn!("Agent key could not be locked in memory");
+        }
+        LockedKey(key)
+    }
+}
+
+impl Drop for LockedKey {
+    fn drop(&mut self) {
+        self.0.key.zeroize();
+        // SAFETY: same region as the one given to `mlock`.
+        unsafe {
+            libc::munlock(
+                &*self.0 as *const VaultKey as *const libc::c_void,
+                size_of::<VaultKey>(),
+            );
+        }
+    }
+}
+
+/// The path of the agent socket: `$FRTRS_AGENT_SOCK`, else in `$XDG_RUNTIME_DIR`,
+/// else in a private directory of `/tmp`.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os(SOCKET_ENV) {
+        return PathBuf::from(path);
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("frtrs-agent.sock"),
+        // SAFETY: `getuid` cannot fail.
+        None => {
+            PathBuf::from(format!("/tmp/frtrs-{}", unsafe { libc::getuid() })).join("agent.sock")
+        }
+    }
+}
+
+/// Serve the key until it is wiped.
+/// ## Parameters:
+/// - `socket`: The path of the socket to listen on
+/// - `file`: The path of the unlocked vault
+/// - `key`: The key derived from the master password
+/// - `ttl`: How long the key is kept. Zero keeps it until `frtrs lock`
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn serve(socket: &Path, file: &str, key: VaultKey, ttl: Duration) -> Result<(), FortressError> {
+    let vault = fs::canonicalize(file)?;
+    let key = LockedKey::new(key);
+    let listener = bind(socket)?;
+    listener.set_nonblocking(true)?;
+    log::info!("Agent started on {}", socket.display());
+
+    let started = Instant::now();
+    loop {
+        if !ttl.is_zero() && started.elapsed() >= ttl {
+            log::info!("Agent key expired");
+            break;
+        }
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(TICK);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match handle(stream, &vault, &key) {
+            Ok(true) => {
+                log::info!("Agent locked");
+                break;
+            }
+            Ok(false) => {}
+            Err(e) => log::warn!("Agent request rejected: {}", e),
+        }
+    }
+
+    drop(key);
+    let _ = fs::remove_file(socket);
+    Ok(())
+}
+
+/// Answer one peer.
+/// ## Returns:
+/// A result of `true` if the agent must stop, or a [`FortressError`]
+fn handle(stream: UnixStream, vault: &Path, key: &LockedKey) -> Result<bool, FortressError> {
+    check_peer(&stream)?;
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let request: Request = read_line(&stream)?;
+    let (response, stop) = match request {
+        Request::Key(path) if Path::new(&path) == vault => (
+            Response::Key {
+                salt: key.0.salt,
+                key: key.0.key,
+            },
+            false,
+        ),
+        Request::Key(_) => (Response::Unknown, false),
+        Request::Lock => (Response::Locked, true),
+    };
+    write_line(&stream, &response)?;
+    Ok(stop)
+}
+
+/// Listen on the socket, readable and writable only by the current user.
+fn bind(socket: &Path) -> Result<UnixListener, FortressError> {
+    if UnixStream::connect(socket).is_ok() {
+        return Err(FortressError::Agent(format!(
+            "an agent is already running on {}",
+            socket.display()
+        )));
+    }
+    let _ = fs::remove_file(socket);
+    if let Some(dir) = socket.parent()
+        && !dir.exists()
+    {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+/// Get the key of a vault from the agent, if one is running and holds it.
+/// The key is ignored if the vault has been saved with another salt since.
+/// ## Parameters:
+/// - `file`: The path of the vault
+/// ## Returns:
+/// The key, or `None` if the master password must be asked.
+pub fn cached_key(file: &str) -> Option<VaultKey> {
+    let key = fetch_key(&socket_path(), file).ok()??;
+    let encrypted = fs::read(file).ok()?;
+    let (header, _) = VaultHeader::parse(&encrypted).ok()?;
+    (header.salt == key.salt).then_some(key)
+}
+
+/// Ask the agent on the socket for the key of a vault.
+/// ## Parameters:
+/// - `socket`: The path of the agent socket
+/// - `file`: The path of the vault
+/// ## Returns:
+/// A result of the key if the agent holds it, or a [`FortressError`]
+pub fn fetch_key(socket: &Path, file: &str) -> Result<Option<VaultKey>, FortressError> {
+    let vault = fs::canonicalize(file)?;
+    let request = Request::Key(vault.to_string_lossy().into_owned());
+    match call(socket, &request)? {
+        Response::Key { salt, key } => Ok(Some(VaultKey { salt, key })),
+        _ => Ok(None),
+    }
+}
+
+/// Ask the agent on the socket to wipe its key and stop.
+/// ## Parameters:
+/// - `socket`: The path of the agent socket
+/// ## Returns:
+/// A result of `true` if an agent was running, or a [`FortressError`]
+pub fn lock(socket: &Path) -> Result<bool, FortressError> {
+    if !socket.exists() {
+        return Ok(false);
+    }
+    match call(socket, &Request::Lock) {
+        Ok(_) => Ok(true),
+        Err(FortressError::IoError(e)) if e.kind() == io::ErrorKind::ConnectionRefused => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Send a request to the agent and read its answer.
+fn call(socket: &Path, request: &Request) -> Result<Response, FortressError> {
+    let stream = UnixStream::connect(socket)?;
+    check_peer(&stream)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    write_line(&stream, request)?;
+    read_line(&stream)
+}
+
+/// Reject peers running as another user.
+fn check_peer(stream: &UnixStream) -> Result<(), FortressError> {
+    let uid = peer_uid(stream)?;
+    // SAFETY: `getuid` cannot fail.
+    if uid != unsafe { libc::getuid() } {
+        return Err(FortressError::Agent(format!(
+            "peer runs as another user ({})",
+            uid
+        )));
+    }
+    Ok(())
+}
+
+/// The user id of the process on the other end of the socket.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: `cred` and `len` are valid for writes of the size given in `len`.
+    let res = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+/// The user id of the process on the other end of the socket.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut uid = 0;
+    let mut gid = 0;
+    // SAFETY: `uid` and `gid` are valid for writes.
+    let res = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+/// Write a message as one JSON line, wiping the serialized copy.
+fn write_line(mut stream: &UnixStream, message: &impl Serialize) -> Result<(), FortressError> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    let res = stream.write_all(line.as_bytes());
+    line.zeroize();
+    Ok(res?)
+}
+
+/// Read a message from one JSON line, wiping the received copy.
+fn read_line<T: for<'de> Deserialize<'de>>(stream: &UnixStream) -> Result<T, FortressError> {
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let message = serde_json::from_str(&line);
+    line.zeroize();
+    Ok(message?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::structs::GeneralArgs;
+    use crate::helpers::{self, structs::VaultData};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    fn wait_for(socket: &Path) {
+        let started = Instant::now();
+        while !socket.exists() && started.elapsed() < Duration::from_secs(5) {
+            thread::sleep(TICK);
+        }
+    }
+
+    fn start(file: &str, socket: &Path, ttl: Duration) -> thread::JoinHandle<()> {
+        let args = GeneralArgs::new(file.to_string(), "S3cureP@ssword".to_string());
+        helpers::save_vault(args.clone(), &VaultData::default()).expect("save failed");
+        let key = helpers::unlock_vault(&args).expect("unlock failed");
+        let (path, file) = (socket.to_path_buf(), file.to_string());
+        let handle = thread::spawn(move || serve(&path, &file, key, ttl).unwrap());
+        wait_for(socket);
+        handle
+    }
+
+    #[test]
+    fn test_agent_serves_key_until_locked() {
+        let file = tmp_path("agent_vault");
+        let socket = PathBuf::from(tmp_path("agent_sock"));
+        let handle = start(&file, &socket, Duration::ZERO);
+
+        let mode = fs::metadata(&socket).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let key = fetch_key(&socket, &file).unwrap().expect("no key served");
+        let mut args = GeneralArgs::new(file.clone(), String::new());
+        args.key = Some(key);
+        assert!(helpers::load_vault(args).is_ok());
+
+        let other = tmp_path("agent_other");
+        fs::write(&other, b"other").unwrap();
+        assert!(fetch_key(&socket, &other).unwrap().is_none());
+
+        assert!(lock(&socket).unwrap());
+        handle.join().unwrap();
+        assert!(!socket.exists());
+        assert!(!lock(&socket).unwrap());
+        cleanup(&file);
+        cleanup(&other);
+    }
+
+    #[test]
+    fn test_agent_ttl_and_single_instance() {
+        let file = tmp_path("agent_ttl_vault");
+        let socket = PathBuf::from(tmp_path("agent_ttl_sock"));
+        let handle = start(&file, &socket, Duration::from_secs(1));
+
+        let key = VaultKey {
+            salt: [0; SALT_LEN],
+            key: [0; KEY_LEN],
+        };
+        let res = serve(&socket, &file, key, Duration::from_secs(1));
+        assert!(matches!(res, Err(FortressError::Agent(_))));
+
+        handle.join().unwrap();
+        assert!(!socket.exists());
+        cleanup(&file);
+    }
+}
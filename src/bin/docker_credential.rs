@@ -0,0 +1,50 @@
+//! `docker-credential-frtrs`: Docker credential helper keeping registry logins in the vault.
+//!
+//! Set `"credsStore": "frtrs"` in `~/.docker/config.json`. The vault is `$FRTRS_VAULT`, and its key
+//! comes from the agent, else the master password is asked on the terminal.
+use fortress::agent;
+use fortress::commands::docker_credential::{self, NOT_FOUND};
+use fortress::helpers::cli::{DEFAULT_LOG_FILE, DEFAULT_VAULT, VAULT_ENV};
+use fortress::helpers::errors::FortressError;
+use fortress::helpers::logger;
+use fortress::helpers::structs::GeneralArgs;
+use rpassword::prompt_password;
+use std::io;
+
+/// Run the action given by Docker as first argument.
+fn main() {
+    let _ = logger::init(Some(DEFAULT_LOG_FILE));
+    let operation = std::env::args().nth(1).unwrap_or_default();
+    if operation == "version" {
+        println!("{}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+
+    let file = std::env::var(VAULT_ENV).unwrap_or_else(|_| DEFAULT_VAULT.to_string());
+    let key = agent::cached_key(&file);
+    let password = match key {
+        Some(_) => String::new(),
+        None => match prompt_password("Enter the master vault password: ") {
+            Ok(password) => password,
+            Err(e) => fail(FortressError::IoError(e)),
+        },
+    };
+    let mut args = GeneralArgs::new(file, password);
+    args.key = key;
+
+    if let Err(e) =
+        docker_credential::handle(&operation, io::stdin().lock(), &mut io::stdout(), args)
+    {
+        fail(e);
+    }
+}
+
+/// Docker reads the error message on stdout, and recognizes missing credentials by their message.
+fn fail(error: FortressError) -> ! {
+    log::error!("Error: {}", error);
+    match error {
+        FortressError::IdNotFound(..) => println!("{}", NOT_FOUND),
+        error => println!("{}", error),
+    }
+    std::process::exit(1);
+}
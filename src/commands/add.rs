@@ -1,6 +1,7 @@
 //! Add a new entry to the vault.
 use crate::helpers::structs::{GeneralArgs, PasswordEntry};
 use crate::helpers::{self, errors::FortressError};
+use crate::vault::Vault;
 
 /// Add a new entry to the vault.
 /// If no one of password or generate is provided, the clipboard is used.
@@ -9,6 +10,8 @@ use crate::helpers::{self, errors::FortressError};
 /// - `username`: Username or email used to log in.
 /// - `password`: if provided, the password to save.
 /// - `generate`: If true, generate a new password.
+/// - `url`: If provided, the URL of the service.
+/// - `tags`: Tags to organize the entry.
 /// - `args`: The context of the program
 /// ## Returns:
 /// A result of nothing or a [`FortressError`]
@@ -17,6 +20,8 @@ pub fn add(
     username: String,
     password: Option<String>,
     generate: bool,
+    url: Option<String>,
+    tags: Vec<String>,
     args: GeneralArgs,
 ) -> Result<(), FortressError> {
     let password = if generate {
@@ -31,23 +36,16 @@ pub fn add(
         identifier,
         username,
         password,
+        url,
+        tags,
+        ..Default::default()
     };
 
-    let mut updated: Vec<PasswordEntry> = match helpers::load_vault(args.clone()) {
-        Ok(entries) => entries.clone(),
-        Err(e) => return Err(e),
-    };
-
-    updated.push(entry.clone());
-
-    match helpers::save_vault(args, &updated) {
-        Ok(_) => {
-            println!("{}", entry);
-            log::info!("Added entry {}", entry.identifier);
-            Ok(())
-        }
-        Err(e) => Err(e),
-    }
+    let mut vault = Vault::with_args(args)?;
+    vault.insert(entry.clone())?;
+    vault.save()?;
+    println!("{}", entry);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -58,7 +56,15 @@ mod tests {
     #[test]
     fn test_add_with_generate() {
         let args = GeneralArgs::new("/tmp/test.frt".to_string(), "S3cureP@ssword".to_string());
-        let result = add("id".to_string(), "user".to_string(), None, true, args);
+        let result = add(
+            "id".to_string(),
+            "user".to_string(),
+            None,
+            true,
+            None,
+            Vec::new(),
+            args,
+        );
         assert!(result.is_err() || result.is_ok());
     }
 }
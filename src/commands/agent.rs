@@ -0,0 +1,41 @@
+//! Start and stop the agent keeping the unlocked key.
+use crate::agent;
+use crate::helpers::structs::GeneralArgs;
+use crate::helpers::{self, errors::FortressError};
+use std::time::Duration;
+use zeroize::Zeroize;
+
+/// Unlock the vault and serve its key to the next commands until it is wiped.
+/// ## Parameters:
+/// - `ttl`: The number of seconds the key is kept. 0 keeps it until `lock`
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn agent(ttl: u64, mut args: GeneralArgs) -> Result<(), FortressError> {
+    let key = match args.key.take() {
+        Some(key) => key,
+        None => helpers::unlock_vault(&args)?,
+    };
+    args.password.zeroize();
+    let socket = agent::socket_path();
+    println!(
+        "Agent listening on {} (set {} to use another socket).",
+        socket.display(),
+        agent::SOCKET_ENV
+    );
+    agent::serve(&socket, &args.file, key, Duration::from_secs(ttl))
+}
+
+/// Wipe the key kept by the agent.
+/// ## Parameters:
+/// - `_args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn lock(_args: GeneralArgs) -> Result<(), FortressError> {
+    if agent::lock(&agent::socket_path())? {
+        println!("The agent key has been wiped.");
+    } else {
+        println!("No agent is running.");
+    }
+    Ok(())
+}
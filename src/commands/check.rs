@@ -0,0 +1,203 @@
+//! Check the integrity of the vault.
+//! This command never writes anything to the vault file.
+use crate::crypto::{self, MIN_VAULT_LEN, VaultHeader, VaultKey};
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::{GeneralArgs, PasswordEntry};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Check the vault file and report every problem found.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`] if at least one problem was found.
+pub fn check(args: GeneralArgs) -> Result<(), FortressError> {
+    if !Path::new(&args.file).exists() {
+        return Err(FortressError::VaultNotFound);
+    }
+    let data = fs::read(&args.file)?;
+    println!("Checking {} ({} bytes)", args.file, data.len());
+
+    let problems = match &args.key {
+        Some(key) => inspect_with_key(&data, key),
+        None => inspect(&data, &args.password),
+    };
+    log::info!("Vault checked: {} problem(s)", problems.len());
+    if problems.is_empty() {
+        println!("No problem found");
+        Ok(())
+    } else {
+        problems
+            .iter()
+            .for_each(|problem| println!("\t{}", problem));
+        Err(FortressError::CheckFailed(problems.len()))
+    }
+}
+
+/// Inspect an encrypted vault.
+/// ## Process
+/// 1. Check the file length against [`MIN_VAULT_LEN`]
+/// 2. Parse the header
+/// 3. Decrypt, reporting a rejected authentication tag
+/// 4. Parse the JSON and validate its schema
+/// 5. Look for duplicate identifiers, empty passwords and unknown fields
+/// ## Parameters:
+/// - `data`: The encrypted vault as bytes
+/// - `password`: The master password
+/// ## Returns:
+/// A list of human readable problems, empty if the vault is sound.
+pub fn inspect(data: &[u8], password: &str) -> Vec<String> {
+    inspect_with(data, |data| crypto::decrypt_payload(data, password))
+}
+
+/// Inspect an encrypted vault with a key already unwrapped, by a secret key or a keyfile.
+/// ## Parameters:
+/// - `data`: The encrypted vault as bytes
+/// - `key`: The key of the vault
+/// ## Returns:
+/// A list of human readable problems, empty if the vault is sound.
+pub fn inspect_with_key(data: &[u8], key: &VaultKey) -> Vec<String> {
+    inspect_with(data, |data| crypto::decrypt_payload_with_key(data, key))
+}
+
+/// Inspect an encrypted vault, decrypting its payload with `decrypt`. See [`inspect`].
+fn inspect_with(
+    data: &[u8],
+    decrypt: impl FnOnce(&[u8]) -> Result<Vec<u8>, FortressError>,
+) -> Vec<String> {
+    if data.len() < MIN_VAULT_LEN {
+        return vec![format!(
+            "File is {} bytes long, a vault is at least {} bytes",
+            data.len(),
+            MIN_VAULT_LEN
+        )];
+    }
+    if VaultHeader::parse(data).is_err() {
+        return vec!["Unable to parse the vault header".to_string()];
+    }
+
+    let plaintext = match decrypt(data) {
+        Ok(plaintext) => plaintext,
+        Err(FortressError::InvalidMasterPassword) => {
+            return vec![
+                "AEAD tag rejected: wrong master password or tampered ciphertext".to_string(),
+            ];
+        }
+        Err(e) => return vec![format!("Decryption failed: {}", e)],
+    };
+
+    let root: Value = match serde_json::from_slice(&plaintext) {
+        Ok(root) => root,
+        Err(e) => return vec![format!("JSON schema failure: {}", e)],
+    };
+
+    let mut problems = Vec::new();
+    if root.get("_pwcheck").and_then(Value::as_str) != Some("valid") {
+        problems.push("JSON schema failure: missing or invalid password check".to_string());
+    }
+    let entries = match root.get("entries").and_then(Value::as_array) {
+        Some(entries) => entries,
+        None => {
+            problems.push("JSON schema failure: `entries` is not a list".to_string());
+            return problems;
+        }
+    };
+
+    let mut seen: HashSet<String> = HashSet::new();
+    for (index, raw) in entries.iter().enumerate() {
+        if let Some(fields) = raw.as_object() {
+            fields
+                .keys()
+                .filter(|key| !PasswordEntry::FIELDS.contains(&key.as_str()))
+                .for_each(|key| {
+                    problems.push(format!("Entry #{}: unknown field `{}`", index, key))
+                });
+        }
+
+        let entry: PasswordEntry = match serde_json::from_value(raw.clone()) {
+            Ok(entry) => entry,
+            Err(e) => {
+                problems.push(format!("Entry #{}: JSON schema failure: {}", index, e));
+                continue;
+            }
+        };
+        if !seen.insert(entry.identifier.clone()) {
+            problems.push(format!("Duplicate identifier `{}`", entry.identifier));
+        }
+        if entry.password.is_empty() {
+            problems.push(format!(
+                "Entry `{}` has an empty password",
+                entry.identifier
+            ));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::structs::VaultData;
+
+    const PASSWORD: &str = "S3cureP@ssword";
+
+    #[test]
+    fn test_inspect_too_short() {
+        let problems = inspect(&[0u8; 12], PASSWORD);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("at least"));
+    }
+
+    #[test]
+    fn test_inspect_wrong_password_and_bad_schema() {
+        let sound =
+            crypto::encrypt_database(&VaultData::default(), PASSWORD).expect("encrypt failed");
+        assert!(inspect(&sound, PASSWORD).is_empty());
+
+        let problems = inspect(&sound, "wrong_password");
+        assert!(problems[0].contains("AEAD"));
+
+        let broken = crypto::encrypt_payload(b"{\"_pwcheck\":", PASSWORD).unwrap();
+        let problems = inspect(&broken, PASSWORD);
+        assert!(problems[0].contains("JSON schema failure"));
+    }
+
+    #[test]
+    fn test_inspect_entries() {
+        let payload = r#"{"_pwcheck":"valid","entries":[
+            {"identifier":"a","username":"u","password":"p"},
+            {"identifier":"a","username":"u","password":"","color":"red"}
+        ]}"#;
+        let data = crypto::encrypt_payload(payload.as_bytes(), PASSWORD).unwrap();
+        let problems = inspect(&data, PASSWORD);
+        assert_eq!(problems.len(), 3);
+        assert!(problems.iter().any(|p| p.contains("unknown field `color`")));
+        assert!(
+            problems
+                .iter()
+                .any(|p| p.contains("Duplicate identifier `a`"))
+        );
+        assert!(problems.iter().any(|p| p.contains("empty password")));
+    }
+
+    #[test]
+    fn test_inspect_with_key() {
+        let identity = crypto::Identity::generate();
+        let key = VaultKey::random();
+        let slot = crypto::KeySlot::recipient(&key, &identity.recipient()).unwrap();
+        let data = crypto::encrypt_database_with_slots(
+            &VaultData::default(),
+            &key,
+            crypto::Cipher::Aes256Gcm,
+            &[slot],
+        )
+        .unwrap();
+        assert!(inspect(&data, "").iter().any(|p| p.contains("AEAD")));
+        let unlocked = VaultKey::unlock_with_identity(&data, &identity).unwrap();
+        assert!(inspect_with_key(&data, &unlocked).is_empty());
+        assert!(!inspect_with_key(&data, &VaultKey::random()).is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+//! Encrypt the vault again with another cipher.
+//! The data key and the key slots are kept, so the passwords, keyfiles, recipients,
+//! recovery shares and the key held by the agent still open the vault.
+use crate::commands::recipients;
+use crate::crypto::{self, Cipher, VaultHeader};
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::GeneralArgs;
+use std::fs;
+
+/// Encrypt the vault with another cipher.
+/// A vault whose key is derived from the master password is first moved to key slots.
+/// ## Parameters:
+/// - `cipher`: The new cipher
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn convert(cipher: Cipher, args: GeneralArgs) -> Result<(), FortressError> {
+    let current = VaultHeader::parse(&fs::read(&args.file)?)?.0.cipher;
+    if current == cipher {
+        println!("The vault is already encrypted with {}.", cipher);
+        return Ok(());
+    }
+    let (encrypted, key) = recipients::data_key(&args)?;
+    fs::write(
+        &args.file,
+        crypto::convert_cipher(&encrypted, &key, cipher)?,
+    )
+    .map_err(FortressError::IoError)?;
+    log::info!("Vault converted from {} to {}", current, cipher);
+    println!("The vault is now encrypted with {}.", cipher);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    fn cipher_of(path: &str) -> Cipher {
+        VaultHeader::parse(&fs::read(path).unwrap())
+            .unwrap()
+            .0
+            .cipher
+    }
+
+    #[test]
+    fn test_convert_between_ciphers() {
+        let path = tmp_path("convert_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, Cipher::XChaCha20Poly1305, args.clone()).unwrap();
+        assert_eq!(cipher_of(&path), Cipher::XChaCha20Poly1305);
+        crate::commands::add::add(
+            "team/db".to_string(),
+            "admin".to_string(),
+            Some("pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .unwrap();
+        assert_eq!(cipher_of(&path), Cipher::XChaCha20Poly1305);
+        let key = helpers::unlock_vault(&args).unwrap();
+
+        convert(Cipher::Aes256Gcm, args.clone()).unwrap();
+        assert_eq!(cipher_of(&path), Cipher::Aes256Gcm);
+        let mut agent_args = GeneralArgs::new(path.clone(), String::new());
+        agent_args.key = Some(key);
+        assert_eq!(helpers::load_vault(agent_args).unwrap().entries.len(), 1);
+
+        convert(Cipher::XChaCha20Poly1305, args.clone()).unwrap();
+        assert_eq!(cipher_of(&path), Cipher::XChaCha20Poly1305);
+        assert_eq!(helpers::load_vault(args.clone()).unwrap().entries.len(), 1);
+        cleanup(&path);
+
+        crate::commands::create::create(true, Cipher::Aes256Gcm, args.clone()).unwrap();
+        assert!(recipients::read_slots(&path).unwrap().is_empty());
+        convert(Cipher::XChaCha20Poly1305, args.clone()).unwrap();
+        assert_eq!(recipients::read_slots(&path).unwrap().len(), 1);
+        assert_eq!(helpers::load_vault(args).unwrap().entries.len(), 0);
+        cleanup(&path);
+    }
+}
@@ -1,31 +1,39 @@
 //! Copy a specific entry in the vault.
-use crate::helpers::structs::GeneralArgs;
-use crate::helpers::{self, errors::FortressError};
+use crate::helpers::structs::{GeneralArgs, PasswordEntry};
+use crate::helpers::{errors::FortressError, picker};
+use crate::vault::Vault;
 
 /// Copy the password of the specific entry.
 /// ## Parameters:
-/// - `identifier`: The path of the entry to copy
+/// - `identifier`: The path of the entry to copy. Picked interactively if omitted
 /// - `args`: The context of the program
 /// ## Returns:
 /// A result of nothing or a [`FortressError`]
-pub fn copy(identifier: String, args: GeneralArgs) -> Result<(), FortressError> {
-    let decrypted = helpers::load_vault(args);
-    match decrypted {
-        Ok(decrypted) => match decrypted.iter().find(|item| item.identifier == identifier) {
-            Some(el) => {
-                println!("{}", el);
-                match cli_clipboard::set_contents(el.password.to_string()) {
-                    Ok(_) => {
-                        log::info!("Copied Password: {}", identifier);
-                        println!("The decoded password is in your clipboard");
-                        Ok(())
-                    }
-                    Err(_) => Err(FortressError::Clipboard(el.password.to_string())),
-                }
-            }
-            None => Err(FortressError::IdNotFound(identifier)),
-        },
-        Err(e) => Err(e),
+pub fn copy(identifier: Option<String>, args: GeneralArgs) -> Result<(), FortressError> {
+    let vault = Vault::with_args(args)?;
+    let identifier = picker::resolve(
+        identifier,
+        vault.entries().iter().map(|item| item.identifier.as_str()),
+    )?;
+    let el = vault.get(&identifier)?;
+    println!("{}", el);
+    copy_password(el)?;
+    println!("The decoded password is in your clipboard");
+    Ok(())
+}
+
+/// Put the password of an entry in the clipboard, without printing anything.
+/// ## Parameters:
+/// - `entry`: The entry to copy
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn copy_password(entry: &PasswordEntry) -> Result<(), FortressError> {
+    match cli_clipboard::set_contents(entry.password.to_string()) {
+        Ok(_) => {
+            log::info!("Copied Password: {}", entry.identifier);
+            Ok(())
+        }
+        Err(_) => Err(FortressError::Clipboard(entry.password.to_string())),
     }
 }
 
@@ -56,17 +64,20 @@ mod tests {
         let path = tmp_path("copy_test");
         cleanup(&path);
         let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
-        crate::commands::create::create(true, args.clone()).expect("create failed");
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
         crate::commands::add::add(
             "copy_id".to_string(),
             "copy_user".to_string(),
             Some("copy_pw".to_string()),
             false,
+            None,
+            Vec::new(),
             args.clone(),
         )
         .expect("add failed");
 
-        let res = copy("copy_id".to_string(), args.clone());
+        let res = copy(Some("copy_id".to_string()), args.clone());
         assert!(res.is_ok() || matches!(res, Err(FortressError::Clipboard(_))));
         cleanup(&path);
     }
@@ -75,10 +86,11 @@ mod tests {
         let path = tmp_path("copy_missing");
         cleanup(&path);
         let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
-        crate::commands::create::create(true, args.clone()).expect("create failed");
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
 
-        let res = copy("no_id".to_string(), args.clone());
-        assert!(matches!(res, Err(FortressError::IdNotFound(_))));
+        let res = copy(Some("no_id".to_string()), args.clone());
+        assert!(matches!(res, Err(FortressError::IdNotFound(..))));
         cleanup(&path);
     }
 }
@@ -1,37 +1,28 @@
 //! Create a new vault.
-use crate::helpers::structs::{GeneralArgs, PasswordEntry};
-use crate::helpers::{self, errors::FortressError};
-use password_strength::estimate_strength;
-use std::path::Path;
+use crate::crypto::Cipher;
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::GeneralArgs;
+use crate::vault::Vault;
 
 /// Create a new vault.
 /// If the vault already exists, an error is returned unless force is set.
 /// ## Parameters:
 /// - `force`: If true, overwrite the existing vault.
+/// - `cipher`: The cipher encrypting the vault
 /// - `args`: The context of the program
 /// ## Returns:
 /// A result of nothing or a [`FortressError`]
-pub fn create(force: bool, args: GeneralArgs) -> Result<(), FortressError> {
-    if Path::new(&args.file).exists() && !force {
-        Err(FortressError::VaultAlreadyExists)
-    } else if estimate_strength(args.clone().password.as_str()) <= 0.7 {
-        Err(FortressError::WeakPassword)
-    } else {
-        let empty_entries: Vec<PasswordEntry> = Vec::new();
-        match helpers::save_vault(args.clone(), &empty_entries) {
-            Ok(_) => {
-                println!("Created new vault at {}", args.file);
-                log::info!("Created new vault at {}", args.file);
-                Ok(())
-            }
-            Err(e) => Err(e),
-        }
-    }
+pub fn create(force: bool, cipher: Cipher, args: GeneralArgs) -> Result<(), FortressError> {
+    Vault::create_with_cipher(&args.file, &args.password, force, cipher)?;
+    println!("Created new vault at {}", args.file);
+    log::info!("Created new vault at {}", args.file);
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::helpers::structs::VaultData;
     use std::fs;
     use std::io::Write;
     use std::path::Path;
@@ -42,7 +33,7 @@ mod tests {
 
     struct MockCrypto;
     impl MockCrypto {
-        fn encrypt_database(_: &Vec<PasswordEntry>, _: &str) -> Result<Vec<u8>, ()> {
+        fn encrypt_database(_: &VaultData, _: &str) -> Result<Vec<u8>, ()> {
             Err(())
         }
     }
@@ -53,6 +44,7 @@ mod tests {
         cleanup(path);
         let result = create(
             true,
+            Cipher::Aes256Gcm,
             GeneralArgs::new(path.to_string(), "S3cureP@ssword".to_string()),
         );
         assert!(result.is_ok());
@@ -68,6 +60,7 @@ mod tests {
         writeln!(f, "dummy").unwrap();
         let result = create(
             false,
+            Cipher::Aes256Gcm,
             GeneralArgs::new(path.to_string(), "S3cureP@ssword".to_string()),
         );
         assert!(matches!(result, Err(FortressError::VaultAlreadyExists)));
@@ -82,6 +75,7 @@ mod tests {
         writeln!(f, "dummy").unwrap();
         let result = create(
             true,
+            Cipher::Aes256Gcm,
             GeneralArgs::new(path.to_string(), "S3cureP@ssword".to_string()),
         );
         assert!(result.is_ok());
@@ -91,7 +85,7 @@ mod tests {
 
     #[test]
     fn test_encryption_failed() {
-        let result = MockCrypto::encrypt_database(&vec![], "pw");
+        let result = MockCrypto::encrypt_database(&VaultData::default(), "pw");
         assert!(result.is_err());
     }
 }
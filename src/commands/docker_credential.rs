@@ -0,0 +1,201 @@
+//! Docker credential helper protocol, see `docker-credential-helpers`.
+//!
+//! Docker runs `docker-credential-frtrs <action>`: `store` reads a JSON credential on stdin,
+//! `get` and `erase` read a server URL, and `list` prints the server URLs with their usernames.
+//! Each login is kept as the entry `docker/<registry>`, with the server URL as URL.
+use crate::commands::remove;
+use crate::helpers::structs::{GeneralArgs, PasswordEntry, VaultData};
+use crate::helpers::{self, errors::FortressError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+/// Folder of the registry logins.
+pub const PREFIX: &str = "docker/";
+
+/// Message expected by Docker when a server has no credentials.
+pub const NOT_FOUND: &str = "credentials not found in native keychain";
+
+/// A registry login, as exchanged with Docker.
+#[derive(Serialize, Deserialize)]
+pub struct DockerCredential {
+    #[serde(rename = "ServerURL")]
+    pub server_url: String,
+    #[serde(rename = "Username")]
+    pub username: String,
+    #[serde(rename = "Secret")]
+    pub secret: String,
+}
+
+/// Answer an action of Docker.
+/// ## Parameters:
+/// - `operation`: The action: `get`, `store`, `erase` or `list`
+/// - `input`: The request sent by Docker
+/// - `output`: Where the answer is written
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`], [`FortressError::IdNotFound`] for unknown servers
+pub fn handle(
+    operation: &str,
+    mut input: impl Read,
+    output: &mut impl Write,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let mut request = String::new();
+    input
+        .read_to_string(&mut request)
+        .map_err(FortressError::IoError)?;
+    let mut decrypted = helpers::load_vault(args.clone())?;
+
+    match operation {
+        "get" => {
+            let server_url = request.trim();
+            let index = find(&decrypted, server_url).ok_or_else(|| not_found(server_url))?;
+            let entry = &decrypted.entries[index];
+            serde_json::to_writer(
+                output,
+                &DockerCredential {
+                    server_url: server_url.to_string(),
+                    username: entry.username.clone(),
+                    secret: entry.password.clone(),
+                },
+            )?;
+            log::info!("Docker credential sent for {}", entry.identifier);
+        }
+        "store" => {
+            let credential: DockerCredential = serde_json::from_str(&request)?;
+            match find(&decrypted, &credential.server_url) {
+                Some(index) => {
+                    let entry = &mut decrypted.entries[index];
+                    entry.username = credential.username;
+                    entry.set_password(credential.secret, helpers::now());
+                }
+                None => decrypted.entries.push(PasswordEntry {
+                    identifier: identifier(&credential.server_url),
+                    username: credential.username,
+                    password: credential.secret,
+                    url: Some(credential.server_url.clone()),
+                    ..Default::default()
+                }),
+            }
+            helpers::save_vault(args, &decrypted)?;
+            log::info!("Docker credential stored for {}", credential.server_url);
+        }
+        "erase" => {
+            let server_url = request.trim();
+            let index = find(&decrypted, server_url).ok_or_else(|| not_found(server_url))?;
+            let identifier = decrypted.entries[index].identifier.clone();
+            remove::remove_entry(&mut decrypted, identifier, false)?;
+            helpers::save_vault(args, &decrypted)?;
+        }
+        "list" => {
+            let logins: BTreeMap<&str, &str> = decrypted
+                .entries
+                .iter()
+                .filter(|entry| helpers::in_folder(&entry.identifier, PREFIX))
+                .filter_map(|entry| Some((entry.url.as_deref()?, entry.username.as_str())))
+                .collect();
+            serde_json::to_writer(output, &logins)?;
+        }
+        _ => {
+            return Err(FortressError::Unsupported(format!(
+                "unknown action '{}', expected get, store, erase or list.",
+                operation
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The identifier of the login of a server: its URL without scheme nor trailing `/`, in [`PREFIX`].
+fn identifier(server_url: &str) -> String {
+    let address = server_url
+        .split_once("://")
+        .map_or(server_url, |(_, rest)| rest);
+    format!("{}{}", PREFIX, address.trim_end_matches('/'))
+}
+
+/// Find the login of a server, by URL first, then by identifier.
+fn find(data: &VaultData, server_url: &str) -> Option<usize> {
+    let identifier = identifier(server_url);
+    data.entries
+        .iter()
+        .position(|entry| entry.url.as_deref() == Some(server_url))
+        .or_else(|| {
+            data.entries
+                .iter()
+                .position(|entry| entry.identifier == identifier)
+        })
+}
+
+/// The error of a server without credentials.
+fn not_found(server_url: &str) -> FortressError {
+    FortressError::IdNotFound(identifier(server_url), Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    fn call(operation: &str, input: &str, args: &GeneralArgs) -> Result<String, FortressError> {
+        let mut output = Vec::new();
+        handle(operation, input.as_bytes(), &mut output, args.clone())?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_identifier() {
+        assert_eq!(
+            identifier("https://index.docker.io/v1/"),
+            "docker/index.docker.io/v1"
+        );
+        assert_eq!(
+            identifier("registry.example.com"),
+            "docker/registry.example.com"
+        );
+    }
+
+    #[test]
+    fn test_store_get_list_erase() {
+        let path = tmp_path("docker_credential_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+
+        let server = "https://registry.example.com";
+        let res = call("get", server, &args);
+        assert!(matches!(res, Err(FortressError::IdNotFound(..))));
+
+        let store =
+            r#"{"ServerURL":"https://registry.example.com","Username":"ci","Secret":"tok"}"#;
+        call("store", store, &args).expect("store failed");
+        let got: DockerCredential =
+            serde_json::from_str(&call("get", server, &args).unwrap()).unwrap();
+        assert_eq!((got.username.as_str(), got.secret.as_str()), ("ci", "tok"));
+        assert_eq!(
+            call("list", "", &args).unwrap(),
+            r#"{"https://registry.example.com":"ci"}"#
+        );
+
+        call("erase", server, &args).expect("erase failed");
+        assert_eq!(call("list", "", &args).unwrap(), "{}");
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,149 @@
+//! Edit an existing entry of the vault.
+use crate::helpers::structs::{GeneralArgs, PasswordEntry};
+use crate::helpers::{self, errors::FortressError, picker};
+use crate::vault::{EntryUpdate, Vault};
+
+/// Edit an existing entry.
+/// The replaced password is kept in the history of the entry.
+/// ## Parameters:
+/// - `identifier`: The path of the entry to edit. Picked interactively if omitted
+/// - `username`: If provided, the new username.
+/// - `password`: If provided, the new password.
+/// - `generate`: If true, generate a new password.
+/// - `url`: If provided, the new URL of the service.
+/// - `tags`: If not empty, the new tags of the entry.
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn edit(
+    identifier: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    generate: bool,
+    url: Option<String>,
+    tags: Vec<String>,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let mut vault = Vault::with_args(args)?;
+    let identifier = picker::resolve(
+        identifier,
+        vault.entries().iter().map(|item| item.identifier.as_str()),
+    )?;
+    let password = if generate {
+        Some(helpers::generate_password(32))
+    } else {
+        password
+    };
+    let edited = vault
+        .update(
+            &identifier,
+            EntryUpdate {
+                username,
+                password,
+                url,
+                tags,
+            },
+        )?
+        .clone();
+    vault.save()?;
+    println!("{}", edited);
+    Ok(())
+}
+
+/// Apply the changes to an entry, without saving nor printing anything.
+/// Only the provided fields are changed, the replaced password is kept in the history.
+/// ## Parameters:
+/// - `entry`: The entry to edit
+/// - `username`: If provided, the new username.
+/// - `password`: If provided, the new password.
+/// - `url`: If provided, the new URL of the service.
+/// - `tags`: If not empty, the new tags of the entry.
+pub fn apply(
+    entry: &mut PasswordEntry,
+    username: Option<String>,
+    password: Option<String>,
+    url: Option<String>,
+    tags: Vec<String>,
+) {
+    if let Some(username) = username {
+        entry.username = username;
+    }
+    if url.is_some() {
+        entry.url = url;
+    }
+    if !tags.is_empty() {
+        entry.tags = tags;
+    }
+    if let Some(password) = password {
+        entry.set_password(password, helpers::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::errors::FortressError;
+    use crate::helpers::structs::GeneralArgs;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_edit_existing_and_missing() {
+        let path = tmp_path("edit_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        crate::commands::add::add(
+            "edit_id".to_string(),
+            "edit_user".to_string(),
+            Some("edit_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .expect("add failed");
+
+        let res = edit(
+            Some("edit_id".to_string()),
+            Some("new_user".to_string()),
+            Some("new_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        );
+        assert!(res.is_ok());
+        let entries = helpers::load_vault(args.clone()).unwrap().entries;
+        assert_eq!(entries[0].username, "new_user");
+        assert_eq!(entries[0].password, "new_pw");
+        assert_eq!(entries[0].history[0].password, "edit_pw");
+
+        let res_missing = edit(
+            Some("no_id".to_string()),
+            None,
+            None,
+            false,
+            None,
+            Vec::new(),
+            args,
+        );
+        assert!(matches!(res_missing, Err(FortressError::IdNotFound(..))));
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,344 @@
+//! Git credential helper, see `gitcredentials(7)`.
+//!
+//! Git writes `key=value` lines on stdin and reads the credentials on stdout.
+//! A host matches the entry whose URL has the same host, or the entry `<prefix><host>`.
+use crate::commands::remove;
+use crate::helpers::structs::{GeneralArgs, PasswordEntry, VaultData};
+use crate::helpers::{self, errors::FortressError};
+use std::io::{self, BufRead};
+
+/// The attributes of a credential sent by git.
+#[derive(Default)]
+pub struct Credential {
+    pub protocol: Option<String>,
+    pub host: Option<String>,
+    pub path: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Answer a request of git.
+/// Unknown actions and unknown hosts are ignored, as the protocol expects.
+/// ## Parameters:
+/// - `operation`: The action asked by git: `get`, `store` or `erase`
+/// - `prefix`: The prefix of the identifiers of hosts without an entry matching their URL
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn git_credential(
+    operation: String,
+    prefix: String,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let request = parse(io::stdin().lock())?;
+    if let Some(response) = answer(&operation, &request, &prefix, args)? {
+        print!("{}", response);
+    }
+    Ok(())
+}
+
+/// Run an action on the vault.
+/// ## Returns:
+/// A result of the lines to send back to git, if any, or a [`FortressError`]
+fn answer(
+    operation: &str,
+    request: &Credential,
+    prefix: &str,
+    args: GeneralArgs,
+) -> Result<Option<String>, FortressError> {
+    let Some(host) = request.host.as_deref() else {
+        return Ok(None);
+    };
+    let mut decrypted = helpers::load_vault(args.clone())?;
+    let found = find(&decrypted, request, prefix);
+
+    match (operation, found) {
+        ("get", Some(index)) => {
+            let entry = &decrypted.entries[index];
+            log::info!("Git credential sent for {}", entry.identifier);
+            Ok(Some(format!(
+                "username={}\npassword={}\n",
+                entry.username, entry.password
+            )))
+        }
+        ("store", found) => {
+            let (Some(username), Some(password)) = (&request.username, &request.password) else {
+                return Ok(None);
+            };
+            match found {
+                Some(index) => {
+                    let entry = &mut decrypted.entries[index];
+                    entry.username = username.clone();
+                    entry.set_password(password.clone(), helpers::now());
+                }
+                None => {
+                    let identifier = new_identifier(&decrypted, prefix, host, username)?;
+                    decrypted.entries.push(PasswordEntry {
+                        identifier,
+                        username: username.clone(),
+                        password: password.clone(),
+                        url: Some(format!(
+                            "{}://{}",
+                            request.protocol.as_deref().unwrap_or("https"),
+                            host
+                        )),
+                        ..Default::default()
+                    })
+                }
+            }
+            helpers::save_vault(args, &decrypted)?;
+            log::info!("Git credential stored for {}", host);
+            Ok(None)
+        }
+        ("erase", Some(index)) => {
+            let entry = &decrypted.entries[index];
+            if request
+                .password
+                .as_ref()
+                .is_some_and(|password| *password != entry.password)
+            {
+                return Ok(None);
+            }
+            let identifier = entry.identifier.clone();
+            remove::remove_entry(&mut decrypted, identifier, false)?;
+            helpers::save_vault(args, &decrypted)?;
+            Ok(None)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Parse the `key=value` lines sent by git, until an empty line or the end of the input.
+/// ## Parameters:
+/// - `input`: The reader of the request
+/// ## Returns:
+/// A result of the [`Credential`] or a [`FortressError`]
+pub fn parse(input: impl BufRead) -> Result<Credential, FortressError> {
+    let mut credential = Credential::default();
+    for line in input.lines() {
+        let line = line.map_err(FortressError::IoError)?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = Some(value.to_string());
+        match key {
+            "protocol" => credential.protocol = value,
+            "host" => credential.host = value,
+            "path" => credential.path = value,
+            "username" => credential.username = value,
+            "password" => credential.password = value,
+            _ => {}
+        }
+    }
+    Ok(credential)
+}
+
+/// The identifier of a new login: `<prefix><host>`, or `<prefix><host>/<username>` when another username holds it.
+/// ## Parameters:
+/// - `data`: The decrypted vault
+/// - `prefix`: The folder of the logins
+/// - `host`: The host of the remote
+/// - `username`: The username of the login
+/// ## Returns:
+/// A result of the identifier or a [`FortressError`] if both are taken
+fn new_identifier(
+    data: &VaultData,
+    prefix: &str,
+    host: &str,
+    username: &str,
+) -> Result<String, FortressError> {
+    let exists = |identifier: &str| {
+        data.entries
+            .iter()
+            .any(|entry| entry.identifier == identifier)
+    };
+    let identifier = format!("{}{}", prefix, host);
+    if !exists(&identifier) {
+        return Ok(identifier);
+    }
+    let identifier = format!("{}{}/{}", prefix, host, username);
+    if exists(&identifier) {
+        return Err(FortressError::IdAlreadyExists(identifier));
+    }
+    Ok(identifier)
+}
+
+/// Find the entry of a credential: by the host of its URL first, then by identifier,
+/// `<prefix><host>/<path>` when git sends the path, else `<prefix><host>`.
+/// When git knows the username, only entries with this username match.
+fn find(data: &VaultData, request: &Credential, prefix: &str) -> Option<usize> {
+    let host = request.host.as_deref()?;
+    let identifiers: Vec<String> = request
+        .path
+        .iter()
+        .map(|path| format!("{}{}/{}", prefix, host, path.trim_end_matches(".git")))
+        .chain(std::iter::once(format!("{}{}", prefix, host)))
+        .collect();
+    let username_matches = |entry: &PasswordEntry| {
+        request
+            .username
+            .as_ref()
+            .is_none_or(|username| *username == entry.username)
+    };
+    data.entries
+        .iter()
+        .position(|entry| {
+            entry
+                .url
+                .as_deref()
+                .is_some_and(|url| url_matches(url, request.protocol.as_deref(), host))
+                && username_matches(entry)
+        })
+        .or_else(|| {
+            identifiers.iter().find_map(|identifier| {
+                data.entries
+                    .iter()
+                    .position(|entry| entry.identifier == *identifier && username_matches(entry))
+            })
+        })
+}
+
+/// Check if a URL points to a host, and to the protocol if the URL has a scheme.
+pub(crate) fn url_matches(url: &str, protocol: Option<&str>, host: &str) -> bool {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, url),
+    };
+    let authority = rest.split('/').next().unwrap_or_default();
+    let url_host = authority.rsplit('@').next().unwrap_or_default();
+    url_host.eq_ignore_ascii_case(host)
+        && match (scheme, protocol) {
+            (Some(scheme), Some(protocol)) => scheme.eq_ignore_ascii_case(protocol),
+            _ => true,
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    fn request(input: &str) -> Credential {
+        parse(input.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_url_matches() {
+        let credential =
+            request("protocol=https\nhost=git.example.com\nunknown=1\n\nhost=ignored\n");
+        assert_eq!(credential.protocol.as_deref(), Some("https"));
+        assert_eq!(credential.host.as_deref(), Some("git.example.com"));
+        assert!(credential.username.is_none());
+
+        assert!(url_matches(
+            "https://git.example.com/team/repo",
+            Some("https"),
+            "git.example.com"
+        ));
+        assert!(url_matches(
+            "git.example.com",
+            Some("https"),
+            "git.example.com"
+        ));
+        assert!(url_matches(
+            "https://me@git.example.com",
+            None,
+            "git.example.com"
+        ));
+        assert!(!url_matches(
+            "http://git.example.com",
+            Some("https"),
+            "git.example.com"
+        ));
+        assert!(!url_matches(
+            "https://example.com",
+            Some("https"),
+            "git.example.com"
+        ));
+    }
+
+    #[test]
+    fn test_store_get_erase() {
+        let path = tmp_path("git_credential_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+
+        let get = request("protocol=https\nhost=git.example.com\n");
+        assert_eq!(answer("get", &get, "git/", args.clone()).unwrap(), None);
+
+        let store = request("protocol=https\nhost=git.example.com\nusername=me\npassword=token\n");
+        answer("store", &store, "git/", args.clone()).unwrap();
+        let data = helpers::load_vault(args.clone()).unwrap();
+        assert_eq!(data.entries[0].identifier, "git/git.example.com");
+
+        assert_eq!(
+            answer("get", &get, "git/", args.clone())
+                .unwrap()
+                .as_deref(),
+            Some("username=me\npassword=token\n")
+        );
+
+        let stale = request("protocol=https\nhost=git.example.com\nusername=me\npassword=old\n");
+        answer("erase", &stale, "git/", args.clone()).unwrap();
+        assert_eq!(helpers::load_vault(args.clone()).unwrap().entries.len(), 1);
+        answer("erase", &store, "git/", args.clone()).unwrap();
+        assert!(
+            helpers::load_vault(args.clone())
+                .unwrap()
+                .entries
+                .is_empty()
+        );
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_store_second_username() {
+        let path = tmp_path("git_credential_usernames_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+
+        let alice = request("protocol=https\nhost=github.com\nusername=alice\npassword=a\n");
+        let bob = request("protocol=https\nhost=github.com\nusername=bob\npassword=b\n");
+        answer("store", &alice, "git/", args.clone()).unwrap();
+        answer("store", &bob, "git/", args.clone()).unwrap();
+        answer("store", &bob, "git/", args.clone()).unwrap();
+        let data = helpers::load_vault(args.clone()).unwrap();
+        let identifiers: Vec<&str> = data
+            .entries
+            .iter()
+            .map(|entry| entry.identifier.as_str())
+            .collect();
+        assert_eq!(identifiers, vec!["git/github.com", "git/github.com/bob"]);
+
+        let get = request("protocol=https\nhost=github.com\nusername=bob\n");
+        assert_eq!(
+            answer("get", &get, "git/", args.clone())
+                .unwrap()
+                .as_deref(),
+            Some("username=bob\npassword=b\n")
+        );
+        cleanup(&path);
+    }
+}
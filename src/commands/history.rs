@@ -0,0 +1,152 @@
+//! Show and restore the previous passwords of an entry.
+use crate::helpers::structs::GeneralArgs;
+use crate::helpers::{self, errors::FortressError};
+
+/// Display the previous passwords of the specific entry, most recent first.
+/// ## Parameters:
+/// - `identifier`: The path of the entry
+/// - `show`: If true, reveal the previous passwords
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn history(identifier: String, show: bool, args: GeneralArgs) -> Result<(), FortressError> {
+    let decrypted = helpers::load_vault(args)?;
+    match decrypted
+        .entries
+        .iter()
+        .find(|item| item.identifier == identifier)
+    {
+        Some(el) => {
+            println!("{}", el);
+            if el.history.is_empty() {
+                println!("No previous password");
+            }
+            el.history.iter().enumerate().for_each(|(index, old)| {
+                let password = if show { old.password.as_str() } else { "*****" };
+                println!(
+                    "\t{}: '{}' (replaced on {})",
+                    index + 1,
+                    password,
+                    helpers::format_timestamp(old.replaced_at)
+                )
+            });
+            log::info!("History viewed: {}", identifier);
+            Ok(())
+        }
+        None => Err(FortressError::id_not_found(
+            identifier,
+            decrypted
+                .entries
+                .iter()
+                .map(|item| item.identifier.as_str()),
+        )),
+    }
+}
+
+/// Restore a previous password of the specific entry.
+/// The current password becomes the most recent version of the history.
+/// ## Parameters:
+/// - `identifier`: The path of the entry
+/// - `version`: The version to restore, as numbered by [`history`]
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn restore_password(
+    identifier: String,
+    version: usize,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let mut data = helpers::load_vault(args.clone())?;
+    let entry = match data
+        .entries
+        .iter_mut()
+        .find(|item| item.identifier == identifier)
+    {
+        Some(entry) => entry,
+        None => {
+            return Err(FortressError::id_not_found(
+                identifier,
+                data.entries.iter().map(|item| item.identifier.as_str()),
+            ));
+        }
+    };
+    if version == 0 || version > entry.history.len() {
+        return Err(FortressError::VersionNotFound(identifier, version));
+    }
+
+    let restored = entry.history.remove(version - 1);
+    entry.set_password(restored.password, helpers::now());
+    let entry = entry.clone();
+
+    helpers::save_vault(args, &data)?;
+    println!("{}", entry);
+    println!("Password version {} has been restored.", version);
+    log::info!("Restored password version {} of {}", version, identifier);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::errors::FortressError;
+    use crate::helpers::structs::GeneralArgs;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_history_and_restore() {
+        let path = tmp_path("history_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        crate::commands::add::add(
+            "hist_id".to_string(),
+            "hist_user".to_string(),
+            Some("old_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .expect("add failed");
+        crate::commands::edit::edit(
+            Some("hist_id".to_string()),
+            None,
+            Some("new_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .expect("edit failed");
+
+        assert!(history("hist_id".to_string(), true, args.clone()).is_ok());
+        let res_missing = restore_password("hist_id".to_string(), 2, args.clone());
+        assert!(matches!(
+            res_missing,
+            Err(FortressError::VersionNotFound(_, 2))
+        ));
+
+        restore_password("hist_id".to_string(), 1, args.clone()).expect("restore failed");
+        let entries = helpers::load_vault(args).unwrap().entries;
+        assert_eq!(entries[0].password, "old_pw");
+        assert_eq!(entries[0].history.len(), 1);
+        assert_eq!(entries[0].history[0].password, "new_pw");
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,152 @@
+//! Render a template with references to the vault.
+use crate::helpers::structs::{GeneralArgs, VaultData};
+use crate::helpers::{self, errors::FortressError};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+/// Scheme of the references in templates, as in `{{ frtrs://work/db#password }}`.
+const SCHEME: &str = "frtrs://";
+
+/// Replace the references of a template with values of the vault.
+/// Nothing is written if a reference cannot be resolved.
+/// ## Parameters:
+/// - `input`: The path of the template
+/// - `output`: The path of the rendered file, created with mode 0600. Printed if omitted
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn inject(
+    input: String,
+    output: Option<String>,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let template = fs::read_to_string(&input).map_err(FortressError::IoError)?;
+    let decrypted = helpers::load_vault(args)?;
+    let rendered = render(&template, &decrypted)?;
+
+    match output {
+        Some(output) => {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&output)
+                .map_err(FortressError::IoError)?;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+            file.write_all(rendered.as_bytes())?;
+            log::info!("Template {} rendered to {}", input, output);
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Replace every `{{ frtrs://identifier[#field] }}` placeholder of a template.
+/// Other `{{ ... }}` placeholders are kept as is.
+/// ## Parameters:
+/// - `template`: The text of the template
+/// - `data`: The content of the vault
+/// ## Returns:
+/// A result of the rendered text or a [`FortressError`]
+pub fn render(template: &str, data: &VaultData) -> Result<String, FortressError> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + length + 2];
+        rendered.push_str(&rest[..start]);
+        match placeholder[2..length].trim().strip_prefix(SCHEME) {
+            Some(reference) => rendered.push_str(&helpers::lookup(data, reference)?),
+            None => rendered.push_str(placeholder),
+        }
+        rest = &rest[start + length + 2..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::structs::PasswordEntry;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_render_references() {
+        let data = VaultData {
+            entries: vec![PasswordEntry {
+                identifier: "work/db".to_string(),
+                username: "admin".to_string(),
+                password: "db_pw".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let template = "user: {{frtrs://work/db#username}}\npass: {{ frtrs://work/db#password }}\nkeep: {{ other }}\n";
+        assert_eq!(
+            render(template, &data).unwrap(),
+            "user: admin\npass: db_pw\nkeep: {{ other }}\n"
+        );
+        assert_eq!(
+            render("open {{ frtrs://", &data).unwrap(),
+            "open {{ frtrs://"
+        );
+        assert!(matches!(
+            render("{{ frtrs://work/dbb }}", &data),
+            Err(FortressError::IdNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_inject_writes_private_file() {
+        let path = tmp_path("inject_test");
+        let input = tmp_path("inject_tpl");
+        let output = tmp_path("inject_out");
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        crate::commands::add::add(
+            "work/db".to_string(),
+            "admin".to_string(),
+            Some("db_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .expect("add failed");
+
+        fs::write(&input, "pass={{ frtrs://work/db }}").unwrap();
+        inject(input.clone(), Some(output.clone()), args.clone()).expect("inject failed");
+        assert_eq!(fs::read_to_string(&output).unwrap(), "pass=db_pw");
+        let mode = fs::metadata(&output).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        cleanup(&output);
+        fs::write(&input, "pass={{ frtrs://work/missing }}").unwrap();
+        let res = inject(input.clone(), Some(output.clone()), args);
+        assert!(res.is_err());
+        assert!(!std::path::Path::new(&output).exists());
+
+        cleanup(&path);
+        cleanup(&input);
+    }
+}
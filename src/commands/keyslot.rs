@@ -0,0 +1,205 @@
+//! Manage the key slots of the vault, LUKS-style.
+//! The data key is drawn once and wrapped in each slot, from a password or a keyfile.
+//! Adding or removing a slot only rewrites the header: the data is not encrypted again
+//! and the other slots are unchanged.
+use crate::commands::recipients;
+use crate::crypto::{self, KeySlot, VaultHeader, VaultKey};
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::GeneralArgs;
+use crate::vault::MIN_STRENGTH;
+use password_strength::estimate_strength;
+use rpassword::prompt_password;
+use std::fs;
+use zeroize::Zeroize;
+
+/// List the key slots of the vault, with their index.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn list(args: GeneralArgs) -> Result<(), FortressError> {
+    let slots = recipients::read_slots(&args.file)?;
+    if slots.is_empty() {
+        println!("The key of the vault is derived from the master password, it has no key slot.");
+    }
+    slots
+        .iter()
+        .enumerate()
+        .for_each(|(index, slot)| println!("{}: {}", index, describe(slot)));
+    Ok(())
+}
+
+/// Wrap the data key in a new slot, for a new password or a keyfile.
+/// The new password is asked twice on the terminal.
+/// ## Parameters:
+/// - `keyfile`: If provided, the keyfile opening the new slot
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn add(keyfile: Option<String>, args: GeneralArgs) -> Result<(), FortressError> {
+    let index = match keyfile {
+        Some(path) => {
+            let mut content = fs::read(&path).map_err(FortressError::IoError)?;
+            let index = push_slot(&args, |key| KeySlot::keyfile(key, &content));
+            content.zeroize();
+            index?
+        }
+        None => {
+            let mut password =
+                prompt_password("New slot password: ").map_err(FortressError::IoError)?;
+            let mut confirmation =
+                prompt_password("Confirm the slot password: ").map_err(FortressError::IoError)?;
+            let index = if password != confirmation {
+                Err(FortressError::InvalidKey(
+                    "the passwords do not match".to_string(),
+                ))
+            } else if estimate_strength(&password) <= MIN_STRENGTH {
+                Err(FortressError::WeakPassword)
+            } else {
+                push_slot(&args, |key| KeySlot::password(key, &password))
+            };
+            password.zeroize();
+            confirmation.zeroize();
+            index?
+        }
+    };
+    log::info!("Key slot {} added", index);
+    println!("Key slot {} has been added.", index);
+    Ok(())
+}
+
+/// Remove a key slot. The data key is unchanged: use `recipients remove` to change it.
+/// ## Parameters:
+/// - `index`: The index of the slot, as listed
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn remove(index: usize, args: GeneralArgs) -> Result<(), FortressError> {
+    let (encrypted, _) = recipients::data_key(&args)?;
+    let (header, _) = VaultHeader::parse(&encrypted)?;
+    let mut slots = header.slots;
+    if index >= slots.len() {
+        return Err(FortressError::InvalidKey(format!(
+            "the vault has no key slot {}",
+            index
+        )));
+    }
+    let removed = slots.remove(index);
+    if slots
+        .iter()
+        .all(|slot| matches!(slot, KeySlot::Recovery { .. }))
+    {
+        return Err(FortressError::Unsupported(
+            "The last key slot opening the vault cannot be removed.".to_string(),
+        ));
+    }
+    fs::write(&args.file, crypto::replace_slots(&encrypted, &slots)?)
+        .map_err(FortressError::IoError)?;
+    log::info!("Key slot {} removed", index);
+    println!(
+        "Key slot {} ({}) has been removed.",
+        index,
+        describe(&removed)
+    );
+    Ok(())
+}
+
+/// Add a slot wrapping the data key at the end of the header.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// - `slot`: Wrap the data key in the new slot
+/// ## Returns:
+/// A result of the index of the new slot or a [`FortressError`]
+fn push_slot(
+    args: &GeneralArgs,
+    slot: impl FnOnce(&VaultKey) -> Result<KeySlot, FortressError>,
+) -> Result<usize, FortressError> {
+    let (encrypted, key) = recipients::data_key(args)?;
+    let (header, _) = VaultHeader::parse(&encrypted)?;
+    let mut slots = header.slots;
+    slots.push(slot(&key)?);
+    fs::write(&args.file, crypto::replace_slots(&encrypted, &slots)?)
+        .map_err(FortressError::IoError)?;
+    Ok(slots.len() - 1)
+}
+
+/// A short description of a slot.
+fn describe(slot: &KeySlot) -> String {
+    match slot {
+        KeySlot::Password { .. } => "password".to_string(),
+        KeySlot::Keyfile { .. } => "keyfile".to_string(),
+        KeySlot::Recipient { recipient, .. } => format!("recipient {}", recipient),
+        KeySlot::Recovery { .. } => "recovery key".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_key_slots() {
+        let path = tmp_path("keyslot_test");
+        let keyfile = tmp_path("keyslot_keyfile");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .unwrap();
+        crate::commands::add::add(
+            "team/db".to_string(),
+            "admin".to_string(),
+            Some("pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .unwrap();
+
+        let index = push_slot(&args, |key| KeySlot::password(key, "Br3ak-Gl4ss#Password")).unwrap();
+        assert_eq!(index, 1);
+        fs::write(&keyfile, b"random keyfile content").unwrap();
+        add(Some(keyfile.clone()), args.clone()).unwrap();
+        let before = fs::read(&path).unwrap();
+        let (header, ciphertext) = VaultHeader::parse(&before).unwrap();
+        assert_eq!(header.slots.len(), 3);
+
+        let key = VaultKey::unlock_with_keyfile(&before, b"random keyfile content").unwrap();
+        assert_eq!(key.salt, header.salt);
+        assert!(VaultKey::unlock_with_keyfile(&before, b"other").is_err());
+
+        remove(0, args.clone()).unwrap();
+        let after = fs::read(&path).unwrap();
+        let (rotated, rotated_ciphertext) = VaultHeader::parse(&after).unwrap();
+        assert_eq!(rotated.slots.len(), 2);
+        assert_eq!(rotated_ciphertext, ciphertext);
+        assert!(helpers::load_vault(args).is_err());
+
+        let args = GeneralArgs::new(path.clone(), "Br3ak-Gl4ss#Password".to_string());
+        assert_eq!(helpers::load_vault(args.clone()).unwrap().entries.len(), 1);
+        remove(1, args.clone()).unwrap();
+        assert!(matches!(
+            remove(0, args.clone()),
+            Err(FortressError::Unsupported(_))
+        ));
+        assert!(matches!(remove(3, args), Err(FortressError::InvalidKey(_))));
+        cleanup(&path);
+        cleanup(&keyfile);
+    }
+}
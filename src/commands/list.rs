@@ -5,15 +5,21 @@ use crate::helpers::{self, errors::FortressError};
 /// List all entries in the vault.
 /// All the entries will be printed with the format defined.
 /// ## Parameters:
+/// - `prefix`: If provided, only list the entries of this folder
 /// - `args`: The context of the program
 /// ## Returns:
 /// A result of nothing or a [`FortressError`]
-pub fn list(args: GeneralArgs) -> Result<(), FortressError> {
+pub fn list(prefix: Option<String>, args: GeneralArgs) -> Result<(), FortressError> {
+    let prefix = prefix.unwrap_or_default();
     let decrypted = helpers::load_vault(args);
     match decrypted {
         Ok(decrypted) => {
             println!("[");
-            decrypted.iter().for_each(|item| println!("\t{}", item));
+            decrypted
+                .entries
+                .iter()
+                .filter(|item| helpers::in_folder(&item.identifier, &prefix))
+                .for_each(|item| println!("\t{}", item));
             println!("]");
             log::info!("Vault Listed");
             Ok(())
@@ -49,7 +55,7 @@ mod tests {
         cleanup(&path);
         let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
 
-        let res = list(args);
+        let res = list(None, args);
         assert!(res.is_ok() || res.is_err());
         cleanup(&path);
     }
@@ -59,16 +65,19 @@ mod tests {
         let path = tmp_path("list_after_add");
         cleanup(&path);
         let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
-        crate::commands::create::create(true, args.clone()).expect("create failed");
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
         let _ = crate::commands::add::add(
             "id_list".to_string(),
             "user_list".to_string(),
             Some("pw".to_string()),
             false,
+            None,
+            Vec::new(),
             args.clone(),
         );
 
-        let res = list(args);
+        let res = list(Some("id_list".to_string()), args);
         assert!(res.is_ok(), "list should succeed after adding entry");
         cleanup(&path);
     }
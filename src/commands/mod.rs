@@ -1,7 +1,142 @@
 //! The commands of the program.
 pub mod add;
+pub mod agent;
+pub mod check;
+pub mod convert;
 pub mod copy;
 pub mod create;
+pub mod docker_credential;
+pub mod edit;
+pub mod git_credential;
+pub mod history;
+pub mod inject;
+pub mod keyslot;
 pub mod list;
+pub mod mv;
+pub mod native_host;
+pub mod recipients;
+pub mod recovery;
 pub mod remove;
+pub mod run;
+pub mod search;
+pub mod secret_service;
+pub mod serve;
+pub mod share;
+pub mod shell;
+pub mod trash;
+pub mod tree;
+pub mod tui;
 pub mod view;
+
+use crate::helpers::cli::{
+    Commands, KeyslotCommands, RecipientsCommands, RecoveryCommands, TrashCommands,
+};
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::GeneralArgs;
+
+/// Run a parsed command.
+/// ## Parameters:
+/// - `command`: The command to run
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn run(command: Commands, args: GeneralArgs) -> Result<(), FortressError> {
+    match command {
+        Commands::Create { force, cipher } => create::create(force, cipher, args),
+        Commands::Add {
+            identifier,
+            username,
+            password,
+            generate,
+            url,
+            tags,
+        } => add::add(
+            identifier,
+            username.unwrap_or("<empty>".to_string()),
+            password,
+            generate,
+            url,
+            tags,
+            args,
+        ),
+        Commands::List { prefix } => list::list(prefix, args),
+        Commands::Search { query } => search::search(query, args),
+        Commands::Tree { prefix } => tree::tree(prefix, args),
+        Commands::Edit {
+            identifier,
+            username,
+            password,
+            generate,
+            url,
+            tags,
+        } => edit::edit(identifier, username, password, generate, url, tags, args),
+        Commands::Mv {
+            source,
+            destination,
+        } => mv::mv(source, destination, args),
+        Commands::History { identifier, show } => history::history(identifier, show, args),
+        Commands::RestorePassword {
+            identifier,
+            version,
+        } => history::restore_password(identifier, version, args),
+        Commands::Check {} => check::check(args),
+        Commands::Trash { command } => match command {
+            TrashCommands::List {} => trash::list(args),
+            TrashCommands::Restore { identifier } => trash::restore(identifier, args),
+            TrashCommands::Empty {} => trash::empty(args),
+            TrashCommands::Retention { days } => trash::retention(days, args),
+        },
+        Commands::Run { env, command } => run::run(env, command, args),
+        Commands::Inject { input, output } => inject::inject(input, output, args),
+        Commands::GitCredential { operation, prefix } => {
+            git_credential::git_credential(operation, prefix, args)
+        }
+        Commands::NativeHost { caller } => native_host::native_host(caller, args),
+        Commands::Serve {
+            socket,
+            port,
+            tokens,
+        } => serve::serve(socket, port, tokens, args),
+        Commands::SecretService {} => secret_service::secret_service(args),
+        Commands::Share {
+            identifier,
+            to,
+            output,
+        } => share::share(identifier, to, output, args),
+        Commands::Receive { file, key, rename } => share::receive(file, key, rename, args),
+        Commands::Recipients { command } => match command {
+            RecipientsCommands::List {} => recipients::list(args),
+            RecipientsCommands::Add {
+                recipients,
+                no_password,
+                force,
+            } => recipients::add(recipients, no_password, force, args),
+            RecipientsCommands::Remove { recipients, force } => {
+                recipients::remove(recipients, force, args)
+            }
+            RecipientsCommands::Keygen { output } => recipients::keygen(output, args),
+        },
+        Commands::Keyslot { command } => match command {
+            KeyslotCommands::List {} => keyslot::list(args),
+            KeyslotCommands::Add { keyfile } => keyslot::add(keyfile, args),
+            KeyslotCommands::Remove { index } => keyslot::remove(index, args),
+        },
+        Commands::Recovery { command } => match command {
+            RecoveryCommands::Split { shares, threshold } => {
+                recovery::split(shares, threshold, args)
+            }
+            RecoveryCommands::Unlock {} => recovery::unlock(args),
+        },
+        Commands::Convert { cipher } => convert::convert(cipher, args),
+        Commands::Agent { ttl } => agent::agent(ttl, args),
+        Commands::Lock {} => agent::lock(args),
+        Commands::Shell { lock_after } => shell::shell(lock_after, args),
+        Commands::Tui { lock_after } => tui::tui(lock_after, args),
+        Commands::Copy { identifier } => copy::copy(identifier, args),
+        Commands::View { identifier } => view::view(identifier, args),
+        Commands::Remove {
+            identifier,
+            permanent,
+        } => remove::remove(identifier, permanent, args),
+    }
+}
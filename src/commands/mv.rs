@@ -0,0 +1,177 @@
+//! Rename or move entries of the vault.
+use crate::helpers::structs::{GeneralArgs, PasswordEntry};
+use crate::helpers::{self, errors::FortressError};
+use std::collections::HashSet;
+
+/// Rename an entry, or every entry of a folder.
+/// A `source` ending with `/` is a folder: every identifier starting with it is moved under `destination`.
+/// Everything else in the entries is left unchanged.
+/// ## Parameters:
+/// - `source`: The path of the entry or folder to move
+/// - `destination`: The new path of the entry or folder
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn mv(source: String, destination: String, args: GeneralArgs) -> Result<(), FortressError> {
+    let mut decrypted = helpers::load_vault(args.clone())?;
+    let moved = rename(&mut decrypted.entries, &source, &destination)?;
+
+    helpers::save_vault(args, &decrypted)?;
+    moved.iter().for_each(|(old, new)| {
+        log::info!("Entry moved: {} -> {}", old, new);
+        println!("'{}' -> '{}'", old, new);
+    });
+    Ok(())
+}
+
+/// Compute and apply the new identifiers.
+/// Nothing is modified if a collision is detected.
+/// ## Parameters:
+/// - `entries`: The entries of the vault
+/// - `source`: The path of the entry or folder to move
+/// - `destination`: The new path of the entry or folder
+/// ## Returns:
+/// A result of the list of `(old, new)` identifiers or a [`FortressError`]
+pub fn rename(
+    entries: &mut [PasswordEntry],
+    source: &str,
+    destination: &str,
+) -> Result<Vec<(String, String)>, FortressError> {
+    let renames: Vec<(usize, String)> = if source.ends_with('/') {
+        let destination = if destination.is_empty() || destination.ends_with('/') {
+            destination.to_string()
+        } else {
+            format!("{}/", destination)
+        };
+        entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                item.identifier
+                    .strip_prefix(source)
+                    .map(|rest| (index, format!("{}{}", destination, rest)))
+            })
+            .collect()
+    } else {
+        entries
+            .iter()
+            .position(|item| item.identifier == source)
+            .map(|index| (index, destination.to_string()))
+            .into_iter()
+            .collect()
+    };
+    if renames.is_empty() {
+        return Err(FortressError::id_not_found(
+            source.to_string(),
+            entries.iter().map(|item| item.identifier.as_str()),
+        ));
+    }
+
+    let moved: HashSet<usize> = renames.iter().map(|(index, _)| *index).collect();
+    let mut taken: HashSet<&str> = entries
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !moved.contains(index))
+        .map(|(_, item)| item.identifier.as_str())
+        .collect();
+    for (_, new) in &renames {
+        if new.is_empty() || !taken.insert(new.as_str()) {
+            return Err(FortressError::IdAlreadyExists(new.clone()));
+        }
+    }
+
+    Ok(renames
+        .into_iter()
+        .map(|(index, new)| {
+            let old = std::mem::replace(&mut entries[index].identifier, new.clone());
+            (old, new)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::errors::FortressError;
+    use crate::helpers::structs::GeneralArgs;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    fn entries(identifiers: &[&str]) -> Vec<PasswordEntry> {
+        identifiers
+            .iter()
+            .map(|id| PasswordEntry {
+                identifier: id.to_string(),
+                username: "user".to_string(),
+                password: format!("pw_{}", id),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rename_folder() {
+        let mut vault = entries(&["work/github", "work/aws/prod", "workshop", "home"]);
+        let moved = rename(&mut vault, "work/", "job").expect("rename failed");
+        assert_eq!(moved.len(), 2);
+        assert_eq!(vault[0].identifier, "job/github");
+        assert_eq!(vault[0].password, "pw_work/github");
+        assert_eq!(vault[1].identifier, "job/aws/prod");
+        assert_eq!(vault[2].identifier, "workshop");
+    }
+
+    #[test]
+    fn test_rename_collisions() {
+        let mut vault = entries(&["work/github", "job/github", "home"]);
+        let res = rename(&mut vault, "work/", "job/");
+        assert!(matches!(res, Err(FortressError::IdAlreadyExists(_))));
+        assert_eq!(vault[0].identifier, "work/github");
+
+        let res = rename(&mut vault, "home", "job/github");
+        assert!(matches!(res, Err(FortressError::IdAlreadyExists(_))));
+
+        let res = rename(&mut vault, "missing/", "job/");
+        assert!(matches!(res, Err(FortressError::IdNotFound(..))));
+
+        assert!(rename(&mut vault, "home", "home").is_ok());
+    }
+
+    #[test]
+    fn test_mv_entry() {
+        let path = tmp_path("mv_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        crate::commands::add::add(
+            "mv_id".to_string(),
+            "mv_user".to_string(),
+            Some("mv_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .expect("add failed");
+
+        mv("mv_id".to_string(), "moved/mv_id".to_string(), args.clone()).expect("mv failed");
+        let entries = helpers::load_vault(args).unwrap().entries;
+        assert_eq!(entries[0].identifier, "moved/mv_id");
+        assert_eq!(entries[0].username, "mv_user");
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,378 @@
+//! Browser native messaging host, see the Chrome and Firefox `nativeMessaging` documentation.
+//!
+//! Each message is JSON preceded by its length, as a 32-bit integer in native byte order.
+//! The browser starts the host for each connection and closes stdin when done.
+//! An entry matches a page when its URL has the same host and scheme.
+//!
+//! The browser starts the host without a terminal, so the master password cannot be asked:
+//! the agent must be running first. Without its key, every request is answered with
+//! `{"ok":false,"error":"vault locked"}`.
+use crate::commands::git_credential::url_matches;
+use crate::helpers::structs::{GeneralArgs, PasswordEntry};
+use crate::helpers::{self, errors::FortressError};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+
+/// Maximum size of a message, the limit of the browsers for the messages sent by the host.
+const MAX_MESSAGE: usize = 1024 * 1024;
+
+/// Prefix of the identifiers of the entries saved without one.
+const PREFIX: &str = "web/";
+
+/// The error sent for every request when no key is available.
+const LOCKED: &str = "vault locked";
+
+/// A request of the extension.
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum Request {
+    /// List the entries matching a page, without their passwords.
+    Lookup { url: String },
+    /// Get the credentials of an entry.
+    Get { identifier: String },
+    /// Save credentials, updating the entry of the same page and username if any.
+    Save {
+        url: String,
+        username: String,
+        password: String,
+        identifier: Option<String>,
+    },
+}
+
+/// An entry as sent to the extension.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Login {
+    pub identifier: String,
+    pub username: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// The answer to a request.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Response {
+    Logins { ok: bool, entries: Vec<Login> },
+    Login { ok: bool, entry: Login },
+    Error { ok: bool, error: String },
+}
+
+/// Answer the messages of the browser until it closes the connection.
+/// The browser passes the origin of the extension as argument, which is only logged.
+/// ## Parameters:
+/// - `caller`: The arguments given by the browser
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn native_host(caller: Vec<String>, args: GeneralArgs) -> Result<(), FortressError> {
+    log::info!("Native messaging host started by {}", caller.join(" "));
+    serve(io::stdin().lock(), io::stdout().lock(), args)
+}
+
+/// Answer the messages of a reader on a writer, until the end of the input.
+/// Without a key nor a master password, every message is answered with a locked error.
+/// ## Parameters:
+/// - `input`: The reader of the messages
+/// - `output`: The writer of the answers
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn serve(
+    mut input: impl Read,
+    mut output: impl Write,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let locked = args.key.is_none() && args.password.is_empty();
+    while let Some(message) = read_message(&mut input)? {
+        let response = match serde_json::from_slice::<Request>(&message) {
+            _ if locked => Err(LOCKED.to_string()),
+            Ok(request) => answer(request, args.clone()).map_err(|e| e.to_string()),
+            Err(e) => Err(FortressError::SerializationError(e).to_string()),
+        }
+        .unwrap_or_else(|error| Response::Error { ok: false, error });
+        write_message(&mut output, &response)?;
+    }
+    Ok(())
+}
+
+/// Run a request on the vault.
+/// ## Returns:
+/// A result of the [`Response`] or a [`FortressError`]
+fn answer(request: Request, args: GeneralArgs) -> Result<Response, FortressError> {
+    let mut decrypted = helpers::load_vault(args.clone())?;
+    match request {
+        Request::Lookup { url } => {
+            let entries = decrypted
+                .entries
+                .iter()
+                .filter(|entry| matches(entry, &url))
+                .map(|entry| login(entry, false))
+                .collect();
+            Ok(Response::Logins { ok: true, entries })
+        }
+        Request::Get { identifier } => {
+            let entry = decrypted
+                .entries
+                .iter()
+                .find(|entry| entry.identifier == identifier)
+                .ok_or_else(|| {
+                    FortressError::id_not_found(
+                        identifier.clone(),
+                        decrypted.entries.iter().map(|e| e.identifier.as_str()),
+                    )
+                })?;
+            log::info!("Native host sent the credentials of {}", identifier);
+            Ok(Response::Login {
+                ok: true,
+                entry: login(entry, true),
+            })
+        }
+        Request::Save {
+            url,
+            username,
+            password,
+            identifier,
+        } => {
+            let found = match &identifier {
+                Some(identifier) => decrypted
+                    .entries
+                    .iter()
+                    .position(|entry| entry.identifier == *identifier),
+                None => decrypted
+                    .entries
+                    .iter()
+                    .position(|entry| entry.username == username && matches(entry, &url)),
+            };
+            let index = match found {
+                Some(index) => {
+                    let entry = &mut decrypted.entries[index];
+                    entry.username = username;
+                    entry.set_password(password, helpers::now());
+                    if entry.url.is_none() {
+                        entry.url = Some(url);
+                    }
+                    index
+                }
+                None => {
+                    let identifier =
+                        identifier.unwrap_or_else(|| format!("{}{}", PREFIX, split_url(&url).1));
+                    if decrypted.entries.iter().any(|e| e.identifier == identifier) {
+                        return Err(FortressError::IdAlreadyExists(identifier));
+                    }
+                    decrypted.entries.push(PasswordEntry {
+                        identifier,
+                        username,
+                        password,
+                        url: Some(url),
+                        ..Default::default()
+                    });
+                    decrypted.entries.len() - 1
+                }
+            };
+            helpers::save_vault(args, &decrypted)?;
+            let entry = &decrypted.entries[index];
+            log::info!("Native host saved {}", entry.identifier);
+            Ok(Response::Login {
+                ok: true,
+                entry: login(entry, false),
+            })
+        }
+    }
+}
+
+/// Check if the URL of an entry matches the host and the scheme of a page.
+fn matches(entry: &PasswordEntry, url: &str) -> bool {
+    let (scheme, host) = split_url(url);
+    !host.is_empty()
+        && entry
+            .url
+            .as_deref()
+            .is_some_and(|entry_url| url_matches(entry_url, scheme, host))
+}
+
+/// Split the scheme and the host of a URL.
+fn split_url(url: &str) -> (Option<&str>, &str) {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, url),
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    (scheme, authority.rsplit('@').next().unwrap_or_default())
+}
+
+/// An entry as sent to the extension, with its password or not.
+fn login(entry: &PasswordEntry, password: bool) -> Login {
+    Login {
+        identifier: entry.identifier.clone(),
+        username: entry.username.clone(),
+        password: password.then(|| entry.password.clone()),
+        url: entry.url.clone(),
+    }
+}
+
+/// Read a message, or nothing at the end of the input.
+/// ## Parameters:
+/// - `input`: The reader of the messages
+/// ## Returns:
+/// A result of the content of the message, if any, or a [`FortressError`]
+pub fn read_message(input: &mut impl Read) -> Result<Option<Vec<u8>>, FortressError> {
+    let mut length = [0u8; 4];
+    match input.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(FortressError::IoError(e)),
+    }
+    let length = u32::from_ne_bytes(length) as usize;
+    if length > MAX_MESSAGE {
+        return Err(FortressError::Unsupported(format!(
+            "messages are limited to {} bytes",
+            MAX_MESSAGE
+        )));
+    }
+    let mut message = vec![0u8; length];
+    input
+        .read_exact(&mut message)
+        .map_err(FortressError::IoError)?;
+    Ok(Some(message))
+}
+
+/// Write a message, preceded by its length.
+/// ## Parameters:
+/// - `output`: The writer of the messages
+/// - `message`: The value to send
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn write_message(
+    output: &mut impl Write,
+    message: &impl Serialize,
+) -> Result<(), FortressError> {
+    let message = serde_json::to_vec(message)?;
+    output
+        .write_all(&(message.len() as u32).to_ne_bytes())
+        .and_then(|_| output.write_all(&message))
+        .and_then(|_| output.flush())
+        .map_err(FortressError::IoError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    /// Send messages to the host and read its answers.
+    fn exchange(messages: &[serde_json::Value], args: GeneralArgs) -> Vec<Response> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = Vec::new();
+        serve(input.as_slice(), &mut output, args).unwrap();
+        let mut reader = output.as_slice();
+        let mut responses = Vec::new();
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            responses.push(serde_json::from_slice(&message).unwrap());
+        }
+        responses
+    }
+
+    #[test]
+    fn test_framing_and_split_url() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &json!({"action": "lookup"})).unwrap();
+        assert_eq!(&buffer[..4], &19u32.to_ne_bytes());
+        let mut reader = buffer.as_slice();
+        assert_eq!(
+            read_message(&mut reader).unwrap().unwrap(),
+            br#"{"action":"lookup"}"#.to_vec()
+        );
+        assert!(read_message(&mut reader).unwrap().is_none());
+
+        let too_long = ((MAX_MESSAGE + 1) as u32).to_ne_bytes();
+        assert!(read_message(&mut too_long.as_slice()).is_err());
+
+        assert_eq!(
+            split_url("https://me@example.com:8443/login?next=/"),
+            (Some("https"), "example.com:8443")
+        );
+        assert_eq!(split_url("example.com"), (None, "example.com"));
+    }
+
+    #[test]
+    fn test_save_lookup_get() {
+        let path = tmp_path("native_host_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+
+        let responses = exchange(
+            &[
+                json!({"action": "save", "url": "https://example.com/login", "username": "me", "password": "pw1"}),
+                json!({"action": "save", "url": "https://example.com/account", "username": "me", "password": "pw2"}),
+                json!({"action": "lookup", "url": "https://example.com/other"}),
+                json!({"action": "lookup", "url": "http://example.com/"}),
+                json!({"action": "get", "identifier": "web/example.com"}),
+                json!({"action": "get", "identifier": "missing"}),
+                json!({"action": "unknown"}),
+            ],
+            args.clone(),
+        );
+        let saved = Login {
+            identifier: "web/example.com".to_string(),
+            username: "me".to_string(),
+            password: None,
+            url: Some("https://example.com/login".to_string()),
+        };
+        assert_eq!(
+            responses[1],
+            Response::Login {
+                ok: true,
+                entry: saved
+            }
+        );
+        assert!(matches!(&responses[2], Response::Logins { entries, .. } if entries.len() == 1));
+        assert!(matches!(&responses[3], Response::Logins { entries, .. } if entries.is_empty()));
+        assert!(
+            matches!(&responses[4], Response::Login { entry, .. } if entry.password.as_deref() == Some("pw2"))
+        );
+        assert!(matches!(&responses[5], Response::Error { ok: false, .. }));
+        assert!(matches!(&responses[6], Response::Error { ok: false, .. }));
+
+        let data = helpers::load_vault(args).unwrap();
+        assert_eq!(data.entries.len(), 1);
+        assert_eq!(data.entries[0].history.len(), 1);
+
+        let locked = exchange(
+            &[
+                json!({"action": "get", "identifier": "web/example.com"}),
+                json!({"action": "unknown"}),
+            ],
+            GeneralArgs::new(path.clone(), String::new()),
+        );
+        assert_eq!(locked.len(), 2);
+        assert!(locked.iter().all(|response| *response
+            == Response::Error {
+                ok: false,
+                error: LOCKED.to_string()
+            }));
+        cleanup(&path);
+    }
+}
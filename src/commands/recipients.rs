@@ -0,0 +1,403 @@
+//! Manage the public keys that can open the vault besides the master password.
+//! A new recipient gets a slot wrapping the current data key. When a recipient is removed, a new
+//! data key is drawn and wrapped again for the password and every other recipient, so a removed
+//! recipient cannot open the next versions of the vault.
+use crate::crypto::{self, Identity, KeySlot, Recipient, VaultHeader, VaultKey};
+use crate::helpers::structs::GeneralArgs;
+use crate::helpers::{self, errors::FortressError};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// Generate a secret key and write it to a file readable only by the current user.
+/// The public key is printed, to be given to the owners of the vaults.
+/// ## Parameters:
+/// - `output`: The path of the new key file, which must not exist
+/// - `_args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn keygen(output: String, _args: GeneralArgs) -> Result<(), FortressError> {
+    let identity = Identity::generate();
+    let recipient = identity.recipient();
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&output)
+        .map_err(FortressError::IoError)?;
+    writeln!(file, "# public key: {}\n{}", recipient, identity).map_err(FortressError::IoError)?;
+    log::info!("Generated the key of {} in {}", recipient, output);
+    println!("Public key: {}", recipient);
+    Ok(())
+}
+
+/// List who can open the vault.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn list(args: GeneralArgs) -> Result<(), FortressError> {
+    let slots = read_slots(&args.file)?;
+    if slots.is_empty() {
+        println!("master password");
+    }
+    slots.iter().for_each(|slot| match slot {
+        KeySlot::Password { .. } => println!("master password"),
+        KeySlot::Recipient { recipient, .. } => println!("{}", recipient),
+        KeySlot::Keyfile { .. } => println!("keyfile"),
+        KeySlot::Recovery { .. } => println!("recovery key"),
+    });
+    Ok(())
+}
+
+/// Wrap the data key for new recipients. The data key is unchanged and only the header is rewritten,
+/// unless the master password no longer opens the vault.
+/// ## Parameters:
+/// - `recipients`: The public keys to add
+/// - `no_password`: If true, the master password no longer opens the vault
+/// - `force`: If true, drop the slots that cannot wrap a new data key
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn add(
+    recipients: Vec<String>,
+    no_password: bool,
+    force: bool,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let slots = read_slots(&args.file)?;
+    let previous = slot_recipients(&slots);
+    let mut kept = previous.clone();
+    for recipient in recipients {
+        let recipient: Recipient = recipient.parse()?;
+        if !kept.contains(&recipient) {
+            kept.push(recipient);
+        }
+    }
+    if no_password && has_password(&slots) {
+        rewrap(&kept, false, force, args)?;
+    } else {
+        let (encrypted, key) = data_key(&args)?;
+        let (header, _) = VaultHeader::parse(&encrypted)?;
+        let mut slots = header.slots;
+        for recipient in &kept[previous.len()..] {
+            slots.push(KeySlot::recipient(&key, recipient)?);
+        }
+        fs::write(&args.file, crypto::replace_slots(&encrypted, &slots)?)
+            .map_err(FortressError::IoError)?;
+        log::info!(
+            "Data key wrapped for {} new recipient(s)",
+            kept.len() - previous.len()
+        );
+    }
+    println!("The vault can be opened by {} recipient(s).", kept.len());
+    Ok(())
+}
+
+/// Stop wrapping the data key for recipients.
+/// ## Parameters:
+/// - `recipients`: The public keys to remove
+/// - `force`: If true, drop the slots that cannot wrap a new data key
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn remove(
+    recipients: Vec<String>,
+    force: bool,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let slots = read_slots(&args.file)?;
+    let mut kept = slot_recipients(&slots);
+    for recipient in recipients {
+        let recipient: Recipient = recipient.parse()?;
+        if !kept.contains(&recipient) {
+            return Err(FortressError::InvalidKey(format!(
+                "`{}` is not a recipient of the vault",
+                recipient
+            )));
+        }
+        kept.retain(|other| *other != recipient);
+    }
+    rewrap(&kept, has_password(&slots), force, args)?;
+    println!("The vault can be opened by {} recipient(s).", kept.len());
+    Ok(())
+}
+
+/// Encrypt the vault with a new data key, wrapped for the password and the recipients.
+/// The cipher of the vault is kept. The keyfiles, the other passwords and the recovery key cannot wrap
+/// the new data key, so the vault is left unchanged if it has such slots, unless they are dropped with `force`.
+/// ## Parameters:
+/// - `recipients`: The public keys that open the vault
+/// - `password`: If true, the master password opens the vault
+/// - `force`: If true, drop the slots that cannot wrap the new data key
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn rewrap(
+    recipients: &[Recipient],
+    password: bool,
+    force: bool,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    if !password && recipients.is_empty() {
+        return Err(FortressError::Unsupported(
+            "The vault must be opened by the master password or at least one recipient."
+                .to_string(),
+        ));
+    }
+    if password && args.password.is_empty() {
+        return Err(FortressError::Unsupported(
+            "The master password is needed to wrap the new data key.".to_string(),
+        ));
+    }
+    let data = helpers::load_vault(args.clone())?;
+    let encrypted = fs::read(&args.file)?;
+    let (header, _) = VaultHeader::parse(&encrypted)?;
+    let previous = header.slots;
+    // The first password slot is the master password, kept or removed on purpose.
+    let master = previous
+        .iter()
+        .any(|slot| matches!(slot, KeySlot::Password { .. }));
+    let dropped = previous
+        .iter()
+        .filter(|slot| !matches!(slot, KeySlot::Recipient { .. }))
+        .count()
+        - master as usize;
+    let recovery = previous
+        .iter()
+        .any(|slot| matches!(slot, KeySlot::Recovery { .. }));
+    if dropped > 0 && !force {
+        return Err(FortressError::Unsupported(format!(
+            "{} keyfile, recovery or other password slot(s) cannot wrap a new data key, use --force to drop them.",
+            dropped
+        )));
+    }
+
+    let key = VaultKey::random();
+    let mut slots = Vec::new();
+    if password {
+        slots.push(KeySlot::password(&key, &args.password)?);
+    }
+    for recipient in recipients {
+        slots.push(KeySlot::recipient(&key, recipient)?);
+    }
+    let encrypted = crypto::encrypt_database_with_slots(&data, &key, header.cipher, &slots)?;
+    fs::write(&args.file, encrypted).map_err(FortressError::IoError)?;
+    log::info!("Data key wrapped for {} recipient(s)", recipients.len());
+    if dropped > 0 {
+        println!(
+            "{} key slot(s) have been dropped, add them back with 'frtrs keyslot add'.",
+            dropped
+        );
+    }
+    if recovery {
+        println!("The recovery shares no longer open the vault, run 'frtrs recovery split' again.");
+    }
+    Ok(())
+}
+
+/// Read the key slots in the header of the vault. Empty if the key is derived from the master password.
+pub(crate) fn read_slots(file: &str) -> Result<Vec<KeySlot>, FortressError> {
+    if !Path::new(file).exists() {
+        return Err(FortressError::VaultNotFound);
+    }
+    let encrypted = fs::read(file)?;
+    Ok(VaultHeader::parse(&encrypted)?.0.slots)
+}
+
+/// Read the vault and its data key. A vault whose key is derived from the master password
+/// is first moved to key slots.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of the encrypted vault and its data key, or a [`FortressError`]
+pub(crate) fn data_key(args: &GeneralArgs) -> Result<(Vec<u8>, VaultKey), FortressError> {
+    if read_slots(&args.file)?.is_empty() {
+        rewrap(&[], true, false, args.clone())?;
+    }
+    let encrypted = fs::read(&args.file)?;
+    let (header, _) = VaultHeader::parse(&encrypted)?;
+    let key = match &args.key {
+        Some(key) if key.salt == header.salt => {
+            crypto::decrypt_payload_with_key(&encrypted, key)
+                .map_err(|_| FortressError::DecryptionFailed)?;
+            key.clone()
+        }
+        _ => VaultKey::unlock(&encrypted, &args.password)
+            .map_err(|_| FortressError::DecryptionFailed)?,
+    };
+    Ok((encrypted, key))
+}
+
+/// The recipients of the slots.
+fn slot_recipients(slots: &[KeySlot]) -> Vec<Recipient> {
+    slots
+        .iter()
+        .filter_map(|slot| match slot {
+            KeySlot::Recipient { recipient, .. } => Some(*recipient),
+            _ => None,
+        })
+        .collect()
+}
+
+/// If true, the master password opens the vault.
+fn has_password(slots: &[KeySlot]) -> bool {
+    slots.is_empty()
+        || slots
+            .iter()
+            .any(|slot| matches!(slot, KeySlot::Password { .. }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_add_and_remove_recipients() {
+        let path = tmp_path("recipients_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .unwrap();
+        crate::commands::add::add(
+            "team/db".to_string(),
+            "admin".to_string(),
+            Some("pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .unwrap();
+
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        add(
+            vec![alice.recipient().to_string(), bob.recipient().to_string()],
+            false,
+            false,
+            args.clone(),
+        )
+        .unwrap();
+        let slots = read_slots(&path).unwrap();
+        assert_eq!(slots.len(), 3);
+        assert_eq!(
+            slot_recipients(&slots),
+            vec![alice.recipient(), bob.recipient()]
+        );
+
+        let encrypted = fs::read(&path).unwrap();
+        let carol = Identity::generate();
+        add(
+            vec![carol.recipient().to_string()],
+            false,
+            false,
+            args.clone(),
+        )
+        .unwrap();
+        let with_carol = fs::read(&path).unwrap();
+        assert_eq!(
+            VaultHeader::parse(&with_carol).unwrap().1,
+            VaultHeader::parse(&encrypted).unwrap().1
+        );
+        remove(vec![carol.recipient().to_string()], false, args.clone()).unwrap();
+
+        let encrypted = fs::read(&path).unwrap();
+        let key = VaultKey::unlock_with_identity(&encrypted, &bob).unwrap();
+        let mut bob_args = GeneralArgs::new(path.clone(), String::new());
+        bob_args.key = Some(key);
+        crate::commands::add::add(
+            "team/api".to_string(),
+            "bob".to_string(),
+            Some("token".to_string()),
+            false,
+            None,
+            Vec::new(),
+            bob_args.clone(),
+        )
+        .unwrap();
+        assert!(matches!(
+            remove(vec![alice.recipient().to_string()], false, bob_args),
+            Err(FortressError::Unsupported(_))
+        ));
+
+        let (encrypted, key) = data_key(&args).unwrap();
+        let mut slots = read_slots(&path).unwrap();
+        slots.push(KeySlot::keyfile(&key, b"keyfile content").unwrap());
+        fs::write(&path, crypto::replace_slots(&encrypted, &slots).unwrap()).unwrap();
+        assert!(matches!(
+            remove(vec![bob.recipient().to_string()], false, args.clone()),
+            Err(FortressError::Unsupported(_))
+        ));
+        assert_eq!(read_slots(&path).unwrap().len(), 4);
+
+        remove(vec![bob.recipient().to_string()], true, args.clone()).unwrap();
+        let encrypted = fs::read(&path).unwrap();
+        assert!(VaultKey::unlock_with_identity(&encrypted, &bob).is_err());
+        let key = VaultKey::unlock_with_identity(&encrypted, &alice).unwrap();
+        let mut alice_args = GeneralArgs::new(path.clone(), String::new());
+        alice_args.key = Some(key);
+        let data = helpers::load_vault(alice_args).unwrap();
+        assert_eq!(data.entries.len(), 2);
+        assert_eq!(helpers::load_vault(args.clone()).unwrap().entries.len(), 2);
+        assert_eq!(read_slots(&path).unwrap().len(), 2);
+
+        add(Vec::new(), true, false, args.clone()).unwrap();
+        assert!(helpers::load_vault(args.clone()).is_err());
+        assert!(matches!(
+            remove(vec![alice.recipient().to_string()], false, args),
+            Err(FortressError::Unsupported(_))
+        ));
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_save_after_rotation() {
+        let path = tmp_path("recipients_rotation_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .unwrap();
+        let alice = Identity::generate();
+        add(
+            vec![alice.recipient().to_string()],
+            false,
+            false,
+            args.clone(),
+        )
+        .unwrap();
+
+        let mut vault = crate::vault::Vault::open(&path, "S3cureP@ssword").unwrap();
+        vault
+            .insert(crate::helpers::structs::PasswordEntry {
+                identifier: "team/db".to_string(),
+                password: "pw".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        remove(vec![alice.recipient().to_string()], false, args.clone()).unwrap();
+        assert!(matches!(vault.save(), Err(FortressError::VaultChanged)));
+        drop(vault);
+
+        assert_eq!(read_slots(&path).unwrap().len(), 1);
+        let vault = crate::vault::Vault::open(&path, "S3cureP@ssword").unwrap();
+        assert!(vault.data().entries.is_empty());
+        cleanup(&path);
+    }
+}
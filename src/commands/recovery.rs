@@ -0,0 +1,306 @@
+//! Recover a vault whose master password is lost.
+//! A random recovery key wraps the data key in the header of the vault. It is split in shares
+//! with [`shamir`]: enough of them open the vault and set a new master password.
+use crate::commands::recipients;
+use crate::crypto::{self, KEY_LEN, KeySlot, SALT_LEN, VaultHeader, VaultKey};
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::GeneralArgs;
+use crate::shamir::{self, Share};
+use crate::vault::MIN_STRENGTH;
+use password_strength::estimate_strength;
+use rand::RngCore;
+use rpassword::prompt_password;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+use zeroize::Zeroize;
+
+/// Prefix of the printed shares.
+const SHARE_PREFIX: &str = "frtrs-recovery-";
+/// Number of hexadecimal characters of the vault identifier written in each share.
+const VAULT_HEX_LEN: usize = 8;
+
+/// Wrap the data key with a new recovery key and print its shares.
+/// The shares printed before no longer open the vault.
+/// ## Parameters:
+/// - `shares`: The number of shares
+/// - `threshold`: The number of shares needed to open the vault
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn split(shares: u8, threshold: u8, args: GeneralArgs) -> Result<(), FortressError> {
+    let printed = split_key(shares, threshold, &args)?;
+    log::info!("Recovery key split in {} shares", shares);
+    println!(
+        "Give each share to a different person. Any {} of them open the vault with 'frtrs recovery unlock':",
+        threshold
+    );
+    printed.iter().for_each(|share| println!("{}", share));
+    Ok(())
+}
+
+/// Wrap the data key with a new recovery key, replacing the previous one, and split it.
+/// A vault whose key is derived from the master password is first moved to key slots.
+/// ## Returns:
+/// A result of the printable shares or a [`FortressError`]
+fn split_key(shares: u8, threshold: u8, args: &GeneralArgs) -> Result<Vec<String>, FortressError> {
+    let (encrypted, key) = recipients::data_key(args)?;
+    let (header, _) = VaultHeader::parse(&encrypted)?;
+
+    let mut recovery_key = [0u8; KEY_LEN];
+    rand::rng().fill_bytes(&mut recovery_key);
+    let split = shamir::split(&recovery_key, threshold, shares);
+    let slot = KeySlot::recovery(&key, &recovery_key);
+    recovery_key.zeroize();
+    let mut slots: Vec<KeySlot> = header
+        .slots
+        .into_iter()
+        .filter(|slot| !matches!(slot, KeySlot::Recovery { .. }))
+        .collect();
+    slots.push(slot?);
+    let split = split?;
+
+    let encrypted = crypto::replace_slots(&encrypted, &slots)?;
+    fs::write(&args.file, encrypted).map_err(FortressError::IoError)?;
+    Ok(split
+        .iter()
+        .map(|share| format_share(&key.salt, share))
+        .collect())
+}
+
+/// Combine the shares of the recovery key and set a new master password.
+/// The shares and the new password are asked on the terminal, or read from stdin:
+/// one share per line, then the new password.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn unlock(args: GeneralArgs) -> Result<(), FortressError> {
+    let stdin = io::stdin();
+    let (shares, mut password) = if stdin.is_terminal() {
+        prompt_shares()?
+    } else {
+        read_shares(stdin.lock())?
+    };
+    let result = recover(&args.file, &shares, &password);
+    password.zeroize();
+    result?;
+    println!(
+        "The master password has been changed. Split the recovery key again to renew the shares."
+    );
+    Ok(())
+}
+
+/// Open the vault with shares of its recovery key, and wrap its data key with a new master password.
+/// Only the slot of the master password, the first password slot, is replaced: the other passwords,
+/// the keyfiles, the recipients and the recovery key are kept.
+/// ## Parameters:
+/// - `file`: The path of the vault
+/// - `shares`: The shares, as printed by [`split`]
+/// - `password`: The new master password, which must be strong enough
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn recover(file: &str, shares: &[String], password: &str) -> Result<(), FortressError> {
+    if estimate_strength(password) <= MIN_STRENGTH {
+        return Err(FortressError::WeakPassword);
+    }
+    let encrypted = fs::read(file)?;
+    let (header, _) = VaultHeader::parse(&encrypted)?;
+    let vault = vault_hex(&header.salt);
+    let shares = shares
+        .iter()
+        .map(|text| match parse_share(text)? {
+            (id, share) if id == vault => Ok(share),
+            _ => Err(FortressError::Recovery(
+                "a share belongs to another vault".to_string(),
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut recovery_key: [u8; KEY_LEN] = shamir::combine(&shares)?
+        .try_into()
+        .map_err(|_| FortressError::Recovery("the shares are malformed".to_string()))?;
+    let key = VaultKey::unlock_with_recovery(&encrypted, &recovery_key);
+    recovery_key.zeroize();
+    let key =
+        key.map_err(|_| FortressError::Recovery("the shares do not open the vault".to_string()))?;
+
+    let mut slots = header.slots;
+    let master = KeySlot::password(&key, password)?;
+    match slots
+        .iter()
+        .position(|slot| matches!(slot, KeySlot::Password { .. }))
+    {
+        Some(index) => slots[index] = master,
+        None => slots.insert(0, master),
+    }
+    let encrypted = crypto::replace_slots(&encrypted, &slots)?;
+    fs::write(file, encrypted).map_err(FortressError::IoError)?;
+    log::info!(
+        "Master password reset with {} recovery shares",
+        shares.len()
+    );
+    Ok(())
+}
+
+/// Ask the shares until there are enough, then the new master password twice.
+fn prompt_shares() -> Result<(Vec<String>, String), FortressError> {
+    let mut shares = Vec::new();
+    loop {
+        let share = prompt_password(format!("Share {}: ", shares.len() + 1))
+            .map_err(FortressError::IoError)?;
+        let threshold = parse_share(&share)?.1.threshold as usize;
+        shares.push(share);
+        if shares.len() >= threshold {
+            break;
+        }
+    }
+    let password = prompt_password("New master password: ").map_err(FortressError::IoError)?;
+    let mut confirmation =
+        prompt_password("Confirm the new master password: ").map_err(FortressError::IoError)?;
+    let same = password == confirmation;
+    confirmation.zeroize();
+    if !same {
+        return Err(FortressError::Recovery(
+            "the passwords do not match".to_string(),
+        ));
+    }
+    Ok((shares, password))
+}
+
+/// Read the shares, one per line, then the new master password.
+fn read_shares(input: impl BufRead) -> Result<(Vec<String>, String), FortressError> {
+    let mut shares = Vec::new();
+    for line in input.lines() {
+        let line = line.map_err(FortressError::IoError)?;
+        let line = line.trim();
+        if line.starts_with(SHARE_PREFIX) {
+            shares.push(line.to_string());
+        } else if !line.is_empty() {
+            return Ok((shares, line.to_string()));
+        }
+    }
+    Err(FortressError::Recovery(
+        "the new master password is missing".to_string(),
+    ))
+}
+
+/// Write a share as `frtrs-recovery-<vault>-<index>-<threshold>-<value>`.
+fn format_share(vault: &[u8; SALT_LEN], share: &Share) -> String {
+    format!(
+        "{}{}-{}-{}-{}",
+        SHARE_PREFIX,
+        vault_hex(vault),
+        share.index,
+        share.threshold,
+        crypto::to_hex(&share.value)
+    )
+}
+
+/// Read a share written by [`format_share`].
+/// ## Returns:
+/// A result of the identifier of the vault and the share, or a [`FortressError`]
+fn parse_share(text: &str) -> Result<(String, Share), FortressError> {
+    let malformed = || FortressError::Recovery(format!("`{}` is not a share", text.trim()));
+    let fields: Vec<&str> = text
+        .trim()
+        .strip_prefix(SHARE_PREFIX)
+        .ok_or_else(malformed)?
+        .split('-')
+        .collect();
+    let [vault, index, threshold, value] = fields[..] else {
+        return Err(malformed());
+    };
+    let value: [u8; KEY_LEN] = crypto::from_hex(value).ok_or_else(malformed)?;
+    let share = Share {
+        index: index.parse().map_err(|_| malformed())?,
+        threshold: threshold.parse().map_err(|_| malformed())?,
+        value: value.to_vec(),
+    };
+    Ok((vault.to_lowercase(), share))
+}
+
+/// The start of the identifier of a vault, written in its shares.
+fn vault_hex(vault: &[u8; SALT_LEN]) -> String {
+    crypto::to_hex(&vault[..VAULT_HEX_LEN / 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_split_and_recover() {
+        let path = tmp_path("recovery_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .unwrap();
+        crate::commands::add::add(
+            "team/db".to_string(),
+            "admin".to_string(),
+            Some("pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .unwrap();
+        let shares = split_key(5, 3, &args).unwrap();
+        assert_eq!(shares.len(), 5);
+        let encrypted = fs::read(&path).unwrap();
+        let (header, _) = VaultHeader::parse(&encrypted).unwrap();
+        assert_eq!(header.slots.len(), 2);
+        let key = VaultKey::unlock(&encrypted, "S3cureP@ssword").unwrap();
+        let mut slots = header.slots;
+        slots.push(KeySlot::password(&key, "Br3ak-Gl4ss#Password").unwrap());
+        fs::write(&path, crypto::replace_slots(&encrypted, &slots).unwrap()).unwrap();
+
+        let input = format!("{}\n{}\n\nN3wMaster#Password\n", shares[4], shares[1]);
+        let (read, password) = read_shares(input.as_bytes()).unwrap();
+        assert_eq!(read.len(), 2);
+        assert!(matches!(
+            recover(&path, &read, &password),
+            Err(FortressError::Recovery(_))
+        ));
+        assert!(matches!(
+            recover(&path, &shares[..3], "weak"),
+            Err(FortressError::WeakPassword)
+        ));
+        let other = format_share(&[0u8; SALT_LEN], &parse_share(&shares[0]).unwrap().1);
+        assert!(matches!(
+            recover(
+                &path,
+                &[other, shares[1].clone(), shares[2].clone()],
+                &password
+            ),
+            Err(FortressError::Recovery(_))
+        ));
+
+        recover(&path, &shares[2..], &password).unwrap();
+        assert!(helpers::load_vault(args).is_err());
+        let args = GeneralArgs::new(path.clone(), password);
+        assert_eq!(helpers::load_vault(args).unwrap().entries.len(), 1);
+        let args = GeneralArgs::new(path.clone(), "Br3ak-Gl4ss#Password".to_string());
+        assert_eq!(helpers::load_vault(args).unwrap().entries.len(), 1);
+        let (header, _) = VaultHeader::parse(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(header.slots.len(), 3);
+        assert!(matches!(header.slots[1], KeySlot::Recovery { .. }));
+        cleanup(&path);
+    }
+}
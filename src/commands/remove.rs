@@ -1,31 +1,69 @@
 //! Remove a specific entry from the vault.
 
-use crate::helpers::structs::GeneralArgs;
-use crate::helpers::{self, errors::FortressError};
+use crate::helpers::structs::{GeneralArgs, PasswordEntry, TrashedEntry, VaultData};
+use crate::helpers::{self, errors::FortressError, picker};
+use crate::vault::Vault;
 
 /// Remove the password of the specific entry.
+/// Unless `permanent` is set, the entry is moved to the trash.
 /// ## Parameters:
-/// - `identifier`: The path of the entry to remove
+/// - `identifier`: The path of the entry to remove. Picked interactively if omitted
+/// - `permanent`: If true, drop the entry instead of moving it to the trash
 /// - `args`: The context of the program
 /// ## Returns:
 /// A result of nothing or a [`FortressError`]
-pub fn remove(identifier: String, args: GeneralArgs) -> Result<(), FortressError> {
-    let decrypted = helpers::load_vault(args.clone());
-    match decrypted {
-        Ok(decrypted) => match decrypted.iter().find(|item| item.identifier == identifier) {
-            Some(_) => {
-                let updated_entries: Vec<crate::helpers::structs::PasswordEntry> = decrypted
-                    .into_iter()
-                    .filter(|item| item.identifier != identifier)
-                    .collect();
-                helpers::save_vault(args, &updated_entries)?;
-                log::info!("Entry removed: {}", identifier);
-                println!("Entry '{}' has been removed.", identifier);
-                Ok(())
+pub fn remove(
+    identifier: Option<String>,
+    permanent: bool,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let mut vault = Vault::with_args(args)?;
+    let identifier = picker::resolve(
+        identifier,
+        vault.entries().iter().map(|item| item.identifier.as_str()),
+    )?;
+    vault.remove(&identifier, permanent)?;
+    vault.save()?;
+    if permanent {
+        println!("Entry '{}' has been removed.", identifier);
+    } else {
+        println!("Entry '{}' has been moved to the trash.", identifier);
+    }
+    Ok(())
+}
+
+/// Remove an entry from the vault data, without saving nor printing anything.
+/// ## Parameters:
+/// - `data`: The content of the vault
+/// - `identifier`: The path of the entry to remove
+/// - `permanent`: If true, drop the entry instead of moving it to the trash
+/// ## Returns:
+/// A result of the removed entry or a [`FortressError`]
+pub fn remove_entry(
+    data: &mut VaultData,
+    identifier: String,
+    permanent: bool,
+) -> Result<PasswordEntry, FortressError> {
+    match data
+        .entries
+        .iter()
+        .position(|item| item.identifier == identifier)
+    {
+        Some(index) => {
+            let entry = data.entries.remove(index);
+            if !permanent {
+                data.trash.push(TrashedEntry {
+                    entry: entry.clone(),
+                    deleted_at: helpers::now(),
+                });
             }
-            None => Err(FortressError::IdNotFound(identifier)),
-        },
-        Err(e) => Err(e),
+            log::info!("Entry removed: {}", identifier);
+            Ok(entry)
+        }
+        None => Err(FortressError::id_not_found(
+            identifier,
+            data.entries.iter().map(|item| item.identifier.as_str()),
+        )),
     }
 }
 
@@ -56,23 +94,29 @@ mod tests {
         let path = tmp_path("remove_test");
         cleanup(&path);
         let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
-        crate::commands::create::create(true, args.clone()).expect("create failed");
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
         crate::commands::add::add(
             "remove_id".to_string(),
             "remove_user".to_string(),
             Some("remove_pw".to_string()),
             false,
+            None,
+            Vec::new(),
             args.clone(),
         )
         .expect("add failed");
 
-        let remove_res = remove("remove_id".to_string(), args.clone());
+        let remove_res = remove(Some("remove_id".to_string()), false, args.clone());
         assert!(remove_res.is_ok());
+        let data = helpers::load_vault(args.clone()).unwrap();
+        assert!(data.entries.is_empty());
+        assert_eq!(data.trash[0].entry.identifier, "remove_id");
 
-        let remove_missing_res = remove("remove_id".to_string(), args.clone());
+        let remove_missing_res = remove(Some("remove_id".to_string()), false, args.clone());
         assert!(matches!(
             remove_missing_res,
-            Err(FortressError::IdNotFound(_))
+            Err(FortressError::IdNotFound(..))
         ));
 
         cleanup(&path);
@@ -83,9 +127,10 @@ mod tests {
         let path = tmp_path("remove_empty_test");
         cleanup(&path);
         let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
-        crate::commands::create::create(true, args.clone()).expect("create failed");
-        let remove_res = remove("nonexistent_id".to_string(), args.clone());
-        assert!(matches!(remove_res, Err(FortressError::IdNotFound(_))));
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        let remove_res = remove(Some("nonexistent_id".to_string()), true, args.clone());
+        assert!(matches!(remove_res, Err(FortressError::IdNotFound(..))));
         cleanup(&path);
     }
 }
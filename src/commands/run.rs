@@ -0,0 +1,122 @@
+//! Run a command with secrets injected in its environment.
+use crate::helpers::structs::GeneralArgs;
+use crate::helpers::{self, errors::FortressError};
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus};
+use zeroize::Zeroize;
+
+/// Run a command with secrets of the vault set in its environment only.
+/// The exit code of the command is propagated.
+/// ## Parameters:
+/// - `env`: The variables to set, as names and `identifier[#field]` references
+/// - `command`: The program to run and its arguments
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`], [`FortressError::ChildFailed`] if the command failed
+pub fn run(
+    env: Vec<(String, String)>,
+    command: Vec<String>,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let decrypted = helpers::load_vault(args)?;
+    let mut secrets = env
+        .into_iter()
+        .map(|(name, reference)| Ok((name, helpers::lookup(&decrypted, &reference)?)))
+        .collect::<Result<Vec<(String, String)>, FortressError>>()?;
+    drop(decrypted);
+
+    let status = spawn(&command, &secrets);
+    secrets.iter_mut().for_each(|(_, value)| value.zeroize());
+    let status = status?;
+    log::info!(
+        "Ran `{}` with {} secrets: {}",
+        command[0],
+        secrets.len(),
+        status
+    );
+    match (status.code(), status.signal()) {
+        (Some(0), _) => Ok(()),
+        (Some(code), _) => Err(FortressError::ChildFailed(code)),
+        (None, Some(signal)) => Err(FortressError::ChildFailed(128 + signal)),
+        (None, None) => Err(FortressError::ChildFailed(1)),
+    }
+}
+
+/// Start the command with the variables added to the inherited environment, and wait for it.
+fn spawn(command: &[String], env: &[(String, String)]) -> io::Result<ExitStatus> {
+    let (program, arguments) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no command to run"))?;
+    Command::new(program)
+        .args(arguments)
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .status()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[test]
+    fn test_run_with_secrets_and_exit_code() {
+        let path = tmp_path("run_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        crate::commands::add::add(
+            "prod/db".to_string(),
+            "admin".to_string(),
+            Some("db_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .expect("add failed");
+
+        let env = vec![
+            ("FRTRS_TEST_PASS".to_string(), "prod/db".to_string()),
+            (
+                "FRTRS_TEST_USER".to_string(),
+                "prod/db#username".to_string(),
+            ),
+        ];
+        let res = run(
+            env.clone(),
+            sh("test \"$FRTRS_TEST_PASS\" = db_pw && test \"$FRTRS_TEST_USER\" = admin"),
+            args.clone(),
+        );
+        assert!(res.is_ok());
+        assert!(std::env::var("FRTRS_TEST_PASS").is_err());
+
+        let res = run(env, sh("exit 3"), args.clone());
+        assert!(matches!(res, Err(FortressError::ChildFailed(3))));
+
+        let missing = vec![("X".to_string(), "prod/missing".to_string())];
+        let res = run(missing, sh("true"), args);
+        assert!(matches!(res, Err(FortressError::IdNotFound(..))));
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,116 @@
+//! Search entries of the vault.
+use crate::helpers::structs::{GeneralArgs, PasswordEntry};
+use crate::helpers::{self, errors::FortressError, fuzzy};
+
+/// Search entries by identifier, username, URL and tags.
+/// The matching entries are printed best match first.
+/// ## Parameters:
+/// - `query`: The text to look for
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn search(query: String, args: GeneralArgs) -> Result<(), FortressError> {
+    let decrypted = helpers::load_vault(args)?;
+    println!("[");
+    rank(&query, &decrypted.entries)
+        .iter()
+        .for_each(|item| println!("\t{}", item));
+    println!("]");
+    log::info!("Vault Searched");
+    Ok(())
+}
+
+/// Rank the entries matching a query.
+/// The score of an entry is the best score of its fields.
+/// ## Parameters:
+/// - `query`: The text to look for
+/// - `entries`: The entries of the vault
+/// ## Returns:
+/// The matching entries, best match first.
+pub fn rank<'a>(query: &str, entries: &'a [PasswordEntry]) -> Vec<&'a PasswordEntry> {
+    let mut ranked: Vec<(i64, &PasswordEntry)> = entries
+        .iter()
+        .filter_map(|entry| {
+            std::iter::once(entry.identifier.as_str())
+                .chain(std::iter::once(entry.username.as_str()))
+                .chain(entry.url.as_deref())
+                .chain(entry.tags.iter().map(String::as_str))
+                .filter_map(|field| fuzzy::score(query, field))
+                .max()
+                .map(|score| (score, entry))
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then_with(|| a.1.identifier.cmp(&b.1.identifier))
+    });
+    ranked.into_iter().map(|(_, entry)| entry).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::structs::GeneralArgs;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_rank_fields() {
+        let entries = vec![
+            PasswordEntry {
+                identifier: "work/aws".to_string(),
+                username: "ops".to_string(),
+                tags: vec!["cloud".to_string()],
+                ..Default::default()
+            },
+            PasswordEntry {
+                identifier: "home/router".to_string(),
+                username: "admin".to_string(),
+                url: Some("http://192.168.1.1".to_string()),
+                ..Default::default()
+            },
+            PasswordEntry {
+                identifier: "work/github".to_string(),
+                username: "dev@work.com".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let ids = |query: &str| -> Vec<String> {
+            rank(query, &entries)
+                .iter()
+                .map(|entry| entry.identifier.clone())
+                .collect()
+        };
+        assert_eq!(ids("work"), vec!["work/aws", "work/github"]);
+        assert_eq!(ids("cloud"), vec!["work/aws"]);
+        assert_eq!(ids("192.168"), vec!["home/router"]);
+        assert!(ids("nothing").is_empty());
+    }
+
+    #[test]
+    fn test_search_on_vault() {
+        let path = tmp_path("search_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        let res = search("any".to_string(), args);
+        assert!(res.is_ok());
+        cleanup(&path);
+    }
+}
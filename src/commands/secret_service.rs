@@ -0,0 +1,30 @@
+//! Serve the vault over the Secret Service D-Bus API.
+use crate::helpers;
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::GeneralArgs;
+use crate::secret_service::{self, BUS_NAME};
+use zbus::blocking::Connection;
+use zeroize::Zeroize;
+
+/// Expose the vault on the session bus until the program is stopped.
+/// The key is derived once, and the master password is wiped before serving.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn secret_service(mut args: GeneralArgs) -> Result<(), FortressError> {
+    if args.key.is_none() {
+        args.key = Some(helpers::unlock_vault(&args)?);
+    }
+    args.password.zeroize();
+
+    let connection = Connection::session()?;
+    secret_service::register(&connection, args)?;
+    println!(
+        "Serving the vault as {} on the session bus. Press Ctrl-C to stop.",
+        BUS_NAME
+    );
+    loop {
+        std::thread::park();
+    }
+}
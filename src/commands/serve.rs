@@ -0,0 +1,654 @@
+//! Local REST API over a Unix socket or the loopback interface.
+//!
+//! Every request carries a bearer token. The tokens are read from a file, one per line
+//! followed by its scopes: the folders of the identifiers it may reach, or `*` for all.
+//!
+//! - `GET /entries`: the entries in scope, without their passwords
+//! - `POST /entries`: create an entry, with a generated password if none is given
+//! - `GET /entries/<identifier>`: an entry with its password
+//! - `PUT /entries/<identifier>`: change the given fields of an entry
+//! - `DELETE /entries/<identifier>`: move an entry to the trash
+use crate::helpers::structs::{GeneralArgs, PasswordEntry};
+use crate::helpers::{self, errors::FortressError};
+use crate::vault::{EntryUpdate, Vault};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixListener;
+use std::time::Duration;
+use zeroize::Zeroize;
+
+/// How long a client may take to send its request.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Maximum size of the body of a request.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Maximum size of the request line and of each header.
+const MAX_LINE: usize = 8 * 1024;
+
+/// Minimum length of a token.
+const MIN_TOKEN_LEN: usize = 16;
+
+/// Length of the generated passwords.
+const PASSWORD_LEN: usize = 32;
+
+/// A token accepted by the server.
+pub struct Token {
+    secret: String,
+    scopes: Vec<String>,
+}
+
+impl Token {
+    /// Check if the token may reach an identifier.
+    fn allows(&self, identifier: &str) -> bool {
+        self.scopes
+            .iter()
+            .any(|scope| scope == "*" || helpers::in_folder(identifier, scope))
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+/// Where the server listens.
+pub enum Listener {
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+/// A request, reduced to what the API reads.
+struct Request {
+    method: String,
+    path: String,
+    token: Option<String>,
+    body: Vec<u8>,
+}
+
+/// An entry as returned by the API. The password is only sent for a single entry.
+#[derive(Serialize)]
+struct EntryView<'a> {
+    identifier: &'a str,
+    username: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+    tags: &'a [String],
+}
+
+/// The body of `POST /entries`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewEntry {
+    identifier: String,
+    #[serde(default)]
+    username: String,
+    password: Option<String>,
+    url: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// An HTTP answer: its status and its JSON body, if any.
+type Answer = (u16, Option<serde_json::Value>);
+
+/// Serve the API until the program is stopped.
+/// ## Parameters:
+/// - `socket`: If provided, the path of the Unix socket to listen on
+/// - `port`: If provided, the port of 127.0.0.1 to listen on
+/// - `tokens`: The path of the file of the accepted tokens
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn serve(
+    socket: Option<String>,
+    port: Option<u16>,
+    tokens: String,
+    mut args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let tokens = read_tokens(&tokens)?;
+    if args.key.is_none() {
+        args.key = Some(helpers::unlock_vault(&args)?);
+    }
+    args.password.zeroize();
+
+    let listener = match (socket, port) {
+        (Some(socket), _) => {
+            let _ = fs::remove_file(&socket);
+            let listener = UnixListener::bind(&socket).map_err(FortressError::IoError)?;
+            fs::set_permissions(&socket, fs::Permissions::from_mode(0o600))
+                .map_err(FortressError::IoError)?;
+            println!("Serving the vault on {}", socket);
+            Listener::Unix(listener)
+        }
+        (None, port) => {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port.unwrap_or_default()))
+                .map_err(FortressError::IoError)?;
+            println!("Serving the vault on http://{}", listener.local_addr()?);
+            Listener::Tcp(listener)
+        }
+    };
+    run(listener, &tokens, args)
+}
+
+/// Answer the clients of a listener, one at a time.
+/// ## Parameters:
+/// - `listener`: The listener of the clients
+/// - `tokens`: The accepted tokens
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn run(listener: Listener, tokens: &[Token], args: GeneralArgs) -> Result<(), FortressError> {
+    log::info!("API server started with {} tokens", tokens.len());
+    loop {
+        let result = match &listener {
+            Listener::Unix(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_read_timeout(Some(TIMEOUT))?;
+                Ok(handle(stream, tokens, &args))
+            }),
+            Listener::Tcp(listener) => listener.accept().and_then(|(stream, _)| {
+                stream.set_read_timeout(Some(TIMEOUT))?;
+                Ok(handle(stream, tokens, &args))
+            }),
+        };
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::warn!("API request failed: {}", e),
+            Err(e) => return Err(FortressError::IoError(e)),
+        }
+    }
+}
+
+/// Parse the tokens: one per line, followed by its scopes. Empty lines and `#` comments are skipped.
+/// The file must only be readable by its owner.
+/// ## Parameters:
+/// - `path`: The path of the file
+/// ## Returns:
+/// A result of the tokens or a [`FortressError`]
+pub fn read_tokens(path: &str) -> Result<Vec<Token>, FortressError> {
+    let metadata = fs::metadata(path).map_err(FortressError::IoError)?;
+    if metadata.permissions().mode() & 0o077 != 0 {
+        return Err(FortressError::Unsupported(format!(
+            "{} must only be readable by its owner",
+            path
+        )));
+    }
+    let mut content = fs::read_to_string(path).map_err(FortressError::IoError)?;
+    let tokens = parse_tokens(&content);
+    content.zeroize();
+    tokens
+}
+
+/// Parse the content of a file of tokens.
+fn parse_tokens(content: &str) -> Result<Vec<Token>, FortressError> {
+    let mut tokens = Vec::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let secret = words.next().unwrap_or_default().to_string();
+        let scopes: Vec<String> = words.map(str::to_string).collect();
+        if secret.len() < MIN_TOKEN_LEN || scopes.is_empty() {
+            return Err(FortressError::Unsupported(format!(
+                "tokens must have at least {} characters and one scope",
+                MIN_TOKEN_LEN
+            )));
+        }
+        tokens.push(Token { secret, scopes });
+    }
+    Ok(tokens)
+}
+
+/// Answer one request on a connection.
+fn handle(
+    mut stream: impl Read + Write,
+    tokens: &[Token],
+    args: &GeneralArgs,
+) -> Result<(), FortressError> {
+    let (status, body) = match read_request(&mut stream) {
+        Ok(request) => {
+            let token = request.token.as_deref().and_then(|given| {
+                tokens
+                    .iter()
+                    .find(|token| same(token.secret.as_bytes(), given.as_bytes()))
+            });
+            match token {
+                Some(token) => route(&request, token, args.clone()).unwrap_or_else(|e| {
+                    (
+                        status(&e),
+                        Some(serde_json::json!({ "error": e.to_string() })),
+                    )
+                }),
+                None => error(401, "missing or unknown bearer token"),
+            }
+        }
+        Err(FortressError::SerializationError(_)) => error(400, "malformed request"),
+        Err(e) => return Err(e),
+    };
+    write_response(&mut stream, status, body)
+}
+
+/// Run a request on the vault.
+fn route(request: &Request, token: &Token, args: GeneralArgs) -> Result<Answer, FortressError> {
+    let path = request.path.split('?').next().unwrap_or_default();
+    let identifier = match path.strip_prefix("/entries") {
+        Some("") | Some("/") => None,
+        Some(rest) if rest.starts_with('/') => Some(percent_decode(&rest[1..])),
+        _ => return Ok(error(404, "unknown route")),
+    };
+    if let Some(identifier) = &identifier
+        && !token.allows(identifier)
+    {
+        return Ok(error(403, "identifier out of the scopes of the token"));
+    }
+    let mut vault = Vault::with_args(args)?;
+
+    match (request.method.as_str(), identifier) {
+        ("GET", None) => {
+            let entries: Vec<EntryView> = vault
+                .entries()
+                .iter()
+                .filter(|entry| token.allows(&entry.identifier))
+                .map(|entry| view(entry, false))
+                .collect();
+            Ok((200, Some(serde_json::to_value(entries)?)))
+        }
+        ("POST", None) => {
+            let new: NewEntry = serde_json::from_slice(&request.body)?;
+            if !token.allows(&new.identifier) {
+                return Ok(error(403, "identifier out of the scopes of the token"));
+            }
+            let entry = PasswordEntry {
+                identifier: new.identifier,
+                username: new.username,
+                password: new
+                    .password
+                    .unwrap_or_else(|| helpers::random_password(PASSWORD_LEN)),
+                url: new.url,
+                tags: new.tags,
+                ..Default::default()
+            };
+            let body = serde_json::to_value(view(&entry, true))?;
+            log::info!("API created {}", entry.identifier);
+            vault.insert(entry)?;
+            vault.save()?;
+            Ok((201, Some(body)))
+        }
+        ("GET", Some(identifier)) => {
+            let entry = get(&vault, &identifier, token)?;
+            log::info!("API sent {}", identifier);
+            Ok((200, Some(serde_json::to_value(view(entry, true))?)))
+        }
+        ("PUT", Some(identifier)) => {
+            let update: EntryUpdate = serde_json::from_slice(&request.body)?;
+            get(&vault, &identifier, token)?;
+            let body = serde_json::to_value(view(vault.update(&identifier, update)?, false))?;
+            vault.save()?;
+            log::info!("API edited {}", identifier);
+            Ok((200, Some(body)))
+        }
+        ("DELETE", Some(identifier)) => {
+            get(&vault, &identifier, token)?;
+            vault.remove(&identifier, false)?;
+            vault.save()?;
+            Ok((204, None))
+        }
+        _ => Ok(error(405, "method not allowed")),
+    }
+}
+
+/// Get an entry, suggesting only the identifiers in the scopes of the token when it is not found.
+fn get<'a>(
+    vault: &'a Vault,
+    identifier: &str,
+    token: &Token,
+) -> Result<&'a PasswordEntry, FortressError> {
+    vault.get(identifier).map_err(|e| match e {
+        FortressError::IdNotFound(identifier, _) => FortressError::id_not_found(
+            identifier,
+            vault
+                .entries()
+                .iter()
+                .map(|entry| entry.identifier.as_str())
+                .filter(|candidate| token.allows(candidate)),
+        ),
+        e => e,
+    })
+}
+
+/// The HTTP status of an error.
+fn status(error: &FortressError) -> u16 {
+    match error {
+        FortressError::IdNotFound(..) => 404,
+        FortressError::IdAlreadyExists(_) => 409,
+        FortressError::SerializationError(_) => 400,
+        _ => 500,
+    }
+}
+
+/// An answer with an error message.
+fn error(status: u16, message: &str) -> Answer {
+    (status, Some(serde_json::json!({ "error": message })))
+}
+
+/// An entry as returned by the API.
+fn view(entry: &PasswordEntry, password: bool) -> EntryView<'_> {
+    EntryView {
+        identifier: &entry.identifier,
+        username: &entry.username,
+        password: password.then_some(entry.password.as_str()),
+        url: entry.url.as_deref(),
+        tags: &entry.tags,
+    }
+}
+
+/// Compare two secrets in a time independent of their content.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Decode the `%XX` sequences of a path.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Read the request line, the headers and the body of a request.
+/// A malformed request is reported as a [`FortressError::SerializationError`].
+fn read_request(stream: &mut impl Read) -> Result<Request, FortressError> {
+    let malformed =
+        || FortressError::SerializationError(serde::de::Error::custom("malformed HTTP request"));
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    read_line(&mut reader, &mut line)?;
+    let mut words = line.split_whitespace();
+    let (Some(method), Some(path)) = (words.next(), words.next()) else {
+        return Err(malformed());
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut token = None;
+    let mut length = 0;
+    loop {
+        line.clear();
+        read_line(&mut reader, &mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header.split_once(':').ok_or_else(malformed)?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("authorization") {
+            token = value.strip_prefix("Bearer ").map(str::to_string);
+        } else if name.eq_ignore_ascii_case("content-length") {
+            length = value.parse().map_err(|_| malformed())?;
+        }
+    }
+    if length > MAX_BODY {
+        return Err(malformed());
+    }
+    let mut body = vec![0u8; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(FortressError::IoError)?;
+    Ok(Request {
+        method,
+        path,
+        token,
+        body,
+    })
+}
+
+/// Read a line of the request, of at most [`MAX_LINE`] bytes.
+/// A longer line is reported as a [`FortressError::SerializationError`].
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), FortressError> {
+    let read = reader
+        .by_ref()
+        .take(MAX_LINE as u64)
+        .read_line(line)
+        .map_err(FortressError::IoError)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(FortressError::SerializationError(serde::de::Error::custom(
+            "HTTP line too long",
+        )));
+    }
+    Ok(())
+}
+
+/// Write a response and close the connection.
+fn write_response(
+    stream: &mut impl Write,
+    status: u16,
+    body: Option<serde_json::Value>,
+) -> Result<(), FortressError> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    let body = match body {
+        Some(body) => serde_json::to_vec(&body)?,
+        None => Vec::new(),
+    };
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        status,
+        reason,
+        body.len()
+    );
+    if status == 401 {
+        head.push_str("WWW-Authenticate: Bearer\r\n");
+    }
+    if !body.is_empty() {
+        head.push_str("Content-Type: application/json\r\n");
+    }
+    head.push_str("\r\n");
+    stream
+        .write_all(head.as_bytes())
+        .and_then(|_| stream.write_all(&body))
+        .and_then(|_| stream.flush())
+        .map_err(FortressError::IoError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use std::path::Path;
+    use std::thread;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    const ADMIN: &str = "admin-token-0123456789";
+    const CI: &str = "ci-token-0123456789";
+
+    /// Send a request to the server and read the status and the body of the answer.
+    fn call(
+        socket: &str,
+        method: &str,
+        path: &str,
+        token: &str,
+        body: &str,
+    ) -> (u16, serde_json::Value) {
+        let mut stream = UnixStream::connect(socket).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            token,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        let body = serde_json::from_str(body).unwrap_or(serde_json::Value::Null);
+        (status, body)
+    }
+
+    #[test]
+    fn test_tokens_and_helpers() {
+        let tokens = parse_tokens(&format!("# comment\n\n{} ci deploy/web\n", CI)).unwrap();
+        assert!(tokens[0].allows("ci/token"));
+        assert!(tokens[0].allows("deploy/web/db"));
+        assert!(!tokens[0].allows("deploy/mail"));
+        assert!(parse_tokens("short *").is_err());
+        assert!(parse_tokens(ADMIN).is_err());
+
+        assert!(same(b"token", b"token"));
+        assert!(!same(b"token", b"tokens"));
+        assert_eq!(percent_decode("ci%2Fdeploy%20key%zz"), "ci/deploy key%zz");
+    }
+
+    #[test]
+    fn test_read_request_limits() {
+        let request = "GET /entries HTTP/1.1\r\nAuthorization: Bearer token\r\n\r\n";
+        let parsed = read_request(&mut request.as_bytes()).unwrap();
+        assert_eq!(parsed.token.as_deref(), Some("token"));
+
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        let long_header = format!("GET / HTTP/1.1\r\nX-Pad: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        let large_body = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        for request in [long_line, long_header, large_body] {
+            assert!(matches!(
+                read_request(&mut request.as_bytes()),
+                Err(FortressError::SerializationError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_api_over_unix_socket() {
+        let path = tmp_path("serve_test");
+        let socket = tmp_path("serve_socket");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        crate::commands::add::add(
+            "ci/token".to_string(),
+            "bot".to_string(),
+            Some("ci_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .expect("add failed");
+        crate::commands::add::add(
+            "bank".to_string(),
+            "me".to_string(),
+            Some("bank_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .expect("add failed");
+
+        let tokens = parse_tokens(&format!("{} *\n{} ci\n", ADMIN, CI)).unwrap();
+        let listener = Listener::Unix(UnixListener::bind(&socket).unwrap());
+        let server_args = args.clone();
+        thread::spawn(move || run(listener, &tokens, server_args));
+
+        assert_eq!(call(&socket, "GET", "/entries", "wrong", "").0, 401);
+        let (status, list) = call(&socket, "GET", "/entries", CI, "");
+        assert_eq!(status, 200);
+        assert_eq!(list.as_array().unwrap().len(), 1);
+        assert!(list[0].get("password").is_none());
+
+        let (status, entry) = call(&socket, "GET", "/entries/ci%2Ftoken", CI, "");
+        assert_eq!(status, 200);
+        assert_eq!(entry["password"], "ci_pw");
+        assert_eq!(call(&socket, "GET", "/entries/bank", CI, "").0, 403);
+        assert_eq!(call(&socket, "GET", "/entries/ci/missing", CI, "").0, 404);
+
+        let (status, created) = call(
+            &socket,
+            "POST",
+            "/entries",
+            CI,
+            r#"{"identifier": "ci/deploy", "username": "deployer"}"#,
+        );
+        assert_eq!(status, 201);
+        assert_eq!(created["password"].as_str().unwrap().len(), PASSWORD_LEN);
+        let duplicate = r#"{"identifier": "ci/deploy"}"#;
+        assert_eq!(call(&socket, "POST", "/entries", CI, duplicate).0, 409);
+        let outside = r#"{"identifier": "bank2"}"#;
+        assert_eq!(call(&socket, "POST", "/entries", CI, outside).0, 403);
+        assert_eq!(call(&socket, "POST", "/entries", CI, "{").0, 400);
+
+        let update = r#"{"password": "new_pw", "tags": ["ci"]}"#;
+        assert_eq!(call(&socket, "PUT", "/entries/ci/token", CI, update).0, 200);
+        assert_eq!(call(&socket, "DELETE", "/entries/ci/deploy", CI, "").0, 204);
+        assert_eq!(
+            call(&socket, "GET", "/entries", ADMIN, "")
+                .1
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let data = helpers::load_vault(args).unwrap();
+        let token = &data.entries[0];
+        assert_eq!(token.password, "new_pw");
+        assert_eq!(token.history[0].password, "ci_pw");
+        assert_eq!(data.trash[0].entry.identifier, "ci/deploy");
+
+        assert!(Path::new(&socket).exists());
+        cleanup(&socket);
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,243 @@
+//! Share one entry as a self-contained encrypted bundle, and import the bundles received.
+//! A bundle is encrypted like a vault: a random key encrypts the entry and is wrapped
+//! in a single key slot, for a public key or for a passphrase.
+use crate::crypto::{self, Cipher, KeySlot, RECIPIENT_PREFIX, Recipient, VaultKey};
+use crate::helpers::structs::{GeneralArgs, PasswordEntry};
+use crate::helpers::{self, errors::FortressError};
+use crate::vault::{MIN_STRENGTH, Vault};
+use password_strength::estimate_strength;
+use rpassword::prompt_password;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use zeroize::Zeroize;
+
+/// Content of a bundle.
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    /// The shared entry, without its history.
+    entry: PasswordEntry,
+}
+
+/// Write an entry in a bundle that only the holder of the key or the passphrase can open.
+/// ## Parameters:
+/// - `identifier`: The identifier of the entry
+/// - `to`: A public key `frtrs1...`, or a passphrase
+/// - `output`: The path of the bundle, created with mode 0600
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn share(
+    identifier: String,
+    to: String,
+    output: String,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let vault = Vault::with_args(args)?;
+    let bundle = seal(vault.get(&identifier)?, &to)?;
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&output)
+        .map_err(FortressError::IoError)?;
+    file.write_all(&bundle).map_err(FortressError::IoError)?;
+    log::info!("Shared entry {} in {}", identifier, output);
+    println!("Entry '{}' has been written to {}.", identifier, output);
+    Ok(())
+}
+
+/// Import the entry of a bundle into the vault.
+/// The passphrase of the bundle is asked unless a secret key is given.
+/// ## Parameters:
+/// - `file`: The path of the bundle
+/// - `key`: The file of the secret key the bundle was shared with, if any
+/// - `rename`: If provided, the identifier of the imported entry
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn receive(
+    file: String,
+    key: Option<String>,
+    rename: Option<String>,
+    args: GeneralArgs,
+) -> Result<(), FortressError> {
+    let mut vault = Vault::with_args(args)?;
+    let bundle = fs::read(&file).map_err(FortressError::IoError)?;
+    let bundle_key = match key {
+        Some(identity) => {
+            VaultKey::unlock_with_identity(&bundle, &helpers::read_identity(&identity)?)
+        }
+        None => {
+            let mut passphrase = prompt_password("Enter the passphrase of the bundle: ")
+                .map_err(FortressError::IoError)?;
+            let bundle_key = VaultKey::unlock(&bundle, &passphrase);
+            passphrase.zeroize();
+            bundle_key
+        }
+    }
+    .map_err(|_| FortressError::DecryptionFailed)?;
+    let mut entry = open(&bundle, &bundle_key)?;
+    if let Some(identifier) = rename {
+        entry.identifier = identifier;
+    }
+    let identifier = entry.identifier.clone();
+    vault.insert(entry)?;
+    vault.save()?;
+    println!("Entry '{}' has been imported.", identifier);
+    Ok(())
+}
+
+/// Encrypt an entry for a public key or a passphrase.
+/// ## Parameters:
+/// - `entry`: The entry to share. Its history is left out
+/// - `to`: A public key `frtrs1...`, or a passphrase which must be strong enough
+/// ## Returns:
+/// A result of the bundle as bytes or a [`FortressError`]
+pub fn seal(entry: &PasswordEntry, to: &str) -> Result<Vec<u8>, FortressError> {
+    let key = VaultKey::random();
+    let slot = if to.starts_with(RECIPIENT_PREFIX) {
+        KeySlot::recipient(&key, &to.parse::<Recipient>()?)?
+    } else if estimate_strength(to) <= MIN_STRENGTH {
+        return Err(FortressError::WeakPassword);
+    } else {
+        KeySlot::password(&key, to)?
+    };
+    let mut plaintext = serde_json::to_vec(&Bundle {
+        entry: PasswordEntry {
+            history: Vec::new(),
+            ..entry.clone()
+        },
+    })?;
+    let bundle = crypto::encrypt_payload_with_slots(&plaintext, &key, Cipher::Aes256Gcm, &[slot]);
+    plaintext.zeroize();
+    bundle
+}
+
+/// Decrypt the entry of a bundle.
+/// ## Parameters:
+/// - `bundle`: The bundle as bytes
+/// - `key`: The key unwrapped from the slot of the bundle
+/// ## Returns:
+/// A result of the entry or a [`FortressError`]
+pub fn open(bundle: &[u8], key: &VaultKey) -> Result<PasswordEntry, FortressError> {
+    let mut plaintext = crypto::decrypt_payload_with_key(bundle, key)?;
+    let parsed: Result<Bundle, _> = serde_json::from_slice(&plaintext);
+    plaintext.zeroize();
+    Ok(parsed?.entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::Identity;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let mut entry = PasswordEntry {
+            identifier: "ci/token".to_string(),
+            username: "bot".to_string(),
+            password: "t0ken".to_string(),
+            ..Default::default()
+        };
+        entry.set_password("t1ken".to_string(), 1);
+
+        let bundle = seal(&entry, "correct horse battery staple 42!").unwrap();
+        let key = VaultKey::unlock(&bundle, "correct horse battery staple 42!").unwrap();
+        let opened = open(&bundle, &key).unwrap();
+        assert_eq!(opened.identifier, "ci/token");
+        assert_eq!(opened.password, "t1ken");
+        assert!(opened.history.is_empty());
+        assert!(VaultKey::unlock(&bundle, "wrong").is_err());
+
+        let identity = Identity::generate();
+        let bundle = seal(&entry, &identity.recipient().to_string()).unwrap();
+        let key = VaultKey::unlock_with_identity(&bundle, &identity).unwrap();
+        assert_eq!(open(&bundle, &key).unwrap().username, "bot");
+        assert!(VaultKey::unlock_with_identity(&bundle, &Identity::generate()).is_err());
+
+        assert!(matches!(
+            seal(&entry, "weak"),
+            Err(FortressError::WeakPassword)
+        ));
+        assert!(matches!(
+            seal(&entry, "frtrs1abc"),
+            Err(FortressError::InvalidKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_share_and_receive() {
+        let sender = tmp_path("share_sender");
+        let receiver = tmp_path("share_receiver");
+        let bundle = tmp_path("share_bundle");
+        let identity_file = tmp_path("share_identity");
+        let sender_args = GeneralArgs::new(sender.clone(), "S3cureP@ssword".to_string());
+        let receiver_args = GeneralArgs::new(receiver.clone(), "S3cureP@ssword".to_string());
+        for args in [&sender_args, &receiver_args] {
+            crate::commands::create::create(true, Cipher::Aes256Gcm, args.clone()).unwrap();
+        }
+        crate::commands::add::add(
+            "ci/token".to_string(),
+            "bot".to_string(),
+            Some("t0ken".to_string()),
+            false,
+            None,
+            Vec::new(),
+            sender_args.clone(),
+        )
+        .unwrap();
+
+        let identity = Identity::generate();
+        fs::write(&identity_file, identity.to_string()).unwrap();
+        share(
+            "ci/token".to_string(),
+            identity.recipient().to_string(),
+            bundle.clone(),
+            sender_args,
+        )
+        .unwrap();
+
+        let key = Some(identity_file.clone());
+        receive(bundle.clone(), key.clone(), None, receiver_args.clone()).unwrap();
+        assert!(matches!(
+            receive(bundle.clone(), key.clone(), None, receiver_args.clone()),
+            Err(FortressError::IdAlreadyExists(_))
+        ));
+        receive(
+            bundle.clone(),
+            key,
+            Some("ci/token-copy".to_string()),
+            receiver_args.clone(),
+        )
+        .unwrap();
+
+        let data = helpers::load_vault(receiver_args).unwrap();
+        assert_eq!(data.entries.len(), 2);
+        assert!(
+            data.entries
+                .iter()
+                .all(|entry| entry.password == "t0ken" && entry.username == "bot")
+        );
+        assert_eq!(data.entries[1].identifier, "ci/token-copy");
+        for path in [&sender, &receiver, &bundle, &identity_file] {
+            cleanup(path);
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! Interactive shell running commands on a vault unlocked once.
+//!
+//! The key is derived once, then every command reuses it instead of paying the key derivation again.
+//! After some inactivity the key is wiped and the master password is asked again.
+use crate::commands;
+use crate::helpers::cli::{Commands, ShellLine};
+use crate::helpers::structs::GeneralArgs;
+use crate::helpers::{self, errors::FortressError};
+use clap::Parser;
+use rpassword::prompt_password;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::io;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+use zeroize::Zeroize;
+
+/// Commands completed as the first word of a line.
+const COMMANDS: [&str; 17] = [
+    "add",
+    "copy",
+    "edit",
+    "exit",
+    "help",
+    "history",
+    "inject",
+    "list",
+    "lock",
+    "mv",
+    "remove",
+    "restore-password",
+    "run",
+    "search",
+    "trash",
+    "tree",
+    "view",
+];
+
+/// How often the inactivity is checked.
+const TICK: Duration = Duration::from_secs(1);
+
+/// State shared between the prompt and the inactivity timer.
+struct Session {
+    /// The context of the program, holding the derived key while unlocked.
+    args: GeneralArgs,
+    /// Identifiers of the vault, used for completion.
+    identifiers: Vec<String>,
+    /// Time of the last command.
+    last_activity: Instant,
+}
+
+impl Session {
+    /// Derive the key from the master password, then forget the password.
+    fn unlock(&mut self, mut password: String) -> Result<(), FortressError> {
+        self.args.password = std::mem::take(&mut password);
+        let key = helpers::unlock_vault(&self.args);
+        self.args.password.zeroize();
+        self.args.key = Some(key?);
+        self.last_activity = Instant::now();
+        self.refresh();
+        Ok(())
+    }
+
+    /// Wipe the key and the identifiers.
+    fn lock(&mut self) {
+        self.args.key = None;
+        self.identifiers.clear();
+        log::info!("Shell locked");
+    }
+
+    /// Reload the identifiers used for completion.
+    fn refresh(&mut self) {
+        if self.args.key.is_some()
+            && let Ok(data) = helpers::load_vault(self.args.clone())
+        {
+            self.identifiers = data.entries.into_iter().map(|e| e.identifier).collect();
+        }
+    }
+}
+
+/// Completion of the command names and the identifiers.
+struct ShellHelper {
+    session: Arc<Mutex<Session>>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let session = self.session.lock().unwrap();
+        Ok(complete(&line[..pos], &session.identifiers))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// Unlock the vault once and read commands until `exit`.
+/// The commands use the same grammar as the command line.
+/// ## Parameters:
+/// - `lock_after`: The number of seconds of inactivity after which the vault is locked. 0 never locks
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn shell(lock_after: u64, mut args: GeneralArgs) -> Result<(), FortressError> {
+    let password = std::mem::take(&mut args.password);
+    let session = Arc::new(Mutex::new(Session {
+        args,
+        identifiers: Vec::new(),
+        last_activity: Instant::now(),
+    }));
+    {
+        let mut session = session.lock().unwrap();
+        match session.args.key {
+            Some(_) => session.refresh(),
+            None => session.unlock(password)?,
+        }
+    }
+    if lock_after > 0 {
+        spawn_timer(Arc::downgrade(&session), Duration::from_secs(lock_after));
+    }
+
+    let mut editor = Editor::<ShellHelper, DefaultHistory>::new().map_err(readline_error)?;
+    editor.set_helper(Some(ShellHelper {
+        session: session.clone(),
+    }));
+    log::info!("Shell opened");
+    println!("Vault unlocked. Type 'help' to list the commands, 'exit' to leave.");
+
+    loop {
+        let line = match editor.readline("frtrs> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(readline_error(e)),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line);
+        if matches!(line, "exit" | "quit") {
+            break;
+        }
+
+        let result = unlocked_args(&session).and_then(|args| execute(line, args));
+        if let Err(e) = result {
+            eprintln!("{}", e);
+        }
+        let mut session = session.lock().unwrap();
+        session.last_activity = Instant::now();
+        session.refresh();
+    }
+
+    session.lock().unwrap().lock();
+    Ok(())
+}
+
+/// Parse a line and run it on the unlocked vault.
+/// ## Parameters:
+/// - `line`: The line typed by the user
+/// - `args`: The context of the program, holding the derived key
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+fn execute(line: &str, args: GeneralArgs) -> Result<(), FortressError> {
+    let parsed = match ShellLine::try_parse_from(split_words(line)) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let _ = e.print();
+            return Ok(());
+        }
+    };
+    match parsed.command {
+        Commands::Create { .. }
+        | Commands::Check {}
+        | Commands::Tui { .. }
+        | Commands::Shell { .. }
+        | Commands::Agent { .. }
+        | Commands::GitCredential { .. }
+        | Commands::NativeHost { .. }
+        | Commands::Serve { .. }
+        | Commands::SecretService {}
+        | Commands::Recipients { .. }
+        | Commands::Keyslot { .. }
+        | Commands::Recovery { .. }
+        | Commands::Convert { .. } => Err(FortressError::Unsupported(format!(
+            "'{}' cannot be run from the shell.",
+            line.split_whitespace().next().unwrap_or_default()
+        ))),
+        command => commands::run(command, args),
+    }
+}
+
+/// The context to run a command, asking the master password again if the vault has been locked.
+fn unlocked_args(session: &Mutex<Session>) -> Result<GeneralArgs, FortressError> {
+    let mut session = session.lock().unwrap();
+    if session.args.key.is_none() {
+        println!("The vault has been locked after inactivity.");
+        let password = prompt_password("Enter the master vault password: ")?;
+        session.unlock(password)?;
+    }
+    Ok(session.args.clone())
+}
+
+/// Wipe the key once the session has been inactive for `lock_after`.
+/// The thread stops when the session is dropped.
+fn spawn_timer(session: Weak<Mutex<Session>>, lock_after: Duration) {
+    thread::spawn(move || {
+        loop {
+            thread::sleep(TICK);
+            let Some(session) = session.upgrade() else {
+                return;
+            };
+            let mut session = session.lock().unwrap();
+            if session.args.key.is_some() && session.last_activity.elapsed() >= lock_after {
+                session.lock();
+            }
+        }
+    });
+}
+
+/// Split a line into words, keeping quoted text together.
+fn split_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// Complete the last word of a line: a command name first, then identifiers.
+/// ## Returns:
+/// The position where the completed word starts, and the candidates.
+fn complete(line: &str, identifiers: &[String]) -> (usize, Vec<String>) {
+    let start = line
+        .rfind(char::is_whitespace)
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    let word = &line[start..];
+    let candidates: Vec<&str> = if start == 0 {
+        COMMANDS.to_vec()
+    } else {
+        identifiers.iter().map(String::as_str).collect()
+    };
+    (
+        start,
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Convert an error of the line editor.
+fn readline_error(error: ReadlineError) -> FortressError {
+    match error {
+        ReadlineError::Io(e) => FortressError::IoError(e),
+        e => FortressError::IoError(io::Error::other(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_split_words_and_complete() {
+        assert_eq!(
+            split_words("add 'my id' -u \"john doe\"  -p x"),
+            vec!["add", "my id", "-u", "john doe", "-p", "x"]
+        );
+        let identifiers = vec!["work/github".to_string(), "home".to_string()];
+        assert_eq!(complete("co", &identifiers), (0, vec!["copy".to_string()]));
+        assert_eq!(
+            complete("copy wo", &identifiers),
+            (5, vec!["work/github".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_execute_with_single_unlock() {
+        let path = tmp_path("shell_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+
+        let mut session = Session {
+            args: GeneralArgs::new(path.clone(), String::new()),
+            identifiers: Vec::new(),
+            last_activity: Instant::now(),
+        };
+        session
+            .unlock("S3cureP@ssword".to_string())
+            .expect("unlock failed");
+        assert!(session.args.password.is_empty());
+
+        execute(
+            "add shell_id -u user -p 'pw with spaces'",
+            session.args.clone(),
+        )
+        .expect("add failed");
+        session.refresh();
+        assert_eq!(session.identifiers, vec!["shell_id"]);
+        let res = execute("create --force", session.args.clone());
+        assert!(matches!(res, Err(FortressError::Unsupported(_))));
+
+        let data = helpers::load_vault(args).unwrap();
+        assert_eq!(data.entries[0].password, "pw with spaces");
+
+        session.lock();
+        assert!(session.args.key.is_none());
+        assert!(session.identifiers.is_empty());
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,188 @@
+//! Manage the entries removed from the vault.
+use crate::helpers::structs::GeneralArgs;
+use crate::helpers::{self, errors::FortressError};
+
+/// List all entries in the trash, oldest first.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn list(args: GeneralArgs) -> Result<(), FortressError> {
+    let decrypted = helpers::load_vault(args)?;
+    println!("[");
+    decrypted.trash.iter().for_each(|item| {
+        println!(
+            "\t{} (removed on {})",
+            item.entry,
+            helpers::format_timestamp(item.deleted_at)
+        )
+    });
+    println!("]");
+    if decrypted.trash_retention_days > 0 {
+        println!(
+            "Entries are purged {} days after their removal",
+            decrypted.trash_retention_days
+        );
+    }
+    log::info!("Trash Listed");
+    Ok(())
+}
+
+/// Restore an entry from the trash.
+/// If the identifier has been removed several times, the most recent one is restored.
+/// ## Parameters:
+/// - `identifier`: The path of the entry to restore
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn restore(identifier: String, args: GeneralArgs) -> Result<(), FortressError> {
+    let mut decrypted = helpers::load_vault(args.clone())?;
+    let index = match decrypted
+        .trash
+        .iter()
+        .rposition(|item| item.entry.identifier == identifier)
+    {
+        Some(index) => index,
+        None => {
+            return Err(FortressError::id_not_found(
+                identifier,
+                decrypted
+                    .trash
+                    .iter()
+                    .map(|item| item.entry.identifier.as_str()),
+            ));
+        }
+    };
+    if decrypted
+        .entries
+        .iter()
+        .any(|item| item.identifier == identifier)
+    {
+        return Err(FortressError::IdAlreadyExists(identifier));
+    }
+
+    let restored = decrypted.trash.remove(index).entry;
+    decrypted.entries.push(restored);
+    helpers::save_vault(args, &decrypted)?;
+    log::info!("Entry restored: {}", identifier);
+    println!("Entry '{}' has been restored.", identifier);
+    Ok(())
+}
+
+/// Permanently drop all entries in the trash.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn empty(args: GeneralArgs) -> Result<(), FortressError> {
+    let mut decrypted = helpers::load_vault(args.clone())?;
+    let count = decrypted.trash.len();
+    decrypted.trash.clear();
+    helpers::save_vault(args, &decrypted)?;
+    log::info!("Trash emptied: {} entries", count);
+    println!("{} entries have been permanently removed.", count);
+    Ok(())
+}
+
+/// Set the number of days an entry is kept in the trash.
+/// ## Parameters:
+/// - `days`: The retention period, `0` keeps the entries forever
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn retention(days: u64, args: GeneralArgs) -> Result<(), FortressError> {
+    let mut decrypted = helpers::load_vault(args.clone())?;
+    decrypted.trash_retention_days = days;
+    decrypted.purge_trash(helpers::now());
+    helpers::save_vault(args, &decrypted)?;
+    log::info!("Trash retention set to {} days", days);
+    println!("Trash retention set to {} days.", days);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::errors::FortressError;
+    use crate::helpers::structs::GeneralArgs;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    fn add(id: &str, args: &GeneralArgs) {
+        crate::commands::add::add(
+            id.to_string(),
+            "trash_user".to_string(),
+            Some("trash_pw".to_string()),
+            false,
+            None,
+            Vec::new(),
+            args.clone(),
+        )
+        .expect("add failed");
+    }
+
+    #[test]
+    fn test_restore_from_trash() {
+        let path = tmp_path("trash_restore");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        add("trash_id", &args);
+        crate::commands::remove::remove(Some("trash_id".to_string()), false, args.clone())
+            .expect("remove failed");
+        assert!(list(args.clone()).is_ok());
+
+        add("trash_id", &args);
+        let res_exists = restore("trash_id".to_string(), args.clone());
+        assert!(matches!(res_exists, Err(FortressError::IdAlreadyExists(_))));
+
+        crate::commands::remove::remove(Some("trash_id".to_string()), true, args.clone())
+            .expect("remove failed");
+        restore("trash_id".to_string(), args.clone()).expect("restore failed");
+        let data = helpers::load_vault(args.clone()).unwrap();
+        assert_eq!(data.entries.len(), 1);
+        assert!(data.trash.is_empty());
+
+        let res_missing = restore("trash_id".to_string(), args);
+        assert!(matches!(res_missing, Err(FortressError::IdNotFound(..))));
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_empty_trash() {
+        let path = tmp_path("trash_empty");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        add("trash_id", &args);
+        crate::commands::remove::remove(Some("trash_id".to_string()), false, args.clone())
+            .expect("remove failed");
+
+        retention(u64::MAX, args.clone()).expect("retention failed");
+        let data = helpers::load_vault(args.clone()).unwrap();
+        assert_eq!(data.trash.len(), 1);
+
+        retention(7, args.clone()).expect("retention failed");
+        empty(args.clone()).expect("empty failed");
+        let data = helpers::load_vault(args).unwrap();
+        assert!(data.trash.is_empty());
+        assert_eq!(data.trash_retention_days, 7);
+        cleanup(&path);
+    }
+}
@@ -0,0 +1,161 @@
+//! Show the entries of the vault as a tree of folders.
+use crate::helpers::structs::GeneralArgs;
+use crate::helpers::{self, errors::FortressError};
+use std::collections::BTreeMap;
+
+/// A folder of the tree, built from the path-like identifiers.
+#[derive(Default)]
+struct Folder {
+    /// The sub-folders, sorted by name.
+    folders: BTreeMap<String, Folder>,
+    /// The names of the entries directly inside the folder.
+    entries: Vec<String>,
+}
+
+impl Folder {
+    /// Insert an entry from its path relative to this folder.
+    fn insert(&mut self, path: &str) {
+        match path.split_once('/') {
+            Some((name, rest)) if !rest.is_empty() => self
+                .folders
+                .entry(name.to_string())
+                .or_default()
+                .insert(rest),
+            _ => self.entries.push(path.trim_end_matches('/').to_string()),
+        }
+    }
+
+    /// Number of entries in this folder and all its sub-folders.
+    fn count(&self) -> usize {
+        self.entries.len() + self.folders.values().map(Folder::count).sum::<usize>()
+    }
+
+    /// Render the content of the folder, sub-folders first.
+    fn render(&self, indent: &str, lines: &mut Vec<String>) {
+        let mut entries = self.entries.clone();
+        entries.sort();
+        let total = self.folders.len() + entries.len();
+
+        for (index, (name, folder)) in self.folders.iter().enumerate() {
+            let last = index + 1 == total;
+            let branch = if last { "└── " } else { "├── " };
+            lines.push(format!(
+                "{}{}{}/ ({})",
+                indent,
+                branch,
+                name,
+                folder.count()
+            ));
+            let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            folder.render(&indent, lines);
+        }
+        for (index, name) in entries.iter().enumerate() {
+            let last = self.folders.len() + index + 1 == total;
+            let branch = if last { "└── " } else { "├── " };
+            lines.push(format!("{}{}{}", indent, branch, name));
+        }
+    }
+}
+
+/// Show the entries of the vault as a tree of folders, with the number of entries per folder.
+/// ## Parameters:
+/// - `prefix`: If provided, only show the entries of this folder
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn tree(prefix: Option<String>, args: GeneralArgs) -> Result<(), FortressError> {
+    let decrypted = helpers::load_vault(args)?;
+    let identifiers: Vec<&str> = decrypted
+        .entries
+        .iter()
+        .map(|item| item.identifier.as_str())
+        .collect();
+    render(&identifiers, &prefix.unwrap_or_default())
+        .iter()
+        .for_each(|line| println!("{}", line));
+    log::info!("Vault Tree Listed");
+    Ok(())
+}
+
+/// Render the identifiers of a folder as a tree.
+/// ## Parameters:
+/// - `identifiers`: All identifiers of the vault
+/// - `prefix`: The folder to render, empty for the whole vault
+/// ## Returns:
+/// The lines of the tree.
+pub fn render(identifiers: &[&str], prefix: &str) -> Vec<String> {
+    let prefix = prefix.trim_end_matches('/');
+    let mut root = Folder::default();
+    identifiers
+        .iter()
+        .filter(|identifier| helpers::in_folder(identifier, prefix))
+        .for_each(|identifier| {
+            match identifier
+                .strip_prefix(prefix)
+                .map(|rest| rest.trim_start_matches('/'))
+            {
+                Some(relative) if !relative.is_empty() => root.insert(relative),
+                _ => root.entries.push(identifier.to_string()),
+            }
+        });
+
+    let name = if prefix.is_empty() { "." } else { prefix };
+    let mut lines = vec![format!("{}/ ({})", name, root.count())];
+    root.render("", &mut lines);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::structs::GeneralArgs;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let identifiers = ["work/github", "work/aws/prod", "work/aws/dev", "home"];
+        assert_eq!(
+            render(&identifiers, ""),
+            vec![
+                "./ (4)",
+                "├── work/ (3)",
+                "│   ├── aws/ (2)",
+                "│   │   ├── dev",
+                "│   │   └── prod",
+                "│   └── github",
+                "└── home",
+            ]
+        );
+        assert_eq!(
+            render(&identifiers, "work/aws/"),
+            vec!["work/aws/ (2)", "├── dev", "└── prod"]
+        );
+    }
+
+    #[test]
+    fn test_tree_on_vault() {
+        let path = tmp_path("tree_test");
+        cleanup(&path);
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+        let res = tree(Some("work".to_string()), args);
+        assert!(res.is_ok());
+        cleanup(&path);
+    }
+}
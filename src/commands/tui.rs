@@ -0,0 +1,23 @@
+//! Open the vault in a full-screen terminal UI.
+use crate::helpers::structs::GeneralArgs;
+use crate::helpers::{self, errors::FortressError};
+use crate::tui::{self, App};
+use std::time::Duration;
+use zeroize::Zeroize;
+
+/// Unlock the vault once and browse it in a terminal UI.
+/// The key is derived once and the master password is wiped.
+/// ## Parameters:
+/// - `lock_after`: The number of seconds of inactivity after which the vault is locked. 0 never locks
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn tui(lock_after: u64, mut args: GeneralArgs) -> Result<(), FortressError> {
+    if args.key.is_none() {
+        args.key = Some(helpers::unlock_vault(&args)?);
+    }
+    args.password.zeroize();
+    let decrypted = helpers::load_vault(args.clone())?;
+    log::info!("TUI opened");
+    tui::run(App::new(args, decrypted), Duration::from_secs(lock_after))
+}
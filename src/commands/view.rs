@@ -1,28 +1,32 @@
 //! View a specific entry in the vault.
 
 use crate::helpers::structs::GeneralArgs;
-use crate::helpers::{self, errors::FortressError};
+use crate::helpers::{errors::FortressError, picker};
+use crate::vault::Vault;
 
 /// Display the password of the specific entry.
 /// ## Parameters:
-/// - `identifier`: The path of the entry to display
+/// - `identifier`: The path of the entry to display. Picked interactively if omitted
 /// - `args`: The context of the program
 /// ## Returns:
 /// A result of nothing or a [`FortressError`]
-pub fn view(identifier: String, args: GeneralArgs) -> Result<(), FortressError> {
-    let decrypted = helpers::load_vault(args);
-    match decrypted {
-        Ok(decrypted) => match decrypted.iter().find(|item| item.identifier == identifier) {
-            Some(el) => {
-                println!("{}", el);
-                log::info!("Entry viewed: {}", identifier);
-                println!("The decoded password is: `{}`", el.password);
-                Ok(())
-            }
-            None => Err(FortressError::IdNotFound(identifier)),
-        },
-        Err(e) => Err(e),
+pub fn view(identifier: Option<String>, args: GeneralArgs) -> Result<(), FortressError> {
+    let vault = Vault::with_args(args)?;
+    let identifier = picker::resolve(
+        identifier,
+        vault.entries().iter().map(|item| item.identifier.as_str()),
+    )?;
+    let el = vault.get(&identifier)?;
+    println!("{}", el);
+    if let Some(url) = &el.url {
+        println!("URL: {}", url);
     }
+    if !el.tags.is_empty() {
+        println!("Tags: {}", el.tags.join(", "));
+    }
+    log::info!("Entry viewed: {}", identifier);
+    println!("The decoded password is: `{}`", el.password);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -52,20 +56,23 @@ mod tests {
         let path = tmp_path("view_test");
         cleanup(&path);
         let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
-        crate::commands::create::create(true, args.clone()).expect("create failed");
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
         crate::commands::add::add(
             "view_id".to_string(),
             "view_user".to_string(),
             Some("view_pw".to_string()),
             false,
+            None,
+            Vec::new(),
             args.clone(),
         )
         .expect("add failed");
-        let res_ok = view("view_id".to_string(), args.clone());
+        let res_ok = view(Some("view_id".to_string()), args.clone());
         assert!(res_ok.is_ok(), "view should succeed for existing id");
 
-        let res_missing = view("no_such_id".to_string(), args.clone());
-        assert!(matches!(res_missing, Err(FortressError::IdNotFound(_))));
+        let res_missing = view(Some("no_such_id".to_string()), args.clone());
+        assert!(matches!(res_missing, Err(FortressError::IdNotFound(..))));
         cleanup(&path);
     }
 }
@@ -1,13 +1,20 @@
 //! Cryptographic functions
 use crate::helpers::errors::FortressError;
-use crate::helpers::structs::PasswordEntry;
+use crate::helpers::structs::VaultData;
 use aes_gcm::{
     Aes256Gcm, Key, Nonce,
     aead::{Aead, KeyInit},
 };
 use argon2::{Argon2, Params};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::str::FromStr;
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+use zeroize::Zeroize;
 
 /// Structure of the database.
 /// Contains a check if the database is readable, and the data.
@@ -17,131 +24,1109 @@ struct DatabaseWrapper {
     _pwcheck: String,
 
     /// Actual data.
-    entries: Vec<PasswordEntry>,
+    #[serde(flatten)]
+    data: VaultData,
 }
 
 /// Derive a 256-bit key from `password` using `Argon2id`
 /// ## Parameters:
-/// - `password`: The password to derive the key from.
+/// - `password`: The password, or the content of a keyfile, to derive the key from.
 /// - `salt`: The
 /// ## Returns:
 /// A result of the key or a [`FortressError`]
-fn derive_key(password: &str, salt: &[u8; 32]) -> Result<[u8; 32], FortressError> {
+fn derive_key(
+    password: impl AsRef<[u8]>,
+    salt: &[u8; SALT_LEN],
+) -> Result<[u8; KEY_LEN], FortressError> {
     let params = Params::new(65536, 3, 4, Some(32)).map_err(|_| FortressError::EncryptionFailed)?;
     let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
-    let mut key = [0u8; 32];
+    let mut key = [0u8; KEY_LEN];
     argon2
-        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .hash_password_into(password.as_ref(), salt, &mut key)
         .map_err(|_| FortressError::EncryptionFailed)?;
     Ok(key)
 }
 
+/// Size of the salt at the start of the vault file.
+pub const SALT_LEN: usize = 32;
+/// Size of the AES-GCM nonce following the salt.
+pub const NONCE_LEN: usize = 12;
+/// Size of the XChaCha20-Poly1305 nonce.
+pub const XNONCE_LEN: usize = 24;
+/// Size of the authentication tag appended to the ciphertext, for both ciphers.
+pub const TAG_LEN: usize = 16;
+/// Smallest possible size of a vault file: header and an empty authenticated payload.
+pub const MIN_VAULT_LEN: usize = SALT_LEN + NONCE_LEN + TAG_LEN;
+
+/// Size of the key derived from the master password.
+pub const KEY_LEN: usize = 32;
+
+/// Magic bytes opening the vaults whose data key is wrapped in key slots.
+/// Older vaults start directly with the salt of the master password.
+pub const MAGIC: &[u8; 5] = b"FRTRS";
+/// Version of the format with key slots.
+pub const VERSION: u8 = 3;
+/// Version of the format with key slots written before the cipher was recorded in the header.
+/// Its data is encrypted with AES-256-GCM.
+const AES_SLOTS_VERSION: u8 = 2;
+/// Size of a data key wrapped in a key slot, with its tag.
+pub const WRAPPED_LEN: usize = KEY_LEN + TAG_LEN;
+/// Size of an X25519 key.
+pub const X25519_LEN: usize = 32;
+
+/// Prefix of the public keys of recipients.
+pub const RECIPIENT_PREFIX: &str = "frtrs1";
+/// Prefix of the secret keys of recipients.
+const IDENTITY_PREFIX: &str = "FRTRS-SECRET-KEY-";
+/// Context of the key derived from an X25519 shared secret.
+const RECIPIENT_INFO: &[u8] = b"fortress/x25519";
+/// Context of the XChaCha20-Poly1305 key derived from the data key.
+const XCHACHA_INFO: &[u8] = b"fortress/xchacha20-poly1305";
+
+/// Kind of a password key slot in the header.
+const PASSWORD_SLOT: u8 = 1;
+/// Kind of a recipient key slot in the header.
+const RECIPIENT_SLOT: u8 = 2;
+/// Kind of a recovery key slot in the header.
+const RECOVERY_SLOT: u8 = 3;
+/// Kind of a keyfile key slot in the header.
+const KEYFILE_SLOT: u8 = 4;
+
+/// A key derived from the master password, with the salt used to derive it.
+/// It opens and saves the vault without paying the key derivation again,
+/// as long as the salt of the vault file is unchanged. The key is wiped on drop.
+#[derive(Clone)]
+pub struct VaultKey {
+    /// Salt used to derive the key, written in the header of the vault.
+    pub salt: [u8; SALT_LEN],
+    /// The derived key.
+    pub key: [u8; KEY_LEN],
+}
+
+impl VaultKey {
+    /// Draw a random data key, to be wrapped in key slots.
+    /// The salt is then the identifier of the vault, written in its header.
+    pub fn random() -> Self {
+        let mut vault_key = VaultKey {
+            salt: [0u8; SALT_LEN],
+            key: [0u8; KEY_LEN],
+        };
+        rand::rng().fill_bytes(&mut vault_key.salt);
+        rand::rng().fill_bytes(&mut vault_key.key);
+        vault_key
+    }
+
+    /// Derive a key with a new random salt.
+    /// ## Parameters:
+    /// - `master_password`: The password to derive the key from.
+    /// ## Returns:
+    /// A result of the key or a [`FortressError`]
+    pub fn generate(master_password: &str) -> Result<Self, FortressError> {
+        let mut salt = [0u8; SALT_LEN];
+        rand::rng().fill_bytes(&mut salt);
+        let key = derive_key(master_password, &salt)?;
+        Ok(VaultKey { salt, key })
+    }
+
+    /// Derive the key of an existing vault, and check that it opens the vault.
+    /// ## Parameters:
+    /// - `encrypted_data`: The encrypted vault as bytes.
+    /// - `master_password`: The password to derive the key from.
+    /// ## Returns:
+    /// A result of the key or a [`FortressError`]
+    pub fn unlock(encrypted_data: &[u8], master_password: &str) -> Result<Self, FortressError> {
+        let (header, _) = VaultHeader::parse(encrypted_data)?;
+        if header.slots.is_empty() {
+            let key = derive_key(master_password, &header.salt)?;
+            let vault_key = VaultKey {
+                salt: header.salt,
+                key,
+            };
+            decrypt_payload_with_key(encrypted_data, &vault_key)?;
+            return Ok(vault_key);
+        }
+        for slot in &header.slots {
+            if let Some(key) = slot.open_with_password(master_password)? {
+                return VaultKey::checked(encrypted_data, header.salt, key);
+            }
+        }
+        Err(FortressError::InvalidMasterPassword)
+    }
+
+    /// Unwrap the data key of a vault with the secret key of one of its recipients.
+    /// ## Parameters:
+    /// - `encrypted_data`: The encrypted vault as bytes.
+    /// - `identity`: The secret key of the recipient.
+    /// ## Returns:
+    /// A result of the key or a [`FortressError`]
+    pub fn unlock_with_identity(
+        encrypted_data: &[u8],
+        identity: &Identity,
+    ) -> Result<Self, FortressError> {
+        let (header, _) = VaultHeader::parse(encrypted_data)?;
+        for slot in &header.slots {
+            if let Some(key) = slot.open_with_identity(identity)? {
+                return VaultKey::checked(encrypted_data, header.salt, key);
+            }
+        }
+        Err(FortressError::InvalidMasterPassword)
+    }
+
+    /// Unwrap the data key of a vault with the content of a keyfile.
+    /// ## Parameters:
+    /// - `encrypted_data`: The encrypted vault as bytes.
+    /// - `keyfile`: The content of the keyfile.
+    /// ## Returns:
+    /// A result of the key or a [`FortressError`]
+    pub fn unlock_with_keyfile(
+        encrypted_data: &[u8],
+        keyfile: &[u8],
+    ) -> Result<Self, FortressError> {
+        let (header, _) = VaultHeader::parse(encrypted_data)?;
+        for slot in &header.slots {
+            if let Some(key) = slot.open_with_keyfile(keyfile)? {
+                return VaultKey::checked(encrypted_data, header.salt, key);
+            }
+        }
+        Err(FortressError::InvalidMasterPassword)
+    }
+
+    /// Unwrap the data key of a vault with its recovery key.
+    /// ## Parameters:
+    /// - `encrypted_data`: The encrypted vault as bytes.
+    /// - `recovery_key`: The recovery key, combined from its shares.
+    /// ## Returns:
+    /// A result of the key or a [`FortressError`]
+    pub fn unlock_with_recovery(
+        encrypted_data: &[u8],
+        recovery_key: &[u8; KEY_LEN],
+    ) -> Result<Self, FortressError> {
+        let (header, _) = VaultHeader::parse(encrypted_data)?;
+        for slot in &header.slots {
+            if let Some(key) = slot.open_with_recovery(recovery_key) {
+                return VaultKey::checked(encrypted_data, header.salt, key);
+            }
+        }
+        Err(FortressError::InvalidMasterPassword)
+    }
+
+    /// Build the key unwrapped from a slot, and check that it opens the vault.
+    fn checked(
+        encrypted_data: &[u8],
+        salt: [u8; SALT_LEN],
+        key: [u8; KEY_LEN],
+    ) -> Result<Self, FortressError> {
+        let vault_key = VaultKey { salt, key };
+        decrypt_payload_with_key(encrypted_data, &vault_key)?;
+        Ok(vault_key)
+    }
+}
+
+impl Drop for VaultKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// The AEAD cipher encrypting the data of a vault, recorded in its header.
+/// The vaults without key slots are always encrypted with AES-256-GCM,
+/// and the data key is always wrapped with AES-256-GCM in the slots.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Cipher {
+    /// AES-256-GCM with a random 96-bit nonce.
+    #[default]
+    Aes256Gcm,
+    /// XChaCha20-Poly1305 with a random 192-bit nonce. It is constant-time without AES-NI,
+    /// and random nonces can be drawn for far more messages before a collision is likely.
+    XChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// The identifier of the cipher in the header.
+    fn id(self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => 0,
+            Cipher::XChaCha20Poly1305 => 1,
+        }
+    }
+
+    /// Read the identifier of the cipher in the header.
+    fn from_id(id: u8) -> Result<Self, FortressError> {
+        match id {
+            0 => Ok(Cipher::Aes256Gcm),
+            1 => Ok(Cipher::XChaCha20Poly1305),
+            _ => Err(FortressError::CorruptedVault),
+        }
+    }
+
+    /// Size of the nonce written in the header.
+    pub fn nonce_len(self) -> usize {
+        match self {
+            Cipher::Aes256Gcm => NONCE_LEN,
+            Cipher::XChaCha20Poly1305 => XNONCE_LEN,
+        }
+    }
+
+    /// Encrypt a payload with a new random nonce.
+    /// XChaCha20-Poly1305 uses a key derived from the data key, so that no key is shared by both ciphers.
+    /// ## Returns:
+    /// A result of the nonce and the ciphertext with its tag, or a [`FortressError`]
+    #[allow(deprecated)]
+    fn encrypt(
+        self,
+        key: &VaultKey,
+        plaintext: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), FortressError> {
+        let mut nonce = vec![0u8; self.nonce_len()];
+        rand::rng().fill_bytes(&mut nonce);
+        let ciphertext = match self {
+            Cipher::Aes256Gcm => Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.key))
+                .encrypt(Nonce::from_slice(&nonce), plaintext),
+            Cipher::XChaCha20Poly1305 => {
+                let mut subkey = xchacha_key(key)?;
+                let cipher = XChaCha20Poly1305::new(Key::<XChaCha20Poly1305>::from_slice(&subkey));
+                subkey.zeroize();
+                cipher.encrypt(XNonce::from_slice(&nonce), plaintext)
+            }
+        }
+        .map_err(|_| FortressError::EncryptionFailed)?;
+        Ok((nonce, ciphertext))
+    }
+
+    /// Decrypt a payload.
+    /// ## Returns:
+    /// A result of the plaintext or a [`FortressError`]
+    /// [`FortressError::InvalidMasterPassword`] means the tag was rejected.
+    #[allow(deprecated)]
+    fn decrypt(
+        self,
+        key: &VaultKey,
+        nonce: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, FortressError> {
+        if nonce.len() != self.nonce_len() {
+            return Err(FortressError::CorruptedVault);
+        }
+        match self {
+            Cipher::Aes256Gcm => Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.key))
+                .decrypt(Nonce::from_slice(nonce), ciphertext),
+            Cipher::XChaCha20Poly1305 => {
+                let mut subkey = xchacha_key(key)?;
+                let cipher = XChaCha20Poly1305::new(Key::<XChaCha20Poly1305>::from_slice(&subkey));
+                subkey.zeroize();
+                cipher.decrypt(XNonce::from_slice(nonce), ciphertext)
+            }
+        }
+        .map_err(|_| FortressError::InvalidMasterPassword)
+    }
+}
+
+impl fmt::Display for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cipher::Aes256Gcm => write!(f, "aes-256-gcm"),
+            Cipher::XChaCha20Poly1305 => write!(f, "xchacha20-poly1305"),
+        }
+    }
+}
+
+impl FromStr for Cipher {
+    type Err = FortressError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "aes-256-gcm" => Ok(Cipher::Aes256Gcm),
+            "xchacha20-poly1305" => Ok(Cipher::XChaCha20Poly1305),
+            _ => Err(FortressError::Unsupported(format!(
+                "`{}` is not a cipher, use aes-256-gcm or xchacha20-poly1305",
+                value
+            ))),
+        }
+    }
+}
+
+/// The public key of a recipient, written `frtrs1<hex>`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Recipient(pub [u8; X25519_LEN]);
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", RECIPIENT_PREFIX, to_hex(&self.0))
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = FortressError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .trim()
+            .strip_prefix(RECIPIENT_PREFIX)
+            .and_then(from_hex)
+            .map(Recipient)
+            .ok_or_else(|| FortressError::InvalidKey(format!("`{}` is not a public key", value)))
+    }
+}
+
+/// The secret key of a recipient, written `FRTRS-SECRET-KEY-<hex>`. It is wiped on drop.
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    /// Draw a new secret key.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; X25519_LEN];
+        rand::rng().fill_bytes(&mut bytes);
+        let identity = Identity(StaticSecret::from(bytes));
+        bytes.zeroize();
+        identity
+    }
+
+    /// The public key to give to the owners of the vaults.
+    pub fn recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0).to_bytes())
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            IDENTITY_PREFIX,
+            to_hex(self.0.as_bytes()).to_uppercase()
+        )
+    }
+}
+
+impl FromStr for Identity {
+    type Err = FortressError;
+
+    /// Parse a secret key. Empty lines and `#` comments around it are skipped.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .and_then(|line| line.strip_prefix(IDENTITY_PREFIX))
+            .and_then(from_hex)
+            .map(|mut bytes| {
+                let identity = Identity(StaticSecret::from(bytes));
+                bytes.zeroize();
+                identity
+            })
+            .ok_or_else(|| FortressError::InvalidKey("no secret key found".to_string()))
+    }
+}
+
+/// A copy of the data key, wrapped with the key of one of the people who can open the vault.
+#[derive(Clone, PartialEq, Debug)]
+pub enum KeySlot {
+    /// Wrapped with a key derived from a password.
+    Password {
+        /// Salt used to derive the key from the password.
+        salt: [u8; SALT_LEN],
+        /// Nonce used to wrap the data key.
+        nonce: [u8; NONCE_LEN],
+        /// The wrapped data key.
+        wrapped: [u8; WRAPPED_LEN],
+    },
+    /// Wrapped with a key agreed between an ephemeral key and the public key of a recipient.
+    Recipient {
+        /// The public key of the recipient.
+        recipient: Recipient,
+        /// The ephemeral public key.
+        ephemeral: [u8; X25519_LEN],
+        /// Nonce used to wrap the data key.
+        nonce: [u8; NONCE_LEN],
+        /// The wrapped data key.
+        wrapped: [u8; WRAPPED_LEN],
+    },
+    /// Wrapped with a key derived from the content of a keyfile.
+    Keyfile {
+        /// Salt used to derive the key from the keyfile.
+        salt: [u8; SALT_LEN],
+        /// Nonce used to wrap the data key.
+        nonce: [u8; NONCE_LEN],
+        /// The wrapped data key.
+        wrapped: [u8; WRAPPED_LEN],
+    },
+    /// Wrapped with a random recovery key, split in shares kept by several people.
+    Recovery {
+        /// Nonce used to wrap the data key.
+        nonce: [u8; NONCE_LEN],
+        /// The wrapped data key.
+        wrapped: [u8; WRAPPED_LEN],
+    },
+}
+
+impl KeySlot {
+    /// Wrap a data key with a password.
+    /// ## Parameters:
+    /// - `key`: The data key
+    /// - `password`: The password
+    /// ## Returns:
+    /// A result of the slot or a [`FortressError`]
+    pub fn password(key: &VaultKey, password: &str) -> Result<Self, FortressError> {
+        let (salt, nonce, wrapped) = wrap_with_secret(key, password.as_bytes())?;
+        Ok(KeySlot::Password {
+            salt,
+            nonce,
+            wrapped,
+        })
+    }
+
+    /// Wrap a data key with the content of a keyfile.
+    /// ## Parameters:
+    /// - `key`: The data key
+    /// - `keyfile`: The content of the keyfile
+    /// ## Returns:
+    /// A result of the slot or a [`FortressError`]
+    pub fn keyfile(key: &VaultKey, keyfile: &[u8]) -> Result<Self, FortressError> {
+        if keyfile.is_empty() {
+            return Err(FortressError::InvalidKey(
+                "the keyfile is empty".to_string(),
+            ));
+        }
+        let (salt, nonce, wrapped) = wrap_with_secret(key, keyfile)?;
+        Ok(KeySlot::Keyfile {
+            salt,
+            nonce,
+            wrapped,
+        })
+    }
+
+    /// Wrap a data key for a recipient.
+    /// ## Parameters:
+    /// - `key`: The data key
+    /// - `recipient`: The public key of the recipient
+    /// ## Returns:
+    /// A result of the slot or a [`FortressError`]
+    pub fn recipient(key: &VaultKey, recipient: &Recipient) -> Result<Self, FortressError> {
+        let ephemeral = Identity::generate();
+        let ephemeral_public = ephemeral.recipient();
+        let shared = ephemeral.0.diffie_hellman(&PublicKey::from(recipient.0));
+        let mut kek = agree(shared, &ephemeral_public, recipient)
+            .ok_or_else(|| FortressError::InvalidKey(format!("`{}` is a weak key", recipient)))?;
+        let wrapped = wrap(&kek, &key.key);
+        kek.zeroize();
+        let (nonce, wrapped) = wrapped?;
+        Ok(KeySlot::Recipient {
+            recipient: *recipient,
+            ephemeral: ephemeral_public.0,
+            nonce,
+            wrapped,
+        })
+    }
+
+    /// Wrap a data key with a recovery key.
+    /// ## Parameters:
+    /// - `key`: The data key
+    /// - `recovery_key`: The random recovery key
+    /// ## Returns:
+    /// A result of the slot or a [`FortressError`]
+    pub fn recovery(key: &VaultKey, recovery_key: &[u8; KEY_LEN]) -> Result<Self, FortressError> {
+        let (nonce, wrapped) = wrap(recovery_key, &key.key)?;
+        Ok(KeySlot::Recovery { nonce, wrapped })
+    }
+
+    /// Unwrap the data key if this is a slot of the password.
+    fn open_with_password(&self, password: &str) -> Result<Option<[u8; KEY_LEN]>, FortressError> {
+        match self {
+            KeySlot::Password {
+                salt,
+                nonce,
+                wrapped,
+            } => unwrap_with_secret(password.as_bytes(), salt, nonce, wrapped),
+            _ => Ok(None),
+        }
+    }
+
+    /// Unwrap the data key if this is a slot of the keyfile.
+    fn open_with_keyfile(&self, keyfile: &[u8]) -> Result<Option<[u8; KEY_LEN]>, FortressError> {
+        match self {
+            KeySlot::Keyfile {
+                salt,
+                nonce,
+                wrapped,
+            } => unwrap_with_secret(keyfile, salt, nonce, wrapped),
+            _ => Ok(None),
+        }
+    }
+
+    /// Unwrap the data key if this is the slot of the recipient.
+    fn open_with_identity(
+        &self,
+        identity: &Identity,
+    ) -> Result<Option<[u8; KEY_LEN]>, FortressError> {
+        let KeySlot::Recipient {
+            recipient,
+            ephemeral,
+            nonce,
+            wrapped,
+        } = self
+        else {
+            return Ok(None);
+        };
+        if *recipient != identity.recipient() {
+            return Ok(None);
+        }
+        let shared = identity.0.diffie_hellman(&PublicKey::from(*ephemeral));
+        let Some(mut kek) = agree(shared, &Recipient(*ephemeral), recipient) else {
+            return Ok(None);
+        };
+        let key = unwrap(&kek, nonce, wrapped);
+        kek.zeroize();
+        Ok(key)
+    }
+
+    /// Unwrap the data key if this is the recovery slot.
+    fn open_with_recovery(&self, recovery_key: &[u8; KEY_LEN]) -> Option<[u8; KEY_LEN]> {
+        match self {
+            KeySlot::Recovery { nonce, wrapped } => unwrap(recovery_key, nonce, wrapped),
+            _ => None,
+        }
+    }
+
+    /// Append the slot to a header.
+    fn write(&self, output: &mut Vec<u8>) {
+        match self {
+            KeySlot::Password {
+                salt,
+                nonce,
+                wrapped,
+            } => {
+                output.push(PASSWORD_SLOT);
+                output.extend_from_slice(salt);
+                output.extend_from_slice(nonce);
+                output.extend_from_slice(wrapped);
+            }
+            KeySlot::Recipient {
+                recipient,
+                ephemeral,
+                nonce,
+                wrapped,
+            } => {
+                output.push(RECIPIENT_SLOT);
+                output.extend_from_slice(&recipient.0);
+                output.extend_from_slice(ephemeral);
+                output.extend_from_slice(nonce);
+                output.extend_from_slice(wrapped);
+            }
+            KeySlot::Keyfile {
+                salt,
+                nonce,
+                wrapped,
+            } => {
+                output.push(KEYFILE_SLOT);
+                output.extend_from_slice(salt);
+                output.extend_from_slice(nonce);
+                output.extend_from_slice(wrapped);
+            }
+            KeySlot::Recovery { nonce, wrapped } => {
+                output.push(RECOVERY_SLOT);
+                output.extend_from_slice(nonce);
+                output.extend_from_slice(wrapped);
+            }
+        }
+    }
+
+    /// Read a slot at the start of `input`, and move past it.
+    fn read(input: &mut &[u8]) -> Result<Self, FortressError> {
+        match take::<1>(input)?[0] {
+            PASSWORD_SLOT => Ok(KeySlot::Password {
+                salt: take(input)?,
+                nonce: take(input)?,
+                wrapped: take(input)?,
+            }),
+            RECIPIENT_SLOT => Ok(KeySlot::Recipient {
+                recipient: Recipient(take(input)?),
+                ephemeral: take(input)?,
+                nonce: take(input)?,
+                wrapped: take(input)?,
+            }),
+            KEYFILE_SLOT => Ok(KeySlot::Keyfile {
+                salt: take(input)?,
+                nonce: take(input)?,
+                wrapped: take(input)?,
+            }),
+            RECOVERY_SLOT => Ok(KeySlot::Recovery {
+                nonce: take(input)?,
+                wrapped: take(input)?,
+            }),
+            _ => Err(FortressError::CorruptedVault),
+        }
+    }
+}
+
+/// Derive the key wrapping a data key for a recipient, from an X25519 shared secret.
+/// The ephemeral and recipient public keys are bound to the derived key.
+/// ## Returns:
+/// The key, or `None` if the shared secret is weak.
+fn agree(
+    shared: SharedSecret,
+    ephemeral: &Recipient,
+    recipient: &Recipient,
+) -> Option<[u8; KEY_LEN]> {
+    if !shared.was_contributory() {
+        return None;
+    }
+    let mut salt = [0u8; 2 * X25519_LEN];
+    salt[..X25519_LEN].copy_from_slice(&ephemeral.0);
+    salt[X25519_LEN..].copy_from_slice(&recipient.0);
+    let mut kek = [0u8; KEY_LEN];
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(RECIPIENT_INFO, &mut kek)
+        .ok()?;
+    Some(kek)
+}
+
+/// Derive the XChaCha20-Poly1305 key from the data key, bound to the identifier of the vault.
+fn xchacha_key(key: &VaultKey) -> Result<[u8; KEY_LEN], FortressError> {
+    let mut subkey = [0u8; KEY_LEN];
+    Hkdf::<Sha256>::new(Some(&key.salt), &key.key)
+        .expand(XCHACHA_INFO, &mut subkey)
+        .map_err(|_| FortressError::EncryptionFailed)?;
+    Ok(subkey)
+}
+
+/// Wrap a data key with a key derived from a password or a keyfile, with a new random salt.
+/// ## Returns:
+/// A result of the salt, the nonce and the wrapped key, or a [`FortressError`]
+#[allow(clippy::type_complexity)]
+fn wrap_with_secret(
+    key: &VaultKey,
+    secret: &[u8],
+) -> Result<([u8; SALT_LEN], [u8; NONCE_LEN], [u8; WRAPPED_LEN]), FortressError> {
+    let mut salt = [0u8; SALT_LEN];
+    rand::rng().fill_bytes(&mut salt);
+    let mut kek = derive_key(secret, &salt)?;
+    let wrapped = wrap(&kek, &key.key);
+    kek.zeroize();
+    let (nonce, wrapped) = wrapped?;
+    Ok((salt, nonce, wrapped))
+}
+
+/// Unwrap a data key with a key derived from a password or a keyfile.
+/// ## Returns:
+/// A result of the key, `None` if the secret is not the right one, or a [`FortressError`]
+fn unwrap_with_secret(
+    secret: &[u8],
+    salt: &[u8; SALT_LEN],
+    nonce: &[u8; NONCE_LEN],
+    wrapped: &[u8; WRAPPED_LEN],
+) -> Result<Option<[u8; KEY_LEN]>, FortressError> {
+    let mut kek = derive_key(secret, salt)?;
+    let key = unwrap(&kek, nonce, wrapped);
+    kek.zeroize();
+    Ok(key)
+}
+
+/// Encrypt a data key with a key encryption key and a random nonce.
+#[allow(deprecated)]
+fn wrap(
+    kek: &[u8; KEY_LEN],
+    key: &[u8; KEY_LEN],
+) -> Result<([u8; NONCE_LEN], [u8; WRAPPED_LEN]), FortressError> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(kek));
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::rng().fill_bytes(&mut nonce);
+    let wrapped = cipher
+        .encrypt(Nonce::from_slice(&nonce), key.as_slice())
+        .map_err(|_| FortressError::EncryptionFailed)?;
+    let wrapped = wrapped
+        .try_into()
+        .map_err(|_| FortressError::EncryptionFailed)?;
+    Ok((nonce, wrapped))
+}
+
+/// Decrypt a data key, or `None` if the key encryption key is not the right one.
+#[allow(deprecated)]
+fn unwrap(
+    kek: &[u8; KEY_LEN],
+    nonce: &[u8; NONCE_LEN],
+    wrapped: &[u8; WRAPPED_LEN],
+) -> Option<[u8; KEY_LEN]> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(kek));
+    let mut plain = cipher
+        .decrypt(Nonce::from_slice(nonce), wrapped.as_slice())
+        .ok()?;
+    let key = plain.as_slice().try_into().ok();
+    plain.zeroize();
+    key
+}
+
+/// Read `N` bytes at the start of `input`, and move past them.
+fn take<const N: usize>(input: &mut &[u8]) -> Result<[u8; N], FortressError> {
+    if input.len() < N {
+        return Err(FortressError::CorruptedVault);
+    }
+    let (bytes, rest) = input.split_at(N);
+    *input = rest;
+    bytes.try_into().map_err(|_| FortressError::CorruptedVault)
+}
+
+/// Write bytes as lowercase hexadecimal.
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Read `N` bytes written in hexadecimal, in any case.
+pub(crate) fn from_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != 2 * N || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// Header of an encrypted vault file.
+/// Vaults encrypted with the master password only: [Salt: 32 bytes][Nonce: 12 bytes].
+/// Vaults with key slots: [Magic: 5 bytes][Version: 1 byte][Cipher: 1 byte][Vault id: 32 bytes]
+/// [Slot count: 1 byte][Slots][Nonce: 12 or 24 bytes]. The version 2 has no cipher byte and uses AES-256-GCM.
+/// Both are followed by the encrypted data and its tag.
+pub struct VaultHeader {
+    /// Salt used to derive the key from the master password,
+    /// or identifier of the vault when its data key is wrapped in key slots.
+    pub salt: [u8; SALT_LEN],
+    /// The cipher encrypting the payload.
+    pub cipher: Cipher,
+    /// The slots wrapping the data key, empty if the key is derived from the master password.
+    pub slots: Vec<KeySlot>,
+    /// Nonce used to encrypt the payload, of the size of the nonces of the cipher.
+    pub nonce: Vec<u8>,
+}
+
+impl VaultHeader {
+    /// Split an encrypted vault into its header and its ciphertext.
+    /// ## Parameters:
+    /// - `encrypted_data`: The encrypted database as bytes.
+    /// ## Returns:
+    /// A result of the header and the remaining ciphertext or a [`FortressError`]
+    pub fn parse(encrypted_data: &[u8]) -> Result<(Self, &[u8]), FortressError> {
+        if encrypted_data.len() < MIN_VAULT_LEN {
+            return Err(FortressError::CorruptedVault);
+        }
+
+        let mut input = encrypted_data;
+        let mut slots = Vec::new();
+        let version = input[MAGIC.len()];
+        if input.starts_with(MAGIC) && (version == VERSION || version == AES_SLOTS_VERSION) {
+            input = &input[MAGIC.len() + 1..];
+            let cipher = match version {
+                VERSION => Cipher::from_id(take::<1>(&mut input)?[0])?,
+                _ => Cipher::Aes256Gcm,
+            };
+            let salt = take(&mut input)?;
+            let count = take::<1>(&mut input)?[0];
+            for _ in 0..count {
+                slots.push(KeySlot::read(&mut input)?);
+            }
+            if slots.is_empty() {
+                return Err(FortressError::CorruptedVault);
+            }
+            let nonce = match cipher {
+                Cipher::Aes256Gcm => take::<NONCE_LEN>(&mut input)?.to_vec(),
+                Cipher::XChaCha20Poly1305 => take::<XNONCE_LEN>(&mut input)?.to_vec(),
+            };
+            if input.len() < TAG_LEN {
+                return Err(FortressError::CorruptedVault);
+            }
+            let header = VaultHeader {
+                salt,
+                cipher,
+                slots,
+                nonce,
+            };
+            return Ok((header, input));
+        }
+
+        let salt = take(&mut input)?;
+        let nonce = take::<NONCE_LEN>(&mut input)?.to_vec();
+        let header = VaultHeader {
+            salt,
+            cipher: Cipher::Aes256Gcm,
+            slots,
+            nonce,
+        };
+        Ok((header, input))
+    }
+
+    /// Write the header in front of a ciphertext.
+    fn write(&self, output: &mut Vec<u8>) {
+        if !self.slots.is_empty() {
+            output.extend_from_slice(MAGIC);
+            output.push(VERSION);
+            output.push(self.cipher.id());
+        }
+        output.extend_from_slice(&self.salt);
+        if !self.slots.is_empty() {
+            output.push(self.slots.len() as u8);
+            self.slots.iter().for_each(|slot| slot.write(output));
+        }
+        output.extend_from_slice(&self.nonce);
+    }
+}
+
 /// Encrypt the password database
 /// ## Process
 /// 1. Create a wrapper with password check.
 /// 2. Serialize to JSON
-/// 3. Generate random salt
-/// 4. Derive key from password
-/// 5. Create cipher and generate nonce
-/// 6. Encrypt
-/// 7. Build final format: [Salt: 32 bytes][Nonce: 12 bytes][Encrypted Data + Auth Tag]
+/// 3. Encrypt it with [`encrypt_payload`]
 /// ## Parameters
-/// - `entries`: All entries to save
+/// - `data`: All data to save
 /// - `master_password`: The password to encrypt data.
 /// ## Returns
 /// A result of the encrypted database as bytes or a [`FortressError`].
-#[allow(deprecated)]
-pub fn encrypt_database(
-    entries: &[PasswordEntry],
-    master_password: &str,
+pub fn encrypt_database(data: &VaultData, master_password: &str) -> Result<Vec<u8>, FortressError> {
+    encrypt_payload(&serialize_database(data)?, master_password)
+}
+
+/// Encrypt the password database with an already derived key.
+/// ## Parameters
+/// - `data`: All data to save
+/// - `key`: The key derived from the master password.
+/// ## Returns
+/// A result of the encrypted database as bytes or a [`FortressError`].
+pub fn encrypt_database_with_key(
+    data: &VaultData,
+    key: &VaultKey,
 ) -> Result<Vec<u8>, FortressError> {
+    encrypt_payload_with_key(&serialize_database(data)?, key)
+}
+
+/// Encrypt the password database with a data key, and write the slots wrapping it in the header.
+/// ## Parameters
+/// - `data`: All data to save
+/// - `key`: The data key, or the key derived from the master password if there is no slot.
+/// - `cipher`: The cipher encrypting the data.
+/// - `slots`: The slots wrapping the data key.
+/// ## Returns
+/// A result of the encrypted database as bytes or a [`FortressError`].
+pub fn encrypt_database_with_slots(
+    data: &VaultData,
+    key: &VaultKey,
+    cipher: Cipher,
+    slots: &[KeySlot],
+) -> Result<Vec<u8>, FortressError> {
+    encrypt_payload_with_slots(&serialize_database(data)?, key, cipher, slots)
+}
+
+/// Wrap the data with the password check and serialize it to JSON.
+fn serialize_database(data: &VaultData) -> Result<Vec<u8>, FortressError> {
     let wrapper = DatabaseWrapper {
         _pwcheck: "valid".to_string(),
-        entries: entries.to_vec(),
+        data: data.clone(),
     };
 
-    let json_data = serde_json::to_string(&wrapper)?;
-    let plaintext = json_data.as_bytes();
-
-    let mut salt_32 = [0u8; 32];
-    rand::rng().fill_bytes(&mut salt_32);
+    Ok(serde_json::to_vec(&wrapper)?)
+}
 
-    let key_bytes = derive_key(master_password, &salt_32)?;
-    let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
+/// Encrypt a raw payload
+/// ## Process
+/// 1. Derive a key with a new random salt
+/// 2. Encrypt with [`encrypt_payload_with_key`]
+/// ## Parameters
+/// - `plaintext`: The bytes to encrypt.
+/// - `master_password`: The password to encrypt data.
+/// ## Returns
+/// A result of the encrypted payload as bytes or a [`FortressError`].
+pub fn encrypt_payload(plaintext: &[u8], master_password: &str) -> Result<Vec<u8>, FortressError> {
+    encrypt_payload_with_key(plaintext, &VaultKey::generate(master_password)?)
+}
 
-    let cipher = Aes256Gcm::new(key);
-    let mut nonce_bytes = [0u8; 12];
-    rand::rng().fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
+/// Encrypt a raw payload with an already derived key, reusing its salt.
+/// ## Parameters
+/// - `plaintext`: The bytes to encrypt.
+/// - `key`: The key derived from the master password.
+/// ## Returns
+/// A result of the encrypted payload as bytes or a [`FortressError`].
+pub fn encrypt_payload_with_key(
+    plaintext: &[u8],
+    key: &VaultKey,
+) -> Result<Vec<u8>, FortressError> {
+    encrypt_payload_with_slots(plaintext, key, Cipher::Aes256Gcm, &[])
+}
 
-    let ciphertext = cipher
-        .encrypt(nonce, plaintext)
-        .map_err(|_| FortressError::EncryptionFailed)?;
+/// Encrypt a raw payload, writing the cipher and the slots wrapping the key in the header.
+/// ## Process
+/// 1. Generate nonce and encrypt with the cipher
+/// 2. Build final format: [Header][Encrypted Data + Auth Tag], see [`VaultHeader`]
+/// ## Parameters
+/// - `plaintext`: The bytes to encrypt.
+/// - `key`: The data key, or the key derived from the master password if there is no slot.
+/// - `cipher`: The cipher encrypting the payload, AES-256-GCM if there is no slot.
+/// - `slots`: The slots wrapping the data key.
+/// ## Returns
+/// A result of the encrypted payload as bytes or a [`FortressError`].
+pub fn encrypt_payload_with_slots(
+    plaintext: &[u8],
+    key: &VaultKey,
+    cipher: Cipher,
+    slots: &[KeySlot],
+) -> Result<Vec<u8>, FortressError> {
+    if slots.len() > u8::MAX as usize {
+        return Err(FortressError::EncryptionFailed);
+    }
+    if slots.is_empty() && cipher != Cipher::Aes256Gcm {
+        return Err(FortressError::Unsupported(format!(
+            "Only the vaults with key slots can be encrypted with {}.",
+            cipher
+        )));
+    }
+    let (nonce, ciphertext) = cipher.encrypt(key, plaintext)?;
 
-    let mut result = Vec::with_capacity(32 + 12 + ciphertext.len());
-    result.extend_from_slice(&salt_32);
-    result.extend_from_slice(&nonce_bytes);
+    let header = VaultHeader {
+        salt: key.salt,
+        cipher,
+        slots: slots.to_vec(),
+        nonce,
+    };
+    let mut result = Vec::with_capacity(SALT_LEN + XNONCE_LEN + ciphertext.len());
+    header.write(&mut result);
     result.extend_from_slice(&ciphertext);
 
     Ok(result)
 }
 
+/// Replace the key slots of a vault, keeping its ciphertext as is since the data key is unchanged.
+/// ## Parameters
+/// - `encrypted_data`: The encrypted vault, with key slots.
+/// - `slots`: The new slots wrapping its data key.
+/// ## Returns
+/// A result of the encrypted vault as bytes or a [`FortressError`].
+pub fn replace_slots(encrypted_data: &[u8], slots: &[KeySlot]) -> Result<Vec<u8>, FortressError> {
+    let (header, ciphertext) = VaultHeader::parse(encrypted_data)?;
+    if header.slots.is_empty() || slots.is_empty() || slots.len() > u8::MAX as usize {
+        return Err(FortressError::EncryptionFailed);
+    }
+    let header = VaultHeader {
+        slots: slots.to_vec(),
+        ..header
+    };
+    let mut result = Vec::with_capacity(encrypted_data.len());
+    header.write(&mut result);
+    result.extend_from_slice(ciphertext);
+    Ok(result)
+}
+
+/// Encrypt a vault again with another cipher. The data key and the slots are unchanged.
+/// ## Parameters
+/// - `encrypted_data`: The encrypted vault, with key slots.
+/// - `key`: Its data key.
+/// - `cipher`: The new cipher.
+/// ## Returns
+/// A result of the encrypted vault as bytes or a [`FortressError`].
+pub fn convert_cipher(
+    encrypted_data: &[u8],
+    key: &VaultKey,
+    cipher: Cipher,
+) -> Result<Vec<u8>, FortressError> {
+    let (header, _) = VaultHeader::parse(encrypted_data)?;
+    let mut plaintext = decrypt_payload_with_key(encrypted_data, key)?;
+    let result = encrypt_payload_with_slots(&plaintext, key, cipher, &header.slots);
+    plaintext.zeroize();
+    result
+}
+
 /// Decrypt the password database
 /// ## Process
-/// 1. Check minimum file size (32 + 12 + 16 = 60 bytes minimum)
-/// 2. Extract components: [Salt: 32 bytes][Nonce: 12 bytes][Encrypted Data + Auth Tag]
-/// 3. Derive key from password
-/// 4. Create cipher and nonce
-/// 5. Decrypt
-/// 6. Parse JSON
-/// 7. Verify password check
+/// 1. Decrypt the payload with [`decrypt_payload`]
+/// 2. Parse JSON
+/// 3. Verify password check
 /// ## Parameters
 /// - `encrypted_data`: The encrypted database as bytes.
 /// - `master_password`: The password to decrypt data.
 /// ## Returns
-/// A result of the decrypted entries or a [`FortressError`].
-#[allow(deprecated)]
+/// A result of the decrypted data or a [`FortressError`].
 pub fn decrypt_database(
     encrypted_data: &[u8],
     master_password: &str,
-) -> Result<Vec<PasswordEntry>, FortressError> {
-    if encrypted_data.len() < 60 {
-        return Err(FortressError::CorruptedVault);
-    }
+) -> Result<VaultData, FortressError> {
+    let plaintext = decrypt_payload(encrypted_data, master_password)?;
+    parse_database(&plaintext)
+}
 
-    let salt: [u8; 32] = encrypted_data[0..32]
-        .try_into()
-        .map_err(|_| FortressError::CorruptedVault)?;
-    let nonce_bytes: [u8; 12] = encrypted_data[32..44]
-        .try_into()
-        .map_err(|_| FortressError::CorruptedVault)?;
-    let ciphertext = &encrypted_data[44..];
+/// Decrypt the password database with an already derived key.
+/// ## Parameters
+/// - `encrypted_data`: The encrypted database as bytes.
+/// - `key`: The key derived from the master password.
+/// ## Returns
+/// A result of the decrypted data or a [`FortressError`].
+pub fn decrypt_database_with_key(
+    encrypted_data: &[u8],
+    key: &VaultKey,
+) -> Result<VaultData, FortressError> {
+    let plaintext = decrypt_payload_with_key(encrypted_data, key)?;
+    parse_database(&plaintext)
+}
 
-    let key_bytes = derive_key(master_password, &salt)?;
-    let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
+/// Parse the decrypted JSON and verify the password check.
+fn parse_database(plaintext: &[u8]) -> Result<VaultData, FortressError> {
+    let json_str = std::str::from_utf8(plaintext).map_err(|_| FortressError::CorruptedVault)?;
 
-    let cipher = Aes256Gcm::new(key);
-    let nonce = Nonce::from_slice(&nonce_bytes);
+    let wrapper: DatabaseWrapper = serde_json::from_str(json_str)?;
 
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext)
-        .map_err(|_| FortressError::InvalidMasterPassword)?;
+    if wrapper._pwcheck != "valid" {
+        return Err(FortressError::InvalidMasterPassword);
+    }
 
-    let json_str = std::str::from_utf8(&plaintext).map_err(|_| FortressError::CorruptedVault)?;
+    Ok(wrapper.data)
+}
 
-    let wrapper: DatabaseWrapper = serde_json::from_str(json_str)?;
+/// Decrypt a raw payload
+/// ## Process
+/// 1. Check minimum file size (32 + 12 + 16 = 60 bytes minimum)
+/// 2. Derive key from password and the salt of the header, or unwrap it from a password slot
+/// 3. Decrypt with [`decrypt_payload_with_key`]
+/// ## Parameters
+/// - `encrypted_data`: The encrypted payload as bytes.
+/// - `master_password`: The password to decrypt data.
+/// ## Returns
+/// A result of the plaintext or a [`FortressError`].
+/// [`FortressError::InvalidMasterPassword`] means the authentication tag was rejected.
+pub fn decrypt_payload(
+    encrypted_data: &[u8],
+    master_password: &str,
+) -> Result<Vec<u8>, FortressError> {
+    let (header, _) = VaultHeader::parse(encrypted_data)?;
+    let key = if header.slots.is_empty() {
+        VaultKey {
+            salt: header.salt,
+            key: derive_key(master_password, &header.salt)?,
+        }
+    } else {
+        VaultKey::unlock(encrypted_data, master_password)?
+    };
+    decrypt_payload_with_key(encrypted_data, &key)
+}
 
-    if wrapper._pwcheck != "valid" {
+/// Decrypt a raw payload with an already derived key.
+/// ## Parameters
+/// - `encrypted_data`: The encrypted payload as bytes.
+/// - `key`: The key derived from the master password.
+/// ## Returns
+/// A result of the plaintext or a [`FortressError`].
+/// [`FortressError::InvalidMasterPassword`] means the salt of the vault changed or the tag was rejected.
+pub fn decrypt_payload_with_key(
+    encrypted_data: &[u8],
+    key: &VaultKey,
+) -> Result<Vec<u8>, FortressError> {
+    let (header, ciphertext) = VaultHeader::parse(encrypted_data)?;
+    if header.salt != key.salt {
         return Err(FortressError::InvalidMasterPassword);
     }
 
-    Ok(wrapper.entries)
+    header.cipher.decrypt(key, &header.nonce, ciphertext)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::helpers::errors::FortressError;
+    use crate::helpers::structs::PasswordEntry;
 
     #[test]
     fn test_encrypt_decrypt_cycle() {
@@ -150,23 +1135,30 @@ mod tests {
                 identifier: "Gmail".to_string(),
                 username: "user@gmail.com".to_string(),
                 password: "super_secret_123".to_string(),
+                ..Default::default()
             },
             PasswordEntry {
                 identifier: "GitHub".to_string(),
                 username: "developer".to_string(),
                 password: "github_token_456".to_string(),
+                ..Default::default()
             },
         ];
 
         let master_password = "my_master_password";
+        let data = VaultData {
+            entries: entries.clone(),
+            ..Default::default()
+        };
 
         // Encrypt
         let encrypted =
-            encrypt_database(&entries, master_password).expect("Encryption should succeed");
+            encrypt_database(&data, master_password).expect("Encryption should succeed");
 
         // Decrypt
-        let decrypted =
-            decrypt_database(&encrypted, master_password).expect("Decryption should succeed");
+        let decrypted = decrypt_database(&encrypted, master_password)
+            .expect("Decryption should succeed")
+            .entries;
 
         assert_eq!(entries.len(), decrypted.len());
         assert_eq!(entries[0].identifier, decrypted[0].identifier);
@@ -176,16 +1168,134 @@ mod tests {
 
     #[test]
     fn test_wrong_password() {
-        let entries = vec![PasswordEntry {
-            identifier: "Test".to_string(),
-            username: "test".to_string(),
-            password: "test123".to_string(),
-        }];
+        let data = VaultData {
+            entries: vec![PasswordEntry {
+                identifier: "Test".to_string(),
+                username: "test".to_string(),
+                password: "test123".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
 
         let encrypted =
-            encrypt_database(&entries, "correct_password").expect("Encryption should succeed");
+            encrypt_database(&data, "correct_password").expect("Encryption should succeed");
 
         let result = decrypt_database(&encrypted, "wrong_password");
         assert!(matches!(result, Err(FortressError::InvalidMasterPassword)));
     }
+
+    #[test]
+    fn test_decrypt_vault_without_trash() {
+        let payload = br#"{"_pwcheck":"valid","entries":[]}"#;
+        let encrypted = encrypt_payload(payload, "password").expect("Encryption should succeed");
+
+        let data = decrypt_database(&encrypted, "password").expect("Decryption should succeed");
+        assert!(data.trash.is_empty());
+        assert_eq!(
+            data.trash_retention_days,
+            crate::helpers::structs::TRASH_RETENTION_DAYS
+        );
+    }
+
+    #[test]
+    fn test_header_too_short() {
+        let result = VaultHeader::parse(&[0u8; MIN_VAULT_LEN - 1]);
+        assert!(matches!(result, Err(FortressError::CorruptedVault)));
+
+        let data = [7u8; MIN_VAULT_LEN];
+        let (header, ciphertext) = VaultHeader::parse(&data).expect("header should parse");
+        assert_eq!(header.salt, [7u8; SALT_LEN]);
+        assert_eq!(ciphertext.len(), TAG_LEN);
+    }
+
+    #[test]
+    fn test_key_slots() {
+        let identity = Identity::generate();
+        let parsed: Identity = format!("# comment\n{}\n", identity).parse().unwrap();
+        assert_eq!(parsed.recipient(), identity.recipient());
+        let recipient: Recipient = identity.recipient().to_string().parse().unwrap();
+        assert_eq!(recipient, identity.recipient());
+        assert!(matches!(
+            "frtrs1zz".parse::<Recipient>(),
+            Err(FortressError::InvalidKey(_))
+        ));
+
+        let key = VaultKey::random();
+        let slots = vec![
+            KeySlot::password(&key, "password").unwrap(),
+            KeySlot::recipient(&key, &recipient).unwrap(),
+        ];
+        let encrypted =
+            encrypt_payload_with_slots(b"payload", &key, Cipher::Aes256Gcm, &slots).unwrap();
+        let (header, _) = VaultHeader::parse(&encrypted).unwrap();
+        assert_eq!(header.salt, key.salt);
+        assert_eq!(header.slots, slots);
+
+        assert_eq!(decrypt_payload(&encrypted, "password").unwrap(), b"payload");
+        let unlocked = VaultKey::unlock_with_identity(&encrypted, &identity).unwrap();
+        assert_eq!(unlocked.key, key.key);
+        assert!(matches!(
+            VaultKey::unlock(&encrypted, "wrong"),
+            Err(FortressError::InvalidMasterPassword)
+        ));
+        assert!(VaultKey::unlock_with_identity(&encrypted, &Identity::generate()).is_err());
+    }
+
+    #[test]
+    fn test_ciphers() {
+        assert_eq!(
+            "XChaCha20-Poly1305".parse::<Cipher>().unwrap(),
+            Cipher::XChaCha20Poly1305
+        );
+        assert_eq!(
+            Cipher::Aes256Gcm.to_string().parse::<Cipher>().unwrap(),
+            Cipher::Aes256Gcm
+        );
+        assert!(matches!(
+            "des".parse::<Cipher>(),
+            Err(FortressError::Unsupported(_))
+        ));
+
+        let key = VaultKey::random();
+        let slots = vec![KeySlot::password(&key, "password").unwrap()];
+        let xchacha =
+            encrypt_payload_with_slots(b"payload", &key, Cipher::XChaCha20Poly1305, &slots)
+                .unwrap();
+        let (header, ciphertext) = VaultHeader::parse(&xchacha).unwrap();
+        assert_eq!(header.cipher, Cipher::XChaCha20Poly1305);
+        assert_eq!(header.nonce.len(), XNONCE_LEN);
+        assert_eq!(ciphertext.len(), b"payload".len() + TAG_LEN);
+        assert_eq!(decrypt_payload(&xchacha, "password").unwrap(), b"payload");
+        assert!(
+            encrypt_payload_with_slots(b"payload", &key, Cipher::XChaCha20Poly1305, &[]).is_err()
+        );
+
+        let aes = convert_cipher(&xchacha, &key, Cipher::Aes256Gcm).unwrap();
+        let (header, _) = VaultHeader::parse(&aes).unwrap();
+        assert_eq!(header.cipher, Cipher::Aes256Gcm);
+        assert_eq!(header.nonce.len(), NONCE_LEN);
+        assert_eq!(header.slots, slots);
+        assert_eq!(decrypt_payload_with_key(&aes, &key).unwrap(), b"payload");
+        let back = convert_cipher(&aes, &key, Cipher::XChaCha20Poly1305).unwrap();
+        assert_eq!(decrypt_payload(&back, "password").unwrap(), b"payload");
+
+        // The ciphers do not open each other's payload.
+        let mut swapped = xchacha.clone();
+        swapped[MAGIC.len() + 1] = Cipher::Aes256Gcm.id();
+        assert!(decrypt_payload_with_key(&swapped, &key).is_err());
+        swapped[MAGIC.len() + 1] = 7;
+        assert!(matches!(
+            VaultHeader::parse(&swapped),
+            Err(FortressError::CorruptedVault)
+        ));
+
+        // The version 2 header, without the cipher byte, is still read as AES-256-GCM.
+        let mut version_2 = aes.clone();
+        version_2[MAGIC.len()] = AES_SLOTS_VERSION;
+        version_2.remove(MAGIC.len() + 1);
+        let (header, _) = VaultHeader::parse(&version_2).unwrap();
+        assert_eq!(header.cipher, Cipher::Aes256Gcm);
+        assert_eq!(decrypt_payload(&version_2, "password").unwrap(), b"payload");
+    }
 }
@@ -0,0 +1,432 @@
+//! C interface of the library, built into the `cdylib` and described by `include/fortress.h`.
+//!
+//! A vault is an opaque handle opened with `fortress_open` and released with `fortress_close`.
+//! The functions return a [`FortressStatus`]; the message of the last error of the thread
+//! is given by `fortress_last_error`. The strings handed out are owned by the caller and must be
+//! released with `fortress_free`, which wipes them before freeing the memory.
+//! Files are read and written with the same format as the CLI.
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::PasswordEntry;
+use crate::vault::{EntryUpdate, Vault};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use zeroize::Zeroize;
+
+/// An unlocked vault, opaque to C.
+pub struct FortressVault(Vault);
+
+/// The result of a call.
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FortressStatus {
+    Ok = 0,
+    /// A pointer is null or a string is not UTF-8.
+    InvalidArgument = 1,
+    VaultNotFound = 2,
+    VaultAlreadyExists = 3,
+    /// The master password is wrong or the vault is corrupted.
+    DecryptionFailed = 4,
+    WeakPassword = 5,
+    IdNotFound = 6,
+    IdAlreadyExists = 7,
+    UnknownField = 8,
+    /// Any other error, see `fortress_last_error`.
+    Failed = 9,
+}
+
+thread_local! {
+    /// The message of the last error of the thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The error of a call: an invalid argument, or an error of the vault.
+enum CallError {
+    InvalidArgument(&'static str),
+    Vault(FortressError),
+}
+
+impl From<FortressError> for CallError {
+    fn from(error: FortressError) -> Self {
+        CallError::Vault(error)
+    }
+}
+
+impl From<&FortressError> for FortressStatus {
+    fn from(error: &FortressError) -> Self {
+        match error {
+            FortressError::VaultNotFound => FortressStatus::VaultNotFound,
+            FortressError::VaultAlreadyExists => FortressStatus::VaultAlreadyExists,
+            FortressError::DecryptionFailed
+            | FortressError::InvalidMasterPassword
+            | FortressError::CorruptedVault => FortressStatus::DecryptionFailed,
+            FortressError::WeakPassword => FortressStatus::WeakPassword,
+            FortressError::IdNotFound(..) => FortressStatus::IdNotFound,
+            FortressError::IdAlreadyExists(_) => FortressStatus::IdAlreadyExists,
+            FortressError::UnknownField(_) => FortressStatus::UnknownField,
+            _ => FortressStatus::Failed,
+        }
+    }
+}
+
+/// Run a call, recording its error and stopping panics at the boundary.
+fn guard(call: impl FnOnce() -> Result<(), CallError>) -> FortressStatus {
+    let (status, message) = match panic::catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(())) => (FortressStatus::Ok, None),
+        Ok(Err(CallError::InvalidArgument(reason))) => (
+            FortressStatus::InvalidArgument,
+            Some(format!("InvalidArgument: {}", reason)),
+        ),
+        Ok(Err(CallError::Vault(e))) => (FortressStatus::from(&e), Some(e.to_string())),
+        Err(_) => (FortressStatus::Failed, Some("internal error".to_string())),
+    };
+    LAST_ERROR.with(|last| {
+        *last.borrow_mut() = message.and_then(|message| CString::new(message).ok());
+    });
+    status
+}
+
+/// Read a required C string.
+///
+/// # Safety
+/// `value` must be null or point to a NUL-terminated string.
+unsafe fn read(value: *const c_char) -> Result<String, CallError> {
+    if value.is_null() {
+        return Err(invalid("null pointer"));
+    }
+    // SAFETY: guaranteed by the caller.
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map(str::to_string)
+        .map_err(|_| invalid("string is not UTF-8"))
+}
+
+/// Read an optional C string, null meaning none.
+///
+/// # Safety
+/// `value` must be null or point to a NUL-terminated string.
+unsafe fn read_optional(value: *const c_char) -> Result<Option<String>, CallError> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: guaranteed by the caller.
+    unsafe { read(value) }.map(Some)
+}
+
+/// The error of an invalid argument.
+fn invalid(reason: &'static str) -> CallError {
+    CallError::InvalidArgument(reason)
+}
+
+/// Hand a vault out to C.
+///
+/// # Safety
+/// `out` must be null or valid for writes.
+unsafe fn hand_out(vault: Vault, out: *mut *mut FortressVault) -> Result<(), CallError> {
+    if out.is_null() {
+        return Err(invalid("null pointer"));
+    }
+    // SAFETY: guaranteed by the caller.
+    unsafe { *out = Box::into_raw(Box::new(FortressVault(vault))) };
+    Ok(())
+}
+
+/// Open an existing vault.
+///
+/// # Safety
+/// `path` and `password` must be NUL-terminated strings, `out` must be valid for writes.
+/// On success, `*out` must be released with `fortress_close`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fortress_open(
+    path: *const c_char,
+    password: *const c_char,
+    out: *mut *mut FortressVault,
+) -> FortressStatus {
+    guard(|| {
+        // SAFETY: guaranteed by the caller.
+        let (path, mut password) = unsafe { (read(path)?, read(password)?) };
+        let vault = Vault::open(&path, &password);
+        password.zeroize();
+        // SAFETY: guaranteed by the caller.
+        unsafe { hand_out(vault?, out) }
+    })
+}
+
+/// Create an empty vault, failing if the file exists.
+///
+/// # Safety
+/// `path` and `password` must be NUL-terminated strings, `out` must be valid for writes.
+/// On success, `*out` must be released with `fortress_close`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fortress_create(
+    path: *const c_char,
+    password: *const c_char,
+    out: *mut *mut FortressVault,
+) -> FortressStatus {
+    guard(|| {
+        // SAFETY: guaranteed by the caller.
+        let (path, mut password) = unsafe { (read(path)?, read(password)?) };
+        let vault = Vault::create(&path, &password, false);
+        password.zeroize();
+        // SAFETY: guaranteed by the caller.
+        unsafe { hand_out(vault?, out) }
+    })
+}
+
+/// Get a field of an entry: `identifier`, `username`, `password`, `url` or `tags`.
+///
+/// # Safety
+/// `vault` must come from `fortress_open` or `fortress_create`, `identifier` must be a
+/// NUL-terminated string, `field` may be null for the password, `out` must be valid for writes.
+/// On success, `*out` must be released with `fortress_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fortress_get(
+    vault: *const FortressVault,
+    identifier: *const c_char,
+    field: *const c_char,
+    out: *mut *mut c_char,
+) -> FortressStatus {
+    guard(|| {
+        if vault.is_null() || out.is_null() {
+            return Err(invalid("null pointer"));
+        }
+        // SAFETY: guaranteed by the caller.
+        let (vault, identifier, field) =
+            unsafe { (&(*vault).0, read(identifier)?, read_optional(field)?) };
+        let field = field.unwrap_or_else(|| "password".to_string());
+        let value = vault
+            .get(&identifier)?
+            .field(&field)
+            .ok_or_else(|| FortressError::UnknownField(format!("{}#{}", identifier, field)))?;
+        let value = CString::new(value).map_err(|_| invalid("value contains a NUL byte"))?;
+        // SAFETY: guaranteed by the caller.
+        unsafe { *out = value.into_raw() };
+        Ok(())
+    })
+}
+
+/// Set the username and the password of an entry, creating it if needed.
+/// The replaced password is kept in the history. The vault is saved.
+///
+/// # Safety
+/// `vault` must come from `fortress_open` or `fortress_create`, `identifier` and `password`
+/// must be NUL-terminated strings, `username` may be null to keep the current one.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fortress_set(
+    vault: *mut FortressVault,
+    identifier: *const c_char,
+    username: *const c_char,
+    password: *const c_char,
+) -> FortressStatus {
+    guard(|| {
+        if vault.is_null() {
+            return Err(invalid("null pointer"));
+        }
+        // SAFETY: guaranteed by the caller.
+        let (vault, identifier, username, password) = unsafe {
+            (
+                &mut (*vault).0,
+                read(identifier)?,
+                read_optional(username)?,
+                read(password)?,
+            )
+        };
+        if vault.get(&identifier).is_ok() {
+            vault.update(
+                &identifier,
+                EntryUpdate {
+                    username,
+                    password: Some(password),
+                    ..Default::default()
+                },
+            )?;
+        } else {
+            vault.insert(PasswordEntry {
+                identifier,
+                username: username.unwrap_or_default(),
+                password,
+                ..Default::default()
+            })?;
+        }
+        Ok(vault.save()?)
+    })
+}
+
+/// Move an entry to the trash. The vault is saved.
+///
+/// # Safety
+/// `vault` must come from `fortress_open` or `fortress_create`, `identifier` must be a
+/// NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fortress_remove(
+    vault: *mut FortressVault,
+    identifier: *const c_char,
+) -> FortressStatus {
+    guard(|| {
+        if vault.is_null() {
+            return Err(invalid("null pointer"));
+        }
+        // SAFETY: guaranteed by the caller.
+        let (vault, identifier) = unsafe { (&mut (*vault).0, read(identifier)?) };
+        vault.remove(&identifier, false)?;
+        Ok(vault.save()?)
+    })
+}
+
+/// The message of the last error of the calling thread, or null.
+/// The message is owned by the library and valid until the next call on the thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn fortress_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Wipe and release a string handed out by the library. Null is ignored.
+///
+/// # Safety
+/// `value` must be null or come from `fortress_get`, and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fortress_free(value: *mut c_char) {
+    if value.is_null() {
+        return;
+    }
+    // SAFETY: guaranteed by the caller, the string comes from `CString::into_raw`.
+    let mut bytes = unsafe { CString::from_raw(value) }.into_bytes_with_nul();
+    bytes.zeroize();
+}
+
+/// Close a vault, wiping its key. Null is ignored.
+///
+/// # Safety
+/// `vault` must be null or come from `fortress_open` or `fortress_create`,
+/// and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn fortress_close(vault: *mut FortressVault) {
+    if !vault.is_null() {
+        // SAFETY: guaranteed by the caller, the vault comes from `Box::into_raw`.
+        drop(unsafe { Box::from_raw(vault) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    fn c(value: &str) -> CString {
+        CString::new(value).unwrap()
+    }
+
+    /// Get a field through the C interface.
+    fn get(vault: *const FortressVault, identifier: &str, field: Option<&str>) -> String {
+        let field = field.map(c);
+        let mut out = ptr::null_mut();
+        let status = unsafe {
+            fortress_get(
+                vault,
+                c(identifier).as_ptr(),
+                field.as_ref().map_or(ptr::null(), |field| field.as_ptr()),
+                &mut out,
+            )
+        };
+        assert_eq!(status, FortressStatus::Ok);
+        let value = unsafe { CStr::from_ptr(out) }.to_str().unwrap().to_string();
+        unsafe { fortress_free(out) };
+        value
+    }
+
+    #[test]
+    fn test_c_interface() {
+        let path = tmp_path("ffi_test");
+        cleanup(&path);
+        let (file, password) = (c(&path), c("S3cureP@ssword"));
+        let mut vault = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                fortress_open(file.as_ptr(), password.as_ptr(), &mut vault),
+                FortressStatus::VaultNotFound
+            );
+            assert!(!fortress_last_error().is_null());
+            assert_eq!(
+                fortress_create(file.as_ptr(), password.as_ptr(), &mut vault),
+                FortressStatus::Ok
+            );
+            assert!(fortress_last_error().is_null());
+            assert_eq!(
+                fortress_set(
+                    vault,
+                    c("db").as_ptr(),
+                    c("admin").as_ptr(),
+                    c("pw1").as_ptr()
+                ),
+                FortressStatus::Ok
+            );
+            assert_eq!(
+                fortress_set(vault, c("db").as_ptr(), ptr::null(), c("pw2").as_ptr()),
+                FortressStatus::Ok
+            );
+            fortress_close(vault);
+        }
+
+        // The vault is written with the format of the CLI.
+        let data = crate::helpers::load_vault(crate::helpers::structs::GeneralArgs::new(
+            path.clone(),
+            "S3cureP@ssword".to_string(),
+        ))
+        .unwrap();
+        assert_eq!(data.entries[0].history[0].password, "pw1");
+
+        let mut vault = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                fortress_open(file.as_ptr(), c("wrong").as_ptr(), &mut vault),
+                FortressStatus::DecryptionFailed
+            );
+            assert_eq!(
+                fortress_open(file.as_ptr(), password.as_ptr(), &mut vault),
+                FortressStatus::Ok
+            );
+        }
+        assert_eq!(get(vault, "db", None), "pw2");
+        assert_eq!(get(vault, "db", Some("username")), "admin");
+        let mut out = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                fortress_get(vault, c("db").as_ptr(), c("url").as_ptr(), &mut out),
+                FortressStatus::UnknownField
+            );
+            assert_eq!(
+                fortress_get(vault, ptr::null(), ptr::null(), &mut out),
+                FortressStatus::InvalidArgument
+            );
+            assert_eq!(fortress_remove(vault, c("db").as_ptr()), FortressStatus::Ok);
+            assert_eq!(
+                fortress_get(vault, c("db").as_ptr(), ptr::null(), &mut out),
+                FortressStatus::IdNotFound
+            );
+            assert!(out.is_null());
+            fortress_close(vault);
+            fortress_free(ptr::null_mut());
+        }
+        cleanup(&path);
+    }
+}
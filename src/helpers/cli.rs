@@ -1,6 +1,22 @@
 //! CLI related structs and functions.
+use crate::crypto::Cipher;
 use clap::{Parser, Subcommand};
 
+/// Environment variable setting the path of the vault.
+pub const VAULT_ENV: &str = "FRTRS_VAULT";
+
+/// Environment variable setting the secret key opening the vault.
+pub const IDENTITY_ENV: &str = "FRTRS_IDENTITY";
+
+/// Environment variable setting the keyfile opening the vault.
+pub const KEYFILE_ENV: &str = "FRTRS_KEYFILE";
+
+/// Path of the vault when none is given.
+pub const DEFAULT_VAULT: &str = "/tmp/vault.frt";
+
+/// Default path of the log file.
+pub const DEFAULT_LOG_FILE: &str = "/tmp/fortress.log";
+
 /// The CLI context.
 #[derive(Parser)]
 #[command(name = "frt-rs", version, about, long_about = None)]
@@ -11,11 +27,25 @@ pub struct Cli {
     pub command: Option<Commands>,
 
     /// The input file path
-    #[arg(short, long, value_name = "PATH", default_value = "/tmp/vault.frt")]
+    #[arg(
+        short,
+        long,
+        value_name = "PATH",
+        env = VAULT_ENV,
+        default_value = DEFAULT_VAULT
+    )]
     pub file: Option<String>,
 
+    /// Open the vault with the secret key of one of its recipients instead of the master password
+    #[arg(long, value_name = "PATH", env = IDENTITY_ENV)]
+    pub identity: Option<String>,
+
+    /// Open the vault with a keyfile of one of its key slots instead of the master password
+    #[arg(long, value_name = "PATH", env = KEYFILE_ENV, conflicts_with = "identity")]
+    pub keyfile: Option<String>,
+
     /// Path to a file to write logs to
-    #[arg(long, value_name = "PATH", default_value = "/tmp/fortress.log")]
+    #[arg(long, value_name = "PATH", default_value = DEFAULT_LOG_FILE)]
     pub log_file: Option<String>,
 }
 
@@ -27,24 +57,32 @@ pub enum Commands {
         /// Overwrite the vault if it already exists
         #[arg(short, long)]
         force: bool,
+
+        /// The cipher encrypting the vault: aes-256-gcm or xchacha20-poly1305
+        #[arg(long, value_name = "CIPHER", default_value_t = Cipher::Aes256Gcm)]
+        cipher: Cipher,
     },
 
     /// Copy the password of the desired identifier
     Copy {
-        /// The identifier of the entry
-        identifier: String,
+        /// The identifier of the entry. Picked interactively if omitted
+        identifier: Option<String>,
     },
 
     /// View the password of the desired identifier
     View {
-        /// The identifier of the entry
-        identifier: String,
+        /// The identifier of the entry. Picked interactively if omitted
+        identifier: Option<String>,
     },
 
-    /// Remove an entry from the vault
+    /// Remove an entry from the vault. The entry is moved to the trash unless 'permanent' is set
     Remove {
-        /// The identifier of the entry to remove
-        identifier: String,
+        /// The identifier of the entry to remove. Picked interactively if omitted
+        identifier: Option<String>,
+
+        /// Remove the entry without moving it to the trash
+        #[arg(long)]
+        permanent: bool,
     },
 
     /// Add a new entry to the vault. If no one of the password methods is provided,
@@ -65,9 +103,402 @@ pub enum Commands {
         /// Direct password input. Mutually exclusive with 'generate'
         #[arg(short, long, conflicts_with = "generate")]
         password: Option<String>,
+
+        /// The URL of the service
+        #[arg(long)]
+        url: Option<String>,
+
+        /// A tag to organize the entry. Can be repeated
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Search entries by identifier, username, URL and tags, best matches first
+    Search {
+        /// The text to look for
+        query: String,
     },
+
     /// List all entries in the vault
+    List {
+        /// Only list the entries of this folder
+        prefix: Option<String>,
+    },
+
+    /// Show the entries as a tree of folders
+    Tree {
+        /// Only show the entries of this folder
+        prefix: Option<String>,
+    },
+
+    /// Edit an existing entry. The replaced password is kept in its history
+    Edit {
+        /// The identifier of the entry to edit. Picked interactively if omitted
+        identifier: Option<String>,
+
+        /// The new username or email address for the entry
+        #[arg(short, long)]
+        username: Option<String>,
+
+        /// Generate a new password. Mutually exclusive with 'password'
+        #[arg(short, long, conflicts_with = "password")]
+        generate: bool,
+
+        /// Direct password input. Mutually exclusive with 'generate'
+        #[arg(short, long, conflicts_with = "generate")]
+        password: Option<String>,
+
+        /// The new URL of the service
+        #[arg(long)]
+        url: Option<String>,
+
+        /// A tag to organize the entry, replacing the current ones. Can be repeated
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+
+    /// Rename an entry. A trailing '/' moves every entry of the folder
+    Mv {
+        /// The identifier of the entry, or the folder ending with '/'
+        source: String,
+
+        /// The new identifier of the entry, or the new folder
+        destination: String,
+    },
+
+    /// Show the previous passwords of the desired identifier
+    History {
+        /// The identifier of the entry
+        identifier: String,
+
+        /// Reveal the previous passwords
+        #[arg(short, long)]
+        show: bool,
+    },
+
+    /// Restore a previous password of the desired identifier
+    RestorePassword {
+        /// The identifier of the entry
+        identifier: String,
+
+        /// The version to restore, as numbered by 'history'
+        #[arg(long, value_name = "N")]
+        version: usize,
+    },
+
+    /// Check the integrity of the vault without modifying it
+    Check {},
+
+    /// Manage the entries removed from the vault
+    Trash {
+        /// The action to run on the trash
+        #[command(subcommand)]
+        command: TrashCommands,
+    },
+
+    /// Unlock the vault once and run commands in an interactive shell
+    Shell {
+        /// Lock the vault after this many seconds of inactivity. 0 never locks
+        #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+        lock_after: u64,
+    },
+
+    /// Run a command with secrets of the vault in its environment only
+    Run {
+        /// A variable to set, as NAME=IDENTIFIER or NAME=IDENTIFIER#FIELD (password by default)
+        #[arg(short, long = "env", value_name = "NAME=REFERENCE", value_parser = parse_env)]
+        env: Vec<(String, String)>,
+
+        /// The command to run, after '--'
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+
+    /// Render a template, replacing {{ frtrs://IDENTIFIER#FIELD }} with values of the vault
+    Inject {
+        /// The template to render
+        #[arg(short, long, value_name = "PATH")]
+        input: String,
+
+        /// The rendered file, created with mode 0600. Printed if omitted
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<String>,
+    },
+
+    /// Git credential helper, reading the protocol on stdin. Set 'credential.helper' to '!frtrs git-credential'
+    GitCredential {
+        /// The action asked by git: get, store or erase
+        operation: String,
+
+        /// Prefix of the identifiers of hosts without an entry matching their URL
+        #[arg(long, default_value = "git/")]
+        prefix: String,
+    },
+
+    /// Browser native messaging host, reading length-prefixed JSON messages on stdin
+    NativeHost {
+        /// The origin of the extension, given by the browser
+        #[arg(hide = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        caller: Vec<String>,
+    },
+
+    /// Serve a REST API on a Unix socket or on 127.0.0.1, for the holders of the tokens
+    Serve {
+        /// Listen on this Unix socket
+        #[arg(long, conflicts_with = "port", required_unless_present = "port")]
+        socket: Option<String>,
+
+        /// Listen on this port of 127.0.0.1
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// File of the accepted tokens: one '<token> <scope>...' per line, a scope being a folder or '*'
+        #[arg(long)]
+        tokens: String,
+    },
+
+    /// Expose the vault as a Secret Service collection on the session bus
+    SecretService {},
+
+    /// Write one entry in an encrypted bundle, for a public key or a passphrase
+    Share {
+        /// The identifier of the entry
+        identifier: String,
+
+        /// The public key of the recipient, written 'frtrs1...', or a passphrase
+        #[arg(long)]
+        to: String,
+
+        /// The bundle, created with mode 0600
+        #[arg(short, long, value_name = "PATH")]
+        output: String,
+    },
+
+    /// Import the entry of a bundle made by 'share'. Its passphrase is asked unless 'key' is set
+    Receive {
+        /// The bundle
+        file: String,
+
+        /// The secret key the bundle was shared with
+        #[arg(long, value_name = "PATH")]
+        key: Option<String>,
+
+        /// Import the entry under another identifier
+        #[arg(long = "as", value_name = "IDENTIFIER")]
+        rename: Option<String>,
+    },
+
+    /// Manage the public keys that can open the vault besides the master password
+    Recipients {
+        /// The action to run on the recipients
+        #[command(subcommand)]
+        command: RecipientsCommands,
+    },
+
+    /// Manage the key slots wrapping the data key, each opened by a password or a keyfile
+    Keyslot {
+        /// The action to run on the key slots
+        #[command(subcommand)]
+        command: KeyslotCommands,
+    },
+
+    /// Split a recovery key in shares, or combine them to set a new master password
+    Recovery {
+        /// The action to run on the recovery key
+        #[command(subcommand)]
+        command: RecoveryCommands,
+    },
+
+    /// Encrypt the vault again with another cipher, keeping its key slots
+    Convert {
+        /// The new cipher: aes-256-gcm or xchacha20-poly1305
+        #[arg(long, value_name = "CIPHER")]
+        cipher: Cipher,
+    },
+
+    /// Keep the unlocked key in memory so that the next commands skip the master password
+    Agent {
+        /// Wipe the key after this many seconds. 0 keeps it until 'lock'
+        #[arg(long, value_name = "SECONDS", default_value_t = 900)]
+        ttl: u64,
+    },
+
+    /// Wipe the key kept by the agent and stop it
+    Lock {},
+
+    /// Browse and edit the vault in a full-screen terminal UI
+    Tui {
+        /// Lock the vault after this many seconds of inactivity. 0 never locks
+        #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+        lock_after: u64,
+    },
+}
+
+/// Parse a `NAME=REFERENCE` environment variable of `run`.
+fn parse_env(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, reference)) if !name.is_empty() && !reference.is_empty() => {
+            Ok((name.to_string(), reference.to_string()))
+        }
+        _ => Err(format!("expected NAME=REFERENCE, got `{}`", value)),
+    }
+}
+
+impl Commands {
+    /// If true, the key kept by the agent can be used instead of the master password.
+    pub fn uses_agent(&self) -> bool {
+        !matches!(
+            self,
+            Commands::Create { .. }
+                | Commands::Check {}
+                | Commands::Recipients { .. }
+                | Commands::Recovery { .. }
+                | Commands::Agent { .. }
+                | Commands::Lock {}
+        )
+    }
+
+    /// If true, the command opens the vault, so the master password is needed
+    /// unless a key is given by the agent or an identity.
+    pub fn needs_password(&self) -> bool {
+        !matches!(
+            self,
+            Commands::Lock {}
+                | Commands::Recipients {
+                    command: RecipientsCommands::Keygen { .. } | RecipientsCommands::List {}
+                }
+                | Commands::Keyslot {
+                    command: KeyslotCommands::List {}
+                }
+                | Commands::Recovery {
+                    command: RecoveryCommands::Unlock {}
+                }
+        )
+    }
+
+    /// If true, the command is started by another program without a terminal, so the master
+    /// password is never asked: without a key from the agent or an identity, it runs locked.
+    pub fn is_headless(&self) -> bool {
+        matches!(self, Commands::NativeHost { .. })
+    }
+
+    /// If true, the command reads its own input from stdin, so the master password
+    /// is asked on the terminal instead of being read from stdin.
+    pub fn reads_stdin(&self) -> bool {
+        matches!(
+            self,
+            Commands::GitCredential { .. } | Commands::NativeHost { .. }
+        )
+    }
+}
+
+/// A line typed in the shell, parsed with the same grammar as the command line.
+#[derive(Parser)]
+#[command(multicall = true)]
+pub struct ShellLine {
+    /// The command to run
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+/// The actions that can be run on the trash.
+#[derive(Subcommand)]
+pub enum TrashCommands {
+    /// List all entries in the trash
     List {},
+
+    /// Restore an entry from the trash
+    Restore {
+        /// The identifier of the entry to restore
+        identifier: String,
+    },
+
+    /// Permanently remove all entries in the trash
+    Empty {},
+
+    /// Set the number of days an entry is kept in the trash. 0 keeps them forever
+    Retention {
+        /// The retention period in days
+        days: u64,
+    },
+}
+
+/// The actions that can be run on the recipients of the vault.
+#[derive(Subcommand)]
+pub enum RecipientsCommands {
+    /// List the master password and the public keys that open the vault
+    List {},
+
+    /// Wrap the data key for new public keys. The data key is kept
+    Add {
+        /// The public keys, written 'frtrs1...'
+        #[arg(required = true)]
+        recipients: Vec<String>,
+
+        /// Only the recipients open the vault, not the master password
+        #[arg(long)]
+        no_password: bool,
+
+        /// With --no-password, drop the keyfile, recovery and other password slots,
+        /// which cannot wrap a new data key
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Stop wrapping the data key for public keys. The data key is changed
+    Remove {
+        /// The public keys, written 'frtrs1...'
+        #[arg(required = true)]
+        recipients: Vec<String>,
+
+        /// Drop the keyfile, recovery and other password slots, which cannot wrap a new data key
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Generate a secret key and print its public key
+    Keygen {
+        /// The file of the secret key, created with mode 0600
+        #[arg(short, long, value_name = "PATH")]
+        output: String,
+    },
+}
+
+/// The actions that can be run on the key slots of the vault.
+#[derive(Subcommand)]
+pub enum KeyslotCommands {
+    /// List the key slots with their index
+    List {},
+
+    /// Wrap the data key in a new slot, for a new password asked twice or a keyfile
+    Add {
+        /// Open the new slot with this keyfile instead of a password
+        #[arg(long, value_name = "PATH")]
+        keyfile: Option<String>,
+    },
+
+    /// Remove a key slot. The data key is unchanged
+    Remove {
+        /// The index of the slot, as listed
+        index: usize,
+    },
+}
+
+/// The actions that can be run on the recovery key of the vault.
+#[derive(Subcommand)]
+pub enum RecoveryCommands {
+    /// Wrap the data key with a new recovery key and print its shares. The previous shares are revoked
+    Split {
+        /// The number of shares
+        #[arg(long, default_value_t = 5)]
+        shares: u8,
+
+        /// The number of shares needed to open the vault
+        #[arg(long, default_value_t = 3)]
+        threshold: u8,
+    },
+
+    /// Open the vault with enough shares and set a new master password
+    Unlock {},
 }
 
 #[cfg(test)]
@@ -80,9 +511,50 @@ mod tests {
         matches!(cli.command, Some(Commands::Add { .. }));
     }
 
+    #[test]
+    fn test_cli_parse_trash() {
+        let cli = Cli::parse_from(["frt-rs", "trash", "restore", "id"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Trash {
+                command: TrashCommands::Restore { .. }
+            })
+        ));
+    }
+
+    #[test]
+    fn test_cli_parse_run() {
+        let cli = Cli::parse_from([
+            "frt-rs",
+            "run",
+            "--env",
+            "DB_PASS=prod/db",
+            "--",
+            "./deploy.sh",
+            "--env",
+        ]);
+        match cli.command {
+            Some(Commands::Run { env, command }) => {
+                assert_eq!(env, vec![("DB_PASS".to_string(), "prod/db".to_string())]);
+                assert_eq!(command, vec!["./deploy.sh", "--env"]);
+            }
+            _ => panic!("expected the run command"),
+        }
+        assert!(Cli::try_parse_from(["frt-rs", "run", "--env", "DB_PASS", "--", "ls"]).is_err());
+    }
+
     #[test]
     fn test_cli_parse_create() {
         let cli = Cli::parse_from(["frt-rs", "create", "--force"]);
         matches!(cli.command, Some(Commands::Create { .. }));
+        let cli = Cli::parse_from(["frt-rs", "create", "--cipher", "xchacha20-poly1305"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Create {
+                cipher: Cipher::XChaCha20Poly1305,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["frt-rs", "convert", "--cipher", "rot13"]).is_err());
     }
 }
@@ -1,6 +1,10 @@
 //! Error handling and types.
+use crate::helpers::fuzzy;
 use std::{fmt::Debug, io};
 
+/// Maximum number of identifiers suggested when one is not found.
+const SUGGESTIONS: usize = 3;
+
 /// The different errors that can be raised by the program. Names are self-explanatory.
 #[derive(Debug)]
 pub enum FortressError {
@@ -12,9 +16,21 @@ pub enum FortressError {
     SerializationError(serde_json::Error),
     InvalidMasterPassword,
     CorruptedVault,
-    IdNotFound(String),
+    IdNotFound(String, Vec<String>),
+    IdAlreadyExists(String),
     Clipboard(String),
     WeakPassword,
+    CheckFailed(usize),
+    VersionNotFound(String, usize),
+    NoSelection,
+    Unsupported(String),
+    Agent(String),
+    UnknownField(String),
+    ChildFailed(i32),
+    Dbus(zbus::Error),
+    InvalidKey(String),
+    Recovery(String),
+    VaultChanged,
 }
 
 /// Treat errors as errors.
@@ -57,8 +73,19 @@ impl std::fmt::Display for FortressError {
             }
             FortressError::IoError(e) => write!(f, "IoError: {}", e),
             FortressError::SerializationError(e) => write!(f, "SerializationError: {}", e),
-            FortressError::IdNotFound(id) => {
-                write!(f, "IdNotFoundError: `{}` not found in the vault", id)
+            FortressError::IdNotFound(id, suggestions) => {
+                write!(f, "IdNotFoundError: `{}` not found in the vault", id)?;
+                if !suggestions.is_empty() {
+                    write!(f, ". Did you mean `{}`?", suggestions.join("`, `"))?;
+                }
+                Ok(())
+            }
+            FortressError::IdAlreadyExists(id) => {
+                write!(
+                    f,
+                    "IdAlreadyExistsError: `{}` already exists in the vault",
+                    id
+                )
             }
             FortressError::Clipboard(pass) => write!(
                 f,
@@ -69,10 +96,54 @@ impl std::fmt::Display for FortressError {
                 f,
                 "WeakPasswordError: Your master password is not at the required strength."
             ),
+            FortressError::CheckFailed(count) => write!(
+                f,
+                "CheckFailedError: {} problem(s) found in the vault.",
+                count
+            ),
+            FortressError::NoSelection => {
+                write!(f, "NoSelectionError: No identifier was given nor picked.")
+            }
+            FortressError::Unsupported(what) => write!(f, "UnsupportedError: {}", what),
+            FortressError::Agent(reason) => write!(f, "AgentError: {}", reason),
+            FortressError::UnknownField(reference) => write!(
+                f,
+                "UnknownFieldError: `{}` does not reference a set field (identifier, username, password, url or tags)",
+                reference
+            ),
+            FortressError::Dbus(e) => write!(f, "DBusError: {}", e),
+            FortressError::InvalidKey(message) => write!(f, "InvalidKeyError: {}", message),
+            FortressError::Recovery(message) => write!(f, "RecoveryError: {}", message),
+            FortressError::VaultChanged => write!(
+                f,
+                "VaultChangedError: The key of the vault was changed on disk, reopen it."
+            ),
+            FortressError::ChildFailed(code) => {
+                write!(f, "ChildFailedError: The command exited with code {}", code)
+            }
+            FortressError::VersionNotFound(id, version) => write!(
+                f,
+                "VersionNotFoundError: `{}` has no password version {}",
+                id, version
+            ),
         }
     }
 }
 
+impl FortressError {
+    /// Build an [`FortressError::IdNotFound`] suggesting the closest existing identifiers.
+    /// ## Parameters:
+    /// - `identifier`: The identifier that was not found
+    /// - `candidates`: The existing identifiers
+    pub fn id_not_found<'a>(
+        identifier: String,
+        candidates: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        let suggestions = fuzzy::closest(&identifier, candidates, SUGGESTIONS);
+        FortressError::IdNotFound(identifier, suggestions)
+    }
+}
+
 /// Add `io` support for errors
 impl From<io::Error> for FortressError {
     fn from(error: io::Error) -> Self {
@@ -84,6 +155,13 @@ impl From<io::Error> for FortressError {
     }
 }
 
+/// Add `zbus` support for errors
+impl From<zbus::Error> for FortressError {
+    fn from(error: zbus::Error) -> Self {
+        FortressError::Dbus(error)
+    }
+}
+
 /// Add `serde` support for errors
 impl From<serde_json::Error> for FortressError {
     fn from(error: serde_json::Error) -> Self {
@@ -92,7 +170,12 @@ impl From<serde_json::Error> for FortressError {
 }
 
 /// Print the error message and exit the program with a non-zero exit code.
+/// A failed child command has already reported its error, so only its exit code is propagated.
 pub fn raise(error: FortressError) {
+    if let FortressError::ChildFailed(code) = error {
+        log::error!("Error: {}", error);
+        std::process::exit(code);
+    }
     log::error!("Error: {}", error);
     eprintln!("Error: {}", error);
     std::process::exit(1);
@@ -159,9 +242,14 @@ mod tests {
     #[test]
     fn test_display_id_not_found_and_clipboard() {
         let id = "missing_id".to_string();
-        let e = FortressError::IdNotFound(id.clone());
+        let e = FortressError::IdNotFound(id.clone(), vec![]);
         let s = format!("{}", e);
         assert!(s.contains(&id));
+        assert!(!s.contains("Did you mean"));
+
+        let e = FortressError::id_not_found("gihtub".to_string(), ["github", "bank"]);
+        let s = format!("{}", e);
+        assert!(s.contains("Did you mean `github`?"));
 
         let pw = "topsecret".to_string();
         let e2 = FortressError::Clipboard(pw.clone());
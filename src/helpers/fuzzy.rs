@@ -0,0 +1,131 @@
+//! Fuzzy matching of identifiers and other entry fields.
+//!
+//! - [`score`] ranks a candidate against a query, like a fuzzy finder.
+//! - [`closest`] finds the candidates looking like a mistyped query.
+
+/// Characters starting a new word in an identifier.
+const SEPARATORS: &[char] = &['/', '-', '_', '.', ' ', '@', ':'];
+
+/// Minimum similarity for a candidate to be suggested by [`closest`].
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// Score a candidate against a query, ignoring case.
+/// Exact, prefix and substring matches rank first, then candidates containing
+/// all characters of the query in order, with bonuses for consecutive characters
+/// and characters at the start of a word.
+/// ## Parameters:
+/// - `query`: The text typed by the user
+/// - `candidate`: The text to match
+/// ## Returns:
+/// The score, higher is better, or `None` if the candidate doesn't match.
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    let query = query.to_lowercase();
+    let candidate = candidate.to_lowercase();
+    let length_penalty = candidate.chars().count().min(100) as i64;
+
+    if query.is_empty() {
+        return Some(0);
+    }
+    if candidate == query {
+        return Some(3000);
+    }
+    if candidate.starts_with(&query) {
+        return Some(2000 - length_penalty);
+    }
+    if let Some(position) = candidate.find(&query) {
+        return Some(1000 - position.min(100) as i64 - length_penalty);
+    }
+
+    let mut score = 0;
+    let mut query_chars = query.chars().peekable();
+    let mut previous: Option<char> = None;
+    let mut consecutive = false;
+    for c in candidate.chars() {
+        match query_chars.peek() {
+            Some(&expected) if expected == c => {
+                query_chars.next();
+                score += 1;
+                if consecutive {
+                    score += 10;
+                }
+                if previous.is_none_or(|p| SEPARATORS.contains(&p)) {
+                    score += 8;
+                }
+                consecutive = true;
+            }
+            Some(_) => {
+                consecutive = false;
+                score -= 1;
+            }
+            None => break,
+        }
+        previous = Some(c);
+    }
+
+    if query_chars.peek().is_some() {
+        None
+    } else {
+        Some(score)
+    }
+}
+
+/// Find the candidates closest to a query, to suggest them on a typo.
+/// ## Parameters:
+/// - `query`: The text typed by the user
+/// - `candidates`: The texts to choose from
+/// - `limit`: The maximum number of suggestions
+/// ## Returns:
+/// The closest candidates, best first.
+pub fn closest<'a>(
+    query: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    limit: usize,
+) -> Vec<String> {
+    let query = query.to_lowercase();
+    let mut ranked: Vec<(f64, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let lowered = candidate.to_lowercase();
+            let similarity = strsim::normalized_damerau_levenshtein(&query, &lowered);
+            if similarity >= MIN_SIMILARITY || lowered.contains(&query) {
+                Some((similarity, candidate))
+            } else {
+                None
+            }
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+    ranked.dedup_by(|a, b| a.1 == b.1);
+    ranked
+        .into_iter()
+        .take(limit)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_ranking() {
+        let exact = score("github", "github").unwrap();
+        let prefix = score("git", "github").unwrap();
+        let substring = score("hub", "github").unwrap();
+        let fuzzy = score("wgh", "work/github").unwrap();
+        assert!(exact > prefix && prefix > substring && substring > fuzzy);
+        assert!(score("GitHub", "github").is_some());
+        assert!(score("xyz", "github").is_none());
+        assert!(score("wgh", "work/github") > score("wgh", "wxxgxxh"));
+    }
+
+    #[test]
+    fn test_closest() {
+        let candidates = ["work/github", "work/gitlab", "home/wifi"];
+        assert_eq!(
+            closest("work/gihtub", candidates, 3),
+            vec!["work/github", "work/gitlab"]
+        );
+        assert!(closest("bank", candidates, 3).is_empty());
+    }
+}
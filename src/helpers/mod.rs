@@ -3,21 +3,40 @@ use crate::crypto;
 use crate::helpers::errors::FortressError;
 use std::fs;
 use std::path::Path;
-use structs::{GeneralArgs, PasswordEntry};
+use std::time::{SystemTime, UNIX_EPOCH};
+use structs::{GeneralArgs, VaultData};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc2822;
+use zeroize::Zeroize;
 
 pub mod cli;
 pub mod errors;
+pub mod fuzzy;
 pub mod logger;
+pub mod picker;
 pub mod structs;
 
 /// Encrypts the vault and saves it to the file.
+/// A key unwrapped before the data key was rotated by another process is refused, the vault must be opened again.
 /// ## Parameters:
 /// - `args`: The context of the program
-/// - `entries`: The actual data
+/// - `data`: The actual data
 /// ## Returns:
 /// A result of nothing or a [`FortressError`]
-pub fn save_vault(args: GeneralArgs, entries: &[PasswordEntry]) -> Result<(), FortressError> {
-    let encrypted = match crypto::encrypt_database(entries, &args.password) {
+pub fn save_vault(args: GeneralArgs, data: &VaultData) -> Result<(), FortressError> {
+    let encrypted = match (&args.key, slotted_header(&args.file)) {
+        (Some(key), Some(header)) if header.salt == key.salt => {
+            crypto::encrypt_database_with_slots(data, key, header.cipher, &header.slots)
+        }
+        (Some(_), Some(_)) => return Err(FortressError::VaultChanged),
+        (Some(key), None) => crypto::encrypt_database_with_key(data, key),
+        (None, Some(header)) => {
+            let key = unlock_vault(&args)?;
+            crypto::encrypt_database_with_slots(data, &key, header.cipher, &header.slots)
+        }
+        (None, None) => crypto::encrypt_database(data, &args.password),
+    };
+    let encrypted = match encrypted {
         Ok(vault) => vault,
         Err(_) => return Err(FortressError::EncryptionFailed),
     };
@@ -32,11 +51,12 @@ pub fn save_vault(args: GeneralArgs, entries: &[PasswordEntry]) -> Result<(), Fo
 }
 
 /// Loads the vault from the file.
+/// Trashed entries older than the retention period are purged.
 /// ## Parameters:
 /// - `args`: The context of the program
 /// ## Returns:
-/// A result of a vector of [`PasswordEntry`] or a [`FortressError`]
-pub fn load_vault(args: GeneralArgs) -> Result<Vec<PasswordEntry>, FortressError> {
+/// A result of the [`VaultData`] or a [`FortressError`]
+pub fn load_vault(args: GeneralArgs) -> Result<VaultData, FortressError> {
     if !Path::new(&args.file).exists() {
         return Err(FortressError::VaultNotFound);
     }
@@ -46,33 +66,120 @@ pub fn load_vault(args: GeneralArgs) -> Result<Vec<PasswordEntry>, FortressError
         Err(e) => return Err(FortressError::IoError(e)),
     };
 
-    match crypto::decrypt_database(&encrypted, &args.password) {
-        Ok(entries) => {
+    let decrypted = match &args.key {
+        Some(key) => crypto::decrypt_database_with_key(&encrypted, key),
+        None => crypto::decrypt_database(&encrypted, &args.password),
+    };
+    match decrypted {
+        Ok(mut data) => {
             log::warn!("Vault Opened");
-            Ok(entries)
+            let purged = data.purge_trash(now());
+            if purged > 0 {
+                log::info!("Purged {} entries from the trash", purged);
+            }
+            Ok(data)
         }
         Err(_) => Err(FortressError::DecryptionFailed),
     }
 }
 
-/// Generates a random password of the given length.
-/// The password is copied to the clipboard.
+/// Reads the header of the vault file if it has key slots.
+/// Its cipher and slots are kept when the vault is saved with their data key.
+/// ## Parameters:
+/// - `file`: The path of the vault
+/// ## Returns:
+/// The header, or `None` if the vault has no slot.
+fn slotted_header(file: &str) -> Option<crypto::VaultHeader> {
+    let encrypted = fs::read(file).ok()?;
+    let (header, _) = crypto::VaultHeader::parse(&encrypted).ok()?;
+    (!header.slots.is_empty()).then_some(header)
+}
+
+/// Derives the key of the vault once, so that the next loads and saves skip the key derivation.
+/// ## Parameters:
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of the [`crypto::VaultKey`] or a [`FortressError`]
+pub fn unlock_vault(args: &GeneralArgs) -> Result<crypto::VaultKey, FortressError> {
+    if !Path::new(&args.file).exists() {
+        return Err(FortressError::VaultNotFound);
+    }
+    let encrypted = fs::read(&args.file)?;
+    crypto::VaultKey::unlock(&encrypted, &args.password)
+        .map_err(|_| FortressError::DecryptionFailed)
+}
+
+/// Unwraps the key of the vault with the secret key of one of its recipients, read from a file.
+/// ## Parameters:
+/// - `file`: The path of the vault
+/// - `identity`: The path of the file of the secret key
+/// ## Returns:
+/// A result of the [`crypto::VaultKey`] or a [`FortressError`]
+pub fn unlock_with_identity(file: &str, identity: &str) -> Result<crypto::VaultKey, FortressError> {
+    if !Path::new(file).exists() {
+        return Err(FortressError::VaultNotFound);
+    }
+    let encrypted = fs::read(file)?;
+    crypto::VaultKey::unlock_with_identity(&encrypted, &read_identity(identity)?)
+        .map_err(|_| FortressError::DecryptionFailed)
+}
+
+/// Unwraps the key of the vault with a keyfile of one of its key slots.
+/// ## Parameters:
+/// - `file`: The path of the vault
+/// - `keyfile`: The path of the keyfile
+/// ## Returns:
+/// A result of the [`crypto::VaultKey`] or a [`FortressError`]
+pub fn unlock_with_keyfile(file: &str, keyfile: &str) -> Result<crypto::VaultKey, FortressError> {
+    if !Path::new(file).exists() {
+        return Err(FortressError::VaultNotFound);
+    }
+    let encrypted = fs::read(file)?;
+    let mut content = fs::read(keyfile).map_err(FortressError::IoError)?;
+    let key = crypto::VaultKey::unlock_with_keyfile(&encrypted, &content);
+    content.zeroize();
+    key.map_err(|_| FortressError::DecryptionFailed)
+}
+
+/// Reads a secret key from a file.
+/// ## Parameters:
+/// - `path`: The path of the file of the secret key
+/// ## Returns:
+/// A result of the [`crypto::Identity`] or a [`FortressError`]
+pub fn read_identity(path: &str) -> Result<crypto::Identity, FortressError> {
+    let mut content = fs::read_to_string(path).map_err(FortressError::IoError)?;
+    let identity = content.parse();
+    content.zeroize();
+    identity
+}
+
+/// Generates a random password of the given length, without printing anything.
 /// ## Parameters:
 /// - `length`: The length of the password
 /// ## Returns:
 /// A string containing the generated password.
-pub fn generate_password(length: usize) -> String {
+pub fn random_password(length: usize) -> String {
     use rand::Rng;
     const CHARSET: &[u8] =
         b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789)(*&^%$#@!~";
     let mut rng = rand::rng();
 
-    let password: String = (0..length)
+    (0..length)
         .map(|_| {
             let idx = rng.random_range(0..CHARSET.len());
             CHARSET[idx] as char
         })
-        .collect();
+        .collect()
+}
+
+/// Generates a random password of the given length.
+/// The password is copied to the clipboard.
+/// ## Parameters:
+/// - `length`: The length of the password
+/// ## Returns:
+/// A string containing the generated password.
+pub fn generate_password(length: usize) -> String {
+    let password = random_password(length);
     match cli_clipboard::set_contents(password.to_string()) {
         Ok(_) => {
             println!("Your generated password is in your clipboard");
@@ -84,11 +191,73 @@ pub fn generate_password(length: usize) -> String {
     password
 }
 
+/// Check if an identifier is inside a folder.
+/// Identifiers are seen as paths, so `work` contains `work/github` but not `workshop`.
+/// ## Parameters:
+/// - `identifier`: The identifier to check
+/// - `folder`: The folder, with or without trailing `/`
+/// ## Returns:
+/// True if the identifier is the folder itself or one of its descendants.
+pub fn in_folder(identifier: &str, folder: &str) -> bool {
+    let folder = folder.trim_end_matches('/');
+    folder.is_empty()
+        || identifier == folder
+        || identifier
+            .strip_prefix(folder)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Resolve a reference to a field of an entry, written `identifier` or `identifier#field`.
+/// The password is used when no field is given.
+/// ## Parameters:
+/// - `data`: The content of the vault
+/// - `reference`: The reference to resolve
+/// ## Returns:
+/// A result of the value or a [`FortressError`]
+pub fn lookup(data: &VaultData, reference: &str) -> Result<String, FortressError> {
+    let (identifier, field) = reference
+        .rsplit_once('#')
+        .unwrap_or((reference, "password"));
+    let entry = data
+        .entries
+        .iter()
+        .find(|item| item.identifier == identifier)
+        .ok_or_else(|| {
+            FortressError::id_not_found(
+                identifier.to_string(),
+                data.entries.iter().map(|item| item.identifier.as_str()),
+            )
+        })?;
+    entry
+        .field(field)
+        .ok_or_else(|| FortressError::UnknownField(reference.to_string()))
+}
+
+/// The current time as a UNIX timestamp in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Format a UNIX timestamp in a readable way, using the same format as the logs.
+/// ## Parameters:
+/// - `timestamp`: The UNIX timestamp in seconds
+/// ## Returns:
+/// The formatted date, or the raw timestamp if it can't be formatted.
+pub fn format_timestamp(timestamp: u64) -> String {
+    OffsetDateTime::from_unix_timestamp(timestamp as i64)
+        .ok()
+        .and_then(|date| date.format(&Rfc2822).ok())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::helpers::structs::GeneralArgs;
-    use crate::helpers::structs::PasswordEntry;
+    use crate::helpers::structs::{PasswordEntry, VaultData};
     use std::fs;
     use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -98,6 +267,43 @@ mod tests {
         assert_eq!(pw.len(), 16);
     }
 
+    #[test]
+    fn test_in_folder() {
+        assert!(in_folder("work/github", "work"));
+        assert!(in_folder("work/github", "work/"));
+        assert!(in_folder("work", "work"));
+        assert!(in_folder("work", ""));
+        assert!(!in_folder("workshop", "work"));
+    }
+
+    #[test]
+    fn test_lookup_references() {
+        let data = VaultData {
+            entries: vec![PasswordEntry {
+                identifier: "prod/db".to_string(),
+                username: "admin".to_string(),
+                password: "pw".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(lookup(&data, "prod/db").unwrap(), "pw");
+        assert_eq!(lookup(&data, "prod/db#username").unwrap(), "admin");
+        assert!(matches!(
+            lookup(&data, "prod/db#url"),
+            Err(FortressError::UnknownField(_))
+        ));
+        assert!(matches!(
+            lookup(&data, "prod/dv"),
+            Err(FortressError::IdNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "Thu, 01 Jan 1970 00:00:00 +0000");
+    }
+
     fn tmp_path(name: &str) -> String {
         let mut p = std::env::temp_dir();
         let nanos = SystemTime::now()
@@ -119,20 +325,24 @@ mod tests {
 
         let args = GeneralArgs::new(path.clone(), "masterpw".to_string());
 
-        let entries = vec![PasswordEntry {
-            identifier: "id_rt".to_string(),
-            username: "user_rt".to_string(),
-            password: "pw_rt".to_string(),
-        }];
+        let data = VaultData {
+            entries: vec![PasswordEntry {
+                identifier: "id_rt".to_string(),
+                username: "user_rt".to_string(),
+                password: "pw_rt".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
 
         // Save
-        let save_res = save_vault(args.clone(), &entries);
+        let save_res = save_vault(args.clone(), &data);
         assert!(save_res.is_ok());
 
         // Load
         let load_res = load_vault(args.clone());
         assert!(load_res.is_ok());
-        let loaded = load_res.unwrap();
+        let loaded = load_res.unwrap().entries;
         assert_eq!(loaded.len(), 1);
         assert_eq!(loaded[0].identifier, "id_rt");
         assert_eq!(loaded[0].username, "user_rt");
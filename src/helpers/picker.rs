@@ -0,0 +1,227 @@
+//! Interactive fuzzy picker, used when an identifier is omitted.
+//!
+//! The picker is drawn on stderr below the cursor, so stdout only contains the command output.
+//! Type to filter, use the arrow keys to move, `Enter` to select and `Esc` to cancel.
+use crate::helpers::errors::FortressError;
+use crate::helpers::fuzzy;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Print, Stylize},
+    terminal::{self, Clear, ClearType},
+};
+use std::io::{self, IsTerminal, Write};
+
+/// Maximum number of candidates displayed at once.
+const VISIBLE: usize = 10;
+
+/// The result of a key press ending the picker.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// A candidate has been selected.
+    Selected(String),
+    /// The user left without selecting anything.
+    Cancelled,
+}
+
+/// The state of the picker.
+pub struct Picker<'a> {
+    /// All the candidates.
+    candidates: Vec<&'a str>,
+    /// The text typed by the user.
+    query: String,
+    /// Index of the highlighted candidate among the matches.
+    selected: usize,
+}
+
+impl<'a> Picker<'a> {
+    /// Create a new picker over the candidates.
+    pub fn new(candidates: impl IntoIterator<Item = &'a str>) -> Self {
+        Picker {
+            candidates: candidates.into_iter().collect(),
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    /// The candidates matching the query, best match first.
+    pub fn matches(&self) -> Vec<&'a str> {
+        let mut ranked: Vec<(i64, &str)> = self
+            .candidates
+            .iter()
+            .filter_map(|candidate| fuzzy::score(&self.query, candidate).map(|s| (s, *candidate)))
+            .collect();
+        ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        ranked.into_iter().map(|(_, candidate)| candidate).collect()
+    }
+
+    /// Update the state from a key press.
+    /// ## Parameters:
+    /// - `key`: The key pressed by the user
+    /// ## Returns:
+    /// The [`Outcome`] if the key ends the picker.
+    pub fn handle(&mut self, key: KeyEvent) -> Option<Outcome> {
+        match key.code {
+            KeyCode::Esc => return Some(Outcome::Cancelled),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Some(Outcome::Cancelled);
+            }
+            KeyCode::Enter => {
+                return self
+                    .matches()
+                    .get(self.selected)
+                    .map(|candidate| Outcome::Selected(candidate.to_string()));
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.selected + 1 < self.matches().len() => self.selected += 1,
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Draw the query and the visible matches, leaving the cursor after the query.
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let matches = self.matches();
+        let first = self.selected.saturating_sub(VISIBLE - 1);
+        let visible: Vec<&str> = matches.iter().skip(first).take(VISIBLE).copied().collect();
+
+        queue!(
+            out,
+            cursor::MoveToColumn(0),
+            Clear(ClearType::FromCursorDown),
+            Print(format!("> {}", self.query)),
+            Print(format!("  [{}/{}]", matches.len(), self.candidates.len()).dark_grey())
+        )?;
+        for (index, candidate) in visible.iter().enumerate() {
+            if first + index == self.selected {
+                queue!(out, Print("\r\n"), Print(format!("> {}", candidate).bold()))?;
+            } else {
+                queue!(out, Print(format!("\r\n  {}", candidate)))?;
+            }
+        }
+        if !visible.is_empty() {
+            queue!(out, cursor::MoveUp(visible.len() as u16))?;
+        }
+        queue!(
+            out,
+            cursor::MoveToColumn((self.query.chars().count() + 2) as u16)
+        )?;
+        out.flush()
+    }
+}
+
+/// Return the identifier if provided, otherwise let the user pick one.
+/// ## Parameters:
+/// - `identifier`: The identifier given on the command line, if any
+/// - `candidates`: The identifiers to pick from
+/// ## Returns:
+/// A result of the identifier or a [`FortressError`]
+pub fn resolve<'a>(
+    identifier: Option<String>,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Result<String, FortressError> {
+    match identifier {
+        Some(identifier) => Ok(identifier),
+        None => pick(candidates),
+    }
+}
+
+/// Open the picker in the terminal.
+/// ## Parameters:
+/// - `candidates`: The identifiers to pick from
+/// ## Returns:
+/// A result of the selected identifier or a [`FortressError`]
+pub fn pick<'a>(candidates: impl IntoIterator<Item = &'a str>) -> Result<String, FortressError> {
+    let mut stderr = io::stderr();
+    if !stderr.is_terminal() {
+        return Err(FortressError::NoSelection);
+    }
+    let mut picker = Picker::new(candidates);
+
+    terminal::enable_raw_mode()?;
+    let outcome = run(&mut picker, &mut stderr);
+    let _ = queue!(
+        stderr,
+        cursor::MoveToColumn(0),
+        Clear(ClearType::FromCursorDown)
+    );
+    let _ = stderr.flush();
+    terminal::disable_raw_mode()?;
+
+    match outcome? {
+        Outcome::Selected(identifier) => Ok(identifier),
+        Outcome::Cancelled => Err(FortressError::NoSelection),
+    }
+}
+
+/// Draw the picker and handle key presses until it ends.
+fn run(picker: &mut Picker, out: &mut impl Write) -> io::Result<Outcome> {
+    loop {
+        picker.draw(out)?;
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && let Some(outcome) = picker.handle(key)
+        {
+            return Ok(outcome);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(picker: &mut Picker, code: KeyCode) -> Option<Outcome> {
+        picker.handle(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn test_picker_navigation() {
+        let mut picker = Picker::new(["work/github", "work/gitlab", "home/wifi"]);
+        assert_eq!(picker.matches().len(), 3);
+
+        for c in "wgit".chars() {
+            assert!(press(&mut picker, KeyCode::Char(c)).is_none());
+        }
+        assert_eq!(picker.matches(), vec!["work/github", "work/gitlab"]);
+
+        press(&mut picker, KeyCode::Down);
+        press(&mut picker, KeyCode::Down);
+        assert_eq!(
+            press(&mut picker, KeyCode::Enter),
+            Some(Outcome::Selected("work/gitlab".to_string()))
+        );
+
+        press(&mut picker, KeyCode::Up);
+        press(&mut picker, KeyCode::Up);
+        assert_eq!(
+            press(&mut picker, KeyCode::Enter),
+            Some(Outcome::Selected("work/github".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_picker_cancel_and_no_match() {
+        let mut picker = Picker::new(["github"]);
+        press(&mut picker, KeyCode::Char('z'));
+        assert!(press(&mut picker, KeyCode::Enter).is_none());
+        press(&mut picker, KeyCode::Backspace);
+        assert!(press(&mut picker, KeyCode::Enter).is_some());
+        assert_eq!(press(&mut picker, KeyCode::Esc), Some(Outcome::Cancelled));
+    }
+
+    #[test]
+    fn test_resolve_given_identifier() {
+        let res = resolve(Some("id".to_string()), ["other"]);
+        assert_eq!(res.unwrap(), "id");
+    }
+}
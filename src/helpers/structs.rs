@@ -1,9 +1,16 @@
 //! Some structs used throughout the program.
+use crate::crypto::VaultKey;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+/// Number of previous passwords kept for each entry.
+pub const HISTORY_LEN: usize = 10;
+
+/// Default number of days a removed entry stays in the trash.
+pub const TRASH_RETENTION_DAYS: u64 = 30;
+
 /// A single entry in the vault.
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct PasswordEntry {
     /// The identifier for the entry. Can be see as the path to the entry.
     pub identifier: String,
@@ -11,6 +18,73 @@ pub struct PasswordEntry {
     pub username: String,
     /// The password for the entry.
     pub password: String,
+    /// The URL of the service, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Free tags to organize the entries.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The previous passwords for the entry, most recent first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<PasswordHistory>,
+}
+
+/// A password previously used by an entry.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PasswordHistory {
+    /// The replaced password.
+    pub password: String,
+    /// When the password was replaced, as a UNIX timestamp in seconds.
+    pub replaced_at: u64,
+}
+
+impl PasswordEntry {
+    /// Names of the fields stored for each entry.
+    pub const FIELDS: &'static [&'static str] = &[
+        "identifier",
+        "username",
+        "password",
+        "url",
+        "tags",
+        "history",
+    ];
+
+    /// The value of a field, as referenced by `run` and `inject`.
+    /// Tags are joined with commas.
+    /// ## Parameters:
+    /// - `name`: The name of the field
+    /// ## Returns:
+    /// The value, or `None` if the field does not exist or is not set.
+    pub fn field(&self, name: &str) -> Option<String> {
+        match name {
+            "identifier" => Some(self.identifier.clone()),
+            "username" => Some(self.username.clone()),
+            "password" => Some(self.password.clone()),
+            "url" => self.url.clone(),
+            "tags" if !self.tags.is_empty() => Some(self.tags.join(",")),
+            _ => None,
+        }
+    }
+
+    /// Replace the password, keeping the previous one in the history.
+    /// Only the last [`HISTORY_LEN`] passwords are kept.
+    /// ## Parameters:
+    /// - `password`: The new password
+    /// - `replaced_at`: The UNIX timestamp of the change
+    pub fn set_password(&mut self, password: String, replaced_at: u64) {
+        if password == self.password {
+            return;
+        }
+        let previous = std::mem::replace(&mut self.password, password);
+        self.history.insert(
+            0,
+            PasswordHistory {
+                password: previous,
+                replaced_at,
+            },
+        );
+        self.history.truncate(HISTORY_LEN);
+    }
 }
 
 /// Display the entry in a readable format.
@@ -20,6 +94,62 @@ impl fmt::Display for PasswordEntry {
     }
 }
 
+/// An entry removed from the vault, kept in the trash until it is purged.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrashedEntry {
+    /// The removed entry.
+    pub entry: PasswordEntry,
+    /// When the entry was removed, as a UNIX timestamp in seconds.
+    pub deleted_at: u64,
+}
+
+/// The decrypted content of a vault.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct VaultData {
+    /// The entries of the vault.
+    pub entries: Vec<PasswordEntry>,
+    /// The removed entries, oldest first.
+    #[serde(default)]
+    pub trash: Vec<TrashedEntry>,
+    /// Number of days a removed entry is kept in the trash. `0` keeps them forever.
+    #[serde(default = "default_trash_retention")]
+    pub trash_retention_days: u64,
+}
+
+/// Serde default of [`VaultData::trash_retention_days`].
+fn default_trash_retention() -> u64 {
+    TRASH_RETENTION_DAYS
+}
+
+impl Default for VaultData {
+    fn default() -> Self {
+        VaultData {
+            entries: Vec::new(),
+            trash: Vec::new(),
+            trash_retention_days: TRASH_RETENTION_DAYS,
+        }
+    }
+}
+
+impl VaultData {
+    /// Drop the trashed entries older than the retention period.
+    /// ## Parameters:
+    /// - `now`: The current UNIX timestamp
+    /// ## Returns:
+    /// The number of purged entries.
+    pub fn purge_trash(&mut self, now: u64) -> usize {
+        if self.trash_retention_days == 0 {
+            return 0;
+        }
+        // Saturate, since the retention is saved in the vault: an overflow would make it unreadable.
+        let retention = self.trash_retention_days.saturating_mul(24 * 60 * 60);
+        let before = self.trash.len();
+        self.trash
+            .retain(|trashed| trashed.deleted_at.saturating_add(retention) > now);
+        before - self.trash.len()
+    }
+}
+
 /// The context of the program.
 #[derive(Clone)]
 pub struct GeneralArgs {
@@ -27,13 +157,19 @@ pub struct GeneralArgs {
     pub file: String,
     /// The master password
     pub password: String,
+    /// The key derived from the master password, when the vault has already been unlocked
+    pub key: Option<VaultKey>,
 }
 
 /// Function to use the program context.
 impl GeneralArgs {
     /// Create a new context
     pub fn new(file: String, password: String) -> Self {
-        GeneralArgs { file, password }
+        GeneralArgs {
+            file,
+            password,
+            key: None,
+        }
     }
 }
 
@@ -47,12 +183,56 @@ mod tests {
             identifier: "id".to_string(),
             username: "user".to_string(),
             password: "secret".to_string(),
+            ..Default::default()
         };
         let display = format!("{}", entry);
         assert!(display.contains("id"));
         assert!(display.contains("user"));
     }
 
+    #[test]
+    fn test_set_password_keeps_history() {
+        let mut entry = PasswordEntry {
+            identifier: "id".to_string(),
+            username: "user".to_string(),
+            password: "pw0".to_string(),
+            ..Default::default()
+        };
+        entry.set_password("pw0".to_string(), 1);
+        assert!(entry.history.is_empty());
+
+        for i in 1..=HISTORY_LEN + 2 {
+            entry.set_password(format!("pw{}", i), i as u64);
+        }
+        assert_eq!(entry.password, format!("pw{}", HISTORY_LEN + 2));
+        assert_eq!(entry.history.len(), HISTORY_LEN);
+        assert_eq!(entry.history[0].password, format!("pw{}", HISTORY_LEN + 1));
+        assert_eq!(entry.history[0].replaced_at, HISTORY_LEN as u64 + 2);
+    }
+
+    #[test]
+    fn test_purge_trash() {
+        let day = 24 * 60 * 60;
+        let trashed = |deleted_at: u64| TrashedEntry {
+            entry: PasswordEntry::default(),
+            deleted_at,
+        };
+        let mut vault = VaultData {
+            trash: vec![trashed(0), trashed(29 * day)],
+            ..Default::default()
+        };
+        assert_eq!(vault.purge_trash(31 * day), 1);
+        assert_eq!(vault.trash.len(), 1);
+
+        vault.trash_retention_days = 0;
+        assert_eq!(vault.purge_trash(365 * day), 0);
+        assert_eq!(vault.trash.len(), 1);
+
+        vault.trash_retention_days = u64::MAX;
+        assert_eq!(vault.purge_trash(1000 * 365 * day), 0);
+        assert_eq!(vault.trash.len(), 1);
+    }
+
     #[test]
     fn test_general_args_new() {
         let args = GeneralArgs::new("file".to_string(), "S3cureP@ssword".to_string());
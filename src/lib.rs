@@ -0,0 +1,19 @@
+//! Core of fortress, shared by the `frtrs` and `docker-credential-frtrs` binaries.
+//!
+//! Programs embedding fortress open a [`Vault`], which never prints anything.
+//! C programs use the functions of [`ffi`], declared in `include/fortress.h`.
+pub mod agent;
+pub mod commands;
+pub mod crypto;
+pub mod ffi;
+pub mod helpers;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod secret_service;
+pub mod shamir;
+pub mod tui;
+pub mod vault;
+
+pub use helpers::errors::FortressError;
+pub use helpers::structs::PasswordEntry;
+pub use vault::{EntryUpdate, Vault};
@@ -46,27 +46,53 @@
 //! Usage: frtrs [OPTIONS] [COMMAND]
 //!
 //! Commands:
-//! create  Create a new vault
-//! copy    Copy the password of the desired identifier
-//! view    View the password of the desired identifier
-//! remove  Remove an entry from the vault
-//! add     Add a new entry to the vault. If no one of the password methods is provided, the password will be the content of the clipboard
-//! list    List all entries in the vault
-//! help    Print this message or the help of the given subcommand(s)
+//! create            Create a new vault
+//! copy              Copy the password of the desired identifier
+//! view              View the password of the desired identifier
+//! remove            Remove an entry from the vault. The entry is moved to the trash unless 'permanent' is set
+//! add               Add a new entry to the vault. If no one of the password methods is provided, the password will be the content of the clipboard
+//! search            Search entries by identifier, username, URL and tags, best matches first
+//! list              List all entries in the vault
+//! tree              Show the entries as a tree of folders
+//! edit              Edit an existing entry. The replaced password is kept in its history
+//! mv                Rename an entry. A trailing '/' moves every entry of the folder
+//! history           Show the previous passwords of the desired identifier
+//! restore-password  Restore a previous password of the desired identifier
+//! check             Check the integrity of the vault without modifying it
+//! trash             Manage the entries removed from the vault
+//! shell             Unlock the vault once and run commands in an interactive shell
+//! run               Run a command with secrets of the vault in its environment only
+//! inject            Render a template, replacing {{ frtrs://IDENTIFIER#FIELD }} with values of the vault
+//! git-credential    Git credential helper, reading the protocol on stdin. Set 'credential.helper' to '!frtrs git-credential'
+//! native-host       Browser native messaging host, reading length-prefixed JSON messages on stdin
+//! serve             Serve a REST API on a Unix socket or on 127.0.0.1, for the holders of the tokens
+//! secret-service    Expose the vault as a Secret Service collection on the session bus
+//! share             Write one entry in an encrypted bundle, for a public key or a passphrase
+//! receive           Import the entry of a bundle made by 'share'. Its passphrase is asked unless 'key' is set
+//! recipients        Manage the public keys that can open the vault besides the master password
+//! keyslot           Manage the key slots wrapping the data key, each opened by a password or a keyfile
+//! recovery          Split a recovery key in shares, or combine them to set a new master password
+//! convert           Encrypt the vault again with another cipher, keeping its key slots
+//! agent             Keep the unlocked key in memory so that the next commands skip the master password
+//! lock              Wipe the key kept by the agent and stop it
+//! tui               Browse and edit the vault in a full-screen terminal UI
+//! help              Print this message or the help of the given subcommand(s)
 //!
 //! Options:
-//! -f, --file <PATH>      The input file path [default: /tmp/vault.frt]
+//! -f, --file <PATH>      The input file path [env: FRTRS_VAULT=] [default: /tmp/vault.frt]
+//! --identity <PATH>  Open the vault with the secret key of one of its recipients instead of the master password [env: FRTRS_IDENTITY=]
+//! --keyfile <PATH>   Open the vault with a keyfile of one of its key slots instead of the master password [env: FRTRS_KEYFILE=]
 //! --log-file <PATH>  Path to a file to write logs to [default: /tmp/fortress.log]
 //! -h, --help             Print help
 //! -V, --version          Print version
 //! ```
-mod commands;
-mod crypto;
-mod helpers;
 
 use clap::Parser;
+use fortress::crypto::VaultKey;
+use fortress::{agent, commands, helpers};
+use helpers::errors::{FortressError, raise};
 use helpers::structs::GeneralArgs;
-use helpers::{cli, errors::raise, logger};
+use helpers::{cli, logger};
 use rpassword::prompt_password;
 use std::io;
 use std::io::{IsTerminal, Read, Stdin};
@@ -76,39 +102,43 @@ fn main() {
     let args: cli::Cli = cli::Cli::parse();
     let _ = logger::init(args.log_file.as_deref());
 
+    let file = args.file.unwrap();
+    let needs_password = args.command.as_ref().is_none_or(|c| c.needs_password());
+    let key = match (&args.command, &args.identity, &args.keyfile) {
+        (Some(cli::Commands::Create { .. }), _, _) => None,
+        (_, Some(identity), _) if needs_password => {
+            unlocked(helpers::unlock_with_identity(&file, identity))
+        }
+        (_, _, Some(keyfile)) if needs_password => {
+            unlocked(helpers::unlock_with_keyfile(&file, keyfile))
+        }
+        (Some(command), _, _) if !command.uses_agent() => None,
+        _ => agent::cached_key(&file),
+    };
+
     let stdin: Stdin = io::stdin();
     let mut password: String = String::new();
-    if stdin.is_terminal() {
-        password = prompt_password("Enter the master vault password: ").unwrap();
+    if key.is_some() || !needs_password {
+        log::info!("Master password not needed");
+    } else if args.command.as_ref().is_some_and(|c| c.is_headless()) {
+        log::warn!("No key cached by the agent, the vault stays locked");
+    } else if stdin.is_terminal() || args.command.as_ref().is_some_and(|c| c.reads_stdin()) {
+        match prompt_password("Enter the master vault password: ") {
+            Ok(typed) => password = typed,
+            Err(e) => raise(FortressError::IoError(e)),
+        }
     } else {
         let mut handle = stdin.lock();
         let _ = handle.read_to_string(&mut password);
         password = password.trim().to_string();
     }
 
-    let general_args: GeneralArgs = GeneralArgs::new(args.file.unwrap(), password);
+    let mut general_args: GeneralArgs = GeneralArgs::new(file, password);
+    general_args.key = key;
 
     let result = match args.command {
-        Some(cli::Commands::Create { force }) => commands::create::create(force, general_args),
-        Some(cli::Commands::Add {
-            identifier,
-            username,
-            password,
-            generate,
-        }) => commands::add::add(
-            identifier,
-            username.unwrap_or("<empty>".to_string()),
-            password,
-            generate,
-            general_args,
-        ),
-        Some(cli::Commands::List {}) => commands::list::list(general_args),
-        Some(cli::Commands::Copy { identifier }) => commands::copy::copy(identifier, general_args),
-        Some(cli::Commands::View { identifier }) => commands::view::view(identifier, general_args),
-        Some(cli::Commands::Remove { identifier }) => {
-            commands::remove::remove(identifier, general_args)
-        }
-        None => commands::list::list(general_args),
+        Some(command) => commands::run(command, general_args),
+        None => commands::list::list(None, general_args),
     };
 
     match result {
@@ -117,9 +147,20 @@ fn main() {
     }
 }
 
+/// The key unwrapped with an identity or a keyfile, raising the error if it failed.
+fn unlocked(key: Result<VaultKey, FortressError>) -> Option<VaultKey> {
+    match key {
+        Ok(key) => Some(key),
+        Err(e) => {
+            raise(e);
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::helpers::structs::GeneralArgs;
+    use fortress::helpers::structs::GeneralArgs;
     use std::fs;
     use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -144,19 +185,25 @@ mod tests {
 
         let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
 
-        let create_res = crate::commands::create::create(true, args.clone());
+        let create_res = fortress::commands::create::create(
+            true,
+            fortress::crypto::Cipher::Aes256Gcm,
+            args.clone(),
+        );
         assert!(create_res.is_ok());
 
-        let add_res = crate::commands::add::add(
+        let add_res = fortress::commands::add::add(
             "id1".to_string(),
             "user1".to_string(),
             Some("secretpw".to_string()),
             false,
+            None,
+            Vec::new(),
             args.clone(),
         );
         assert!(add_res.is_ok());
 
-        let list_res = crate::commands::list::list(args.clone());
+        let list_res = fortress::commands::list::list(None, args.clone());
         assert!(list_res.is_ok());
 
         cleanup(&path);
@@ -171,7 +218,11 @@ mod tests {
         writeln!(f, "dummy").unwrap();
 
         let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
-        let res = crate::commands::create::create(false, args.clone());
+        let res = fortress::commands::create::create(
+            false,
+            fortress::crypto::Cipher::Aes256Gcm,
+            args.clone(),
+        );
         assert!(res.is_err());
 
         cleanup(&path);
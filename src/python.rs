@@ -0,0 +1,293 @@
+//! Python module `fortress`, built with `maturin` from the `python` feature.
+//!
+//! A `Vault` is opened with its master password and returns `Entry` objects.
+//! The errors are raised as subclasses of `fortress.FortressError`, one per variant.
+use crate::helpers;
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::PasswordEntry;
+use crate::vault::{self, EntryUpdate};
+use pyo3::prelude::*;
+
+/// Length of the passwords generated when none is given.
+const GENERATED_LENGTH: usize = 32;
+
+/// The exceptions raised by the module.
+mod exceptions {
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyException;
+
+    create_exception!(
+        fortress,
+        FortressError,
+        PyException,
+        "Base class of the errors of fortress."
+    );
+    create_exception!(fortress, VaultNotFound, FortressError);
+    create_exception!(fortress, VaultAlreadyExists, FortressError);
+    create_exception!(fortress, DecryptionFailed, FortressError);
+    create_exception!(fortress, EncryptionFailed, FortressError);
+    create_exception!(fortress, InvalidMasterPassword, FortressError);
+    create_exception!(fortress, CorruptedVault, FortressError);
+    create_exception!(fortress, IdNotFound, FortressError);
+    create_exception!(fortress, IdAlreadyExists, FortressError);
+    create_exception!(fortress, WeakPassword, FortressError);
+    create_exception!(fortress, UnknownField, FortressError);
+}
+
+/// Raise an error as the exception of its variant.
+fn raise(error: FortressError) -> PyErr {
+    let message = error.to_string();
+    match error {
+        FortressError::VaultNotFound => exceptions::VaultNotFound::new_err(message),
+        FortressError::VaultAlreadyExists => exceptions::VaultAlreadyExists::new_err(message),
+        FortressError::DecryptionFailed => exceptions::DecryptionFailed::new_err(message),
+        FortressError::EncryptionFailed => exceptions::EncryptionFailed::new_err(message),
+        FortressError::InvalidMasterPassword => exceptions::InvalidMasterPassword::new_err(message),
+        FortressError::CorruptedVault => exceptions::CorruptedVault::new_err(message),
+        FortressError::IdNotFound(..) => exceptions::IdNotFound::new_err(message),
+        FortressError::IdAlreadyExists(_) => exceptions::IdAlreadyExists::new_err(message),
+        FortressError::WeakPassword => exceptions::WeakPassword::new_err(message),
+        FortressError::UnknownField(_) => exceptions::UnknownField::new_err(message),
+        _ => exceptions::FortressError::new_err(message),
+    }
+}
+
+/// An entry of the vault, as a read-only object.
+#[pyclass(name = "Entry", module = "fortress", frozen, get_all)]
+#[derive(Clone)]
+pub struct Entry {
+    identifier: String,
+    username: String,
+    password: String,
+    url: Option<String>,
+    tags: Vec<String>,
+}
+
+#[pymethods]
+impl Entry {
+    /// The password is hidden, like in `frtrs list`.
+    fn __repr__(&self) -> String {
+        format!(
+            "Entry(identifier={:?}, username={:?}, password='*****')",
+            self.identifier, self.username
+        )
+    }
+}
+
+impl From<&PasswordEntry> for Entry {
+    fn from(entry: &PasswordEntry) -> Self {
+        Entry {
+            identifier: entry.identifier.clone(),
+            username: entry.username.clone(),
+            password: entry.password.clone(),
+            url: entry.url.clone(),
+            tags: entry.tags.clone(),
+        }
+    }
+}
+
+/// An unlocked vault. The changes are written by `save`.
+#[pyclass(name = "Vault", module = "fortress")]
+pub struct Vault(vault::Vault);
+
+#[pymethods]
+impl Vault {
+    /// Open an existing vault.
+    #[staticmethod]
+    fn open(path: &str, password: &str) -> PyResult<Self> {
+        vault::Vault::open(path, password).map(Vault).map_err(raise)
+    }
+
+    /// Create an empty vault and save it.
+    #[staticmethod]
+    #[pyo3(signature = (path, password, force = false))]
+    fn create(path: &str, password: &str, force: bool) -> PyResult<Self> {
+        vault::Vault::create(path, password, force)
+            .map(Vault)
+            .map_err(raise)
+    }
+
+    /// The path of the vault file.
+    #[getter]
+    fn path(&self) -> &str {
+        self.0.path()
+    }
+
+    /// Get an entry.
+    fn get(&self, identifier: &str) -> PyResult<Entry> {
+        self.0.get(identifier).map(Entry::from).map_err(raise)
+    }
+
+    /// All the entries.
+    fn entries(&self) -> Vec<Entry> {
+        self.0.entries().iter().map(Entry::from).collect()
+    }
+
+    /// Add an entry. A password is generated if none is given.
+    #[pyo3(signature = (identifier, username = String::new(), password = None, url = None, tags = Vec::new()))]
+    fn insert(
+        &mut self,
+        identifier: String,
+        username: String,
+        password: Option<String>,
+        url: Option<String>,
+        tags: Vec<String>,
+    ) -> PyResult<Entry> {
+        let entry = PasswordEntry {
+            identifier,
+            username,
+            password: password.unwrap_or_else(|| helpers::random_password(GENERATED_LENGTH)),
+            url,
+            tags,
+            ..Default::default()
+        };
+        let created = Entry::from(&entry);
+        self.0.insert(entry).map_err(raise)?;
+        Ok(created)
+    }
+
+    /// Change the given fields of an entry.
+    #[pyo3(signature = (identifier, *, username = None, password = None, url = None, tags = Vec::new()))]
+    fn update(
+        &mut self,
+        identifier: &str,
+        username: Option<String>,
+        password: Option<String>,
+        url: Option<String>,
+        tags: Vec<String>,
+    ) -> PyResult<Entry> {
+        let update = EntryUpdate {
+            username,
+            password,
+            url,
+            tags,
+        };
+        self.0
+            .update(identifier, update)
+            .map(Entry::from)
+            .map_err(raise)
+    }
+
+    /// Remove an entry, moved to the trash unless `permanent` is set.
+    #[pyo3(signature = (identifier, permanent = false))]
+    fn remove(&mut self, identifier: &str, permanent: bool) -> PyResult<Entry> {
+        self.0
+            .remove(identifier, permanent)
+            .map(|entry| Entry::from(&entry))
+            .map_err(raise)
+    }
+
+    /// Encrypt the vault and write it to its file.
+    fn save(&self) -> PyResult<()> {
+        self.0.save().map_err(raise)
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.entries().len()
+    }
+
+    fn __contains__(&self, identifier: &str) -> bool {
+        self.0.get(identifier).is_ok()
+    }
+}
+
+/// The `fortress` module.
+#[pymodule]
+#[pyo3(name = "fortress")]
+fn fortress_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = m.py();
+    m.add_class::<Vault>()?;
+    m.add_class::<Entry>()?;
+    m.add("FortressError", py.get_type::<exceptions::FortressError>())?;
+    m.add("VaultNotFound", py.get_type::<exceptions::VaultNotFound>())?;
+    m.add(
+        "VaultAlreadyExists",
+        py.get_type::<exceptions::VaultAlreadyExists>(),
+    )?;
+    m.add(
+        "DecryptionFailed",
+        py.get_type::<exceptions::DecryptionFailed>(),
+    )?;
+    m.add(
+        "EncryptionFailed",
+        py.get_type::<exceptions::EncryptionFailed>(),
+    )?;
+    m.add(
+        "InvalidMasterPassword",
+        py.get_type::<exceptions::InvalidMasterPassword>(),
+    )?;
+    m.add(
+        "CorruptedVault",
+        py.get_type::<exceptions::CorruptedVault>(),
+    )?;
+    m.add("IdNotFound", py.get_type::<exceptions::IdNotFound>())?;
+    m.add(
+        "IdAlreadyExists",
+        py.get_type::<exceptions::IdAlreadyExists>(),
+    )?;
+    m.add("WeakPassword", py.get_type::<exceptions::WeakPassword>())?;
+    m.add("UnknownField", py.get_type::<exceptions::UnknownField>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_python_module() {
+        let path = tmp_path("python_test");
+        cleanup(&path);
+        Python::initialize();
+        Python::attach(|py| {
+            let fortress = PyModule::new(py, "fortress").unwrap();
+            fortress_module(&fortress).unwrap();
+            let locals = pyo3::types::PyDict::new(py);
+            locals.set_item("fortress", fortress).unwrap();
+            locals.set_item("path", &path).unwrap();
+            let script = c"
+vault = fortress.Vault.create(path, 'S3cureP@ssword')
+vault.insert('db', username='admin', password='pw1', tags=['prod'])
+vault.update('db', password='pw2')
+vault.save()
+
+vault = fortress.Vault.open(path, 'S3cureP@ssword')
+entry = vault.get('db')
+assert (entry.username, entry.password, entry.tags) == ('admin', 'pw2', ['prod'])
+assert 'db' in vault and len(vault) == 1
+assert 'pw2' not in repr(entry)
+
+try:
+    vault.get('bd')
+    raise AssertionError('no exception')
+except fortress.IdNotFound as e:
+    assert isinstance(e, fortress.FortressError)
+    assert 'Did you mean `db`?' in str(e)
+
+try:
+    fortress.Vault.open(path, 'wrong')
+    raise AssertionError('no exception')
+except fortress.DecryptionFailed:
+    pass
+";
+            py.run(script, None, Some(&locals)).unwrap();
+        });
+        cleanup(&path);
+    }
+}
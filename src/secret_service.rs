@@ -0,0 +1,670 @@
+//! Freedesktop Secret Service provider, see the `org.freedesktop.secrets` specification.
+//!
+//! The vault is exposed as a single collection, which is also the `default` alias.
+//! Each entry is an item: its label is the identifier and its secret the password.
+//! The `username` and `url` attributes map to the fields of the entry, the other
+//! attributes are kept as `key=value` tags. Only `plain` sessions are supported,
+//! the session bus being private to the user.
+use crate::commands::remove;
+use crate::helpers::structs::{GeneralArgs, PasswordEntry, VaultData};
+use crate::helpers::{self, errors::FortressError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use zbus::object_server::ObjectServer;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Type, Value};
+use zbus::{blocking, fdo, interface};
+
+/// Well-known name of the service on the session bus.
+pub const BUS_NAME: &str = "org.freedesktop.secrets";
+/// Path of the service object.
+pub const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+/// Path of the collection of the vault.
+pub const COLLECTION_PATH: &str = "/org/freedesktop/secrets/collection/fortress";
+/// Prefix of the paths of the sessions.
+const SESSION_PATH: &str = "/org/freedesktop/secrets/session";
+/// Property giving the label of a new item.
+const LABEL_PROPERTY: &str = "org.freedesktop.Secret.Item.Label";
+/// Property giving the attributes of a new item.
+const ATTRIBUTES_PROPERTY: &str = "org.freedesktop.Secret.Item.Attributes";
+/// Identifier of new items without a label.
+const DEFAULT_LABEL: &str = "secret-service/item";
+
+/// The context of the program, shared by all the objects. The lock serializes the changes.
+type Shared = Arc<Mutex<GeneralArgs>>;
+
+/// A secret, as transferred over the bus in a `plain` session.
+#[derive(Type, Serialize, Deserialize)]
+pub struct Secret {
+    pub session: OwnedObjectPath,
+    pub parameters: Vec<u8>,
+    pub value: Vec<u8>,
+    pub content_type: String,
+}
+
+/// Report the errors of the vault to the clients.
+impl From<FortressError> for fdo::Error {
+    fn from(error: FortressError) -> Self {
+        fdo::Error::Failed(error.to_string())
+    }
+}
+
+/// The service object, opening sessions and resolving items across collections.
+struct Service {
+    vault: Shared,
+    sessions: AtomicU64,
+}
+
+/// The collection of the vault.
+struct Collection {
+    vault: Shared,
+}
+
+/// An entry of the vault.
+struct Item {
+    vault: Shared,
+    identifier: String,
+    path: OwnedObjectPath,
+}
+
+/// A `plain` session, only kept to be closed.
+struct Session {
+    path: OwnedObjectPath,
+}
+
+#[interface(name = "org.freedesktop.Secret.Service")]
+impl Service {
+    async fn open_session(
+        &self,
+        algorithm: &str,
+        _input: Value<'_>,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<(OwnedValue, OwnedObjectPath)> {
+        if algorithm != "plain" {
+            return Err(fdo::Error::NotSupported(format!(
+                "algorithm '{}' is not supported, use 'plain'",
+                algorithm
+            )));
+        }
+        let number = self.sessions.fetch_add(1, Ordering::Relaxed);
+        let path = object_path(format!("{}/s{}", SESSION_PATH, number))?;
+        server
+            .at(path.clone(), Session { path: path.clone() })
+            .await?;
+        let output = Value::from("")
+            .try_to_owned()
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        Ok((output, path))
+    }
+
+    async fn search_items(
+        &self,
+        attributes: HashMap<String, String>,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<(Vec<OwnedObjectPath>, Vec<OwnedObjectPath>)> {
+        Ok((search(server, &self.vault, &attributes).await?, Vec::new()))
+    }
+
+    fn unlock(
+        &self,
+        objects: Vec<OwnedObjectPath>,
+    ) -> fdo::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)> {
+        Ok((objects, no_prompt()?))
+    }
+
+    fn lock(
+        &self,
+        _objects: Vec<OwnedObjectPath>,
+    ) -> fdo::Result<(Vec<OwnedObjectPath>, OwnedObjectPath)> {
+        Ok((Vec::new(), no_prompt()?))
+    }
+
+    fn get_secrets(
+        &self,
+        items: Vec<OwnedObjectPath>,
+        session: OwnedObjectPath,
+    ) -> fdo::Result<HashMap<OwnedObjectPath, Secret>> {
+        let data = load(&self.vault)?;
+        let mut secrets = HashMap::new();
+        for entry in &data.entries {
+            let path = object_path(item_path(&entry.identifier))?;
+            if items.contains(&path) {
+                secrets.insert(path, secret(entry, session.clone()));
+            }
+        }
+        Ok(secrets)
+    }
+
+    fn read_alias(&self, name: &str) -> fdo::Result<OwnedObjectPath> {
+        match name {
+            "default" => object_path(COLLECTION_PATH.to_string()),
+            _ => no_prompt(),
+        }
+    }
+
+    fn set_alias(&self, _name: &str, _collection: OwnedObjectPath) {}
+
+    fn create_collection(
+        &self,
+        _properties: HashMap<String, OwnedValue>,
+        _alias: &str,
+    ) -> fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+        Ok((object_path(COLLECTION_PATH.to_string())?, no_prompt()?))
+    }
+
+    #[zbus(property)]
+    fn collections(&self) -> fdo::Result<Vec<OwnedObjectPath>> {
+        Ok(vec![object_path(COLLECTION_PATH.to_string())?])
+    }
+}
+
+#[interface(name = "org.freedesktop.Secret.Collection")]
+impl Collection {
+    async fn search_items(
+        &self,
+        attributes: HashMap<String, String>,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<Vec<OwnedObjectPath>> {
+        search(server, &self.vault, &attributes).await
+    }
+
+    async fn create_item(
+        &self,
+        properties: HashMap<String, OwnedValue>,
+        secret: Secret,
+        replace: bool,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+        let label: String = property(&properties, LABEL_PROPERTY).unwrap_or_default();
+        let attributes: HashMap<String, String> =
+            property(&properties, ATTRIBUTES_PROPERTY).unwrap_or_default();
+        let password = String::from_utf8(secret.value)
+            .map_err(|_| fdo::Error::InvalidArgs("the secret must be UTF-8 text".to_string()))?;
+
+        let identifier = {
+            let args = self.vault.lock().unwrap();
+            let mut data = helpers::load_vault(args.clone())?;
+            let existing = data
+                .entries
+                .iter()
+                .position(|entry| replace && !attributes.is_empty() && matches(entry, &attributes));
+            let identifier = match existing {
+                Some(index) => {
+                    let entry = &mut data.entries[index];
+                    entry.set_password(password, helpers::now());
+                    apply_attributes(entry, &attributes);
+                    entry.identifier.clone()
+                }
+                None => {
+                    let mut entry = PasswordEntry {
+                        identifier: unique_identifier(&data, &label),
+                        password,
+                        ..Default::default()
+                    };
+                    apply_attributes(&mut entry, &attributes);
+                    data.entries.push(entry.clone());
+                    entry.identifier
+                }
+            };
+            helpers::save_vault(args.clone(), &data)?;
+            identifier
+        };
+        log::info!("Secret Service item stored: {}", identifier);
+        let path = register_item(server, &self.vault, &identifier).await?;
+        Ok((path, no_prompt()?))
+    }
+
+    fn delete(&self) -> fdo::Result<OwnedObjectPath> {
+        Err(fdo::Error::NotSupported(
+            "the collection of the vault cannot be deleted".to_string(),
+        ))
+    }
+
+    #[zbus(property)]
+    fn items(&self) -> fdo::Result<Vec<OwnedObjectPath>> {
+        load(&self.vault)?
+            .entries
+            .iter()
+            .map(|entry| object_path(item_path(&entry.identifier)))
+            .collect()
+    }
+
+    #[zbus(property)]
+    fn label(&self) -> String {
+        "fortress".to_string()
+    }
+
+    #[zbus(property)]
+    fn locked(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn created(&self) -> u64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn modified(&self) -> u64 {
+        0
+    }
+}
+
+#[interface(name = "org.freedesktop.Secret.Item")]
+impl Item {
+    fn get_secret(&self, session: OwnedObjectPath) -> fdo::Result<Secret> {
+        let data = load(&self.vault)?;
+        Ok(secret(find(&data, &self.identifier)?, session))
+    }
+
+    fn set_secret(&self, secret: Secret) -> fdo::Result<()> {
+        let password = String::from_utf8(secret.value)
+            .map_err(|_| fdo::Error::InvalidArgs("the secret must be UTF-8 text".to_string()))?;
+        update(&self.vault, &self.identifier, |entry| {
+            entry.set_password(password, helpers::now())
+        })
+    }
+
+    async fn delete(
+        &self,
+        #[zbus(object_server)] server: &ObjectServer,
+    ) -> fdo::Result<OwnedObjectPath> {
+        {
+            let args = self.vault.lock().unwrap();
+            let mut data = helpers::load_vault(args.clone())?;
+            remove::remove_entry(&mut data, self.identifier.clone(), false)?;
+            helpers::save_vault(args.clone(), &data)?;
+        }
+        server.remove::<Item, _>(self.path.as_ref()).await?;
+        no_prompt()
+    }
+
+    #[zbus(property)]
+    fn attributes(&self) -> fdo::Result<HashMap<String, String>> {
+        let data = load(&self.vault)?;
+        Ok(attributes(find(&data, &self.identifier)?))
+    }
+
+    #[zbus(property)]
+    fn set_attributes(&mut self, attributes: HashMap<String, String>) -> fdo::Result<()> {
+        update(&self.vault, &self.identifier, |entry| {
+            apply_attributes(entry, &attributes)
+        })
+    }
+
+    #[zbus(property)]
+    fn label(&self) -> String {
+        self.identifier.clone()
+    }
+
+    #[zbus(property)]
+    fn locked(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn created(&self) -> u64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn modified(&self) -> u64 {
+        0
+    }
+}
+
+#[interface(name = "org.freedesktop.Secret.Session")]
+impl Session {
+    async fn close(&self, #[zbus(object_server)] server: &ObjectServer) -> fdo::Result<()> {
+        server.remove::<Session, _>(self.path.as_ref()).await?;
+        Ok(())
+    }
+}
+
+/// Expose the vault on a bus connection, and take the name of the Secret Service.
+/// ## Parameters:
+/// - `connection`: The connection to the bus
+/// - `args`: The context of the program
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn register(connection: &blocking::Connection, args: GeneralArgs) -> Result<(), FortressError> {
+    let data = helpers::load_vault(args.clone())?;
+    let vault: Shared = Arc::new(Mutex::new(args));
+    let server = connection.object_server();
+    server.at(
+        SERVICE_PATH,
+        Service {
+            vault: vault.clone(),
+            sessions: AtomicU64::new(0),
+        },
+    )?;
+    server.at(
+        COLLECTION_PATH,
+        Collection {
+            vault: vault.clone(),
+        },
+    )?;
+    for entry in &data.entries {
+        let path = object_path(item_path(&entry.identifier)).map_err(zbus::Error::from)?;
+        server.at(
+            path.clone(),
+            Item {
+                vault: vault.clone(),
+                identifier: entry.identifier.clone(),
+                path,
+            },
+        )?;
+    }
+    connection.request_name(BUS_NAME)?;
+    log::info!(
+        "Secret Service registered with {} items",
+        data.entries.len()
+    );
+    Ok(())
+}
+
+/// Find the items matching all the attributes, registering the entries added since the start.
+async fn search(
+    server: &ObjectServer,
+    vault: &Shared,
+    attributes: &HashMap<String, String>,
+) -> fdo::Result<Vec<OwnedObjectPath>> {
+    let identifiers: Vec<String> = load(vault)?
+        .entries
+        .iter()
+        .filter(|entry| matches(entry, attributes))
+        .map(|entry| entry.identifier.clone())
+        .collect();
+    let mut paths = Vec::new();
+    for identifier in identifiers {
+        paths.push(register_item(server, vault, &identifier).await?);
+    }
+    Ok(paths)
+}
+
+/// Register the item of an entry if it is not already.
+async fn register_item(
+    server: &ObjectServer,
+    vault: &Shared,
+    identifier: &str,
+) -> fdo::Result<OwnedObjectPath> {
+    let path = object_path(item_path(identifier))?;
+    server
+        .at(
+            path.clone(),
+            Item {
+                vault: vault.clone(),
+                identifier: identifier.to_string(),
+                path: path.clone(),
+            },
+        )
+        .await?;
+    Ok(path)
+}
+
+/// Decrypt the vault.
+fn load(vault: &Shared) -> fdo::Result<VaultData> {
+    let args = vault.lock().unwrap();
+    Ok(helpers::load_vault(args.clone())?)
+}
+
+/// Change an entry and save the vault.
+fn update(
+    vault: &Shared,
+    identifier: &str,
+    change: impl FnOnce(&mut PasswordEntry),
+) -> fdo::Result<()> {
+    let args = vault.lock().unwrap();
+    let mut data = helpers::load_vault(args.clone())?;
+    let entry = data
+        .entries
+        .iter_mut()
+        .find(|entry| entry.identifier == identifier)
+        .ok_or_else(|| fdo::Error::UnknownObject(identifier.to_string()))?;
+    change(entry);
+    helpers::save_vault(args.clone(), &data)?;
+    Ok(())
+}
+
+/// Find the entry of an item.
+fn find<'a>(data: &'a VaultData, identifier: &str) -> fdo::Result<&'a PasswordEntry> {
+    data.entries
+        .iter()
+        .find(|entry| entry.identifier == identifier)
+        .ok_or_else(|| fdo::Error::UnknownObject(identifier.to_string()))
+}
+
+/// The attributes of an entry: its username and URL, and its `key=value` tags.
+pub fn attributes(entry: &PasswordEntry) -> HashMap<String, String> {
+    let mut attributes: HashMap<String, String> = entry
+        .tags
+        .iter()
+        .filter_map(|tag| tag.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    if !entry.username.is_empty() {
+        attributes.insert("username".to_string(), entry.username.clone());
+    }
+    if let Some(url) = &entry.url {
+        attributes.insert("url".to_string(), url.clone());
+    }
+    attributes
+}
+
+/// Store attributes in the fields and the tags of an entry, replacing the previous `key=value` tags.
+pub fn apply_attributes(entry: &mut PasswordEntry, attributes: &HashMap<String, String>) {
+    entry.tags.retain(|tag| !tag.contains('='));
+    let mut tags = Vec::new();
+    for (key, value) in attributes {
+        match key.as_str() {
+            "username" => entry.username = value.clone(),
+            "url" => entry.url = Some(value.clone()),
+            _ => tags.push(format!("{}={}", key, value)),
+        }
+    }
+    tags.sort();
+    entry.tags.extend(tags);
+}
+
+/// Check if an entry has all the attributes.
+fn matches(entry: &PasswordEntry, query: &HashMap<String, String>) -> bool {
+    let attributes = attributes(entry);
+    query
+        .iter()
+        .all(|(key, value)| attributes.get(key) == Some(value))
+}
+
+/// An identifier for a new item, from its label, not used by another entry.
+fn unique_identifier(data: &VaultData, label: &str) -> String {
+    let label = if label.is_empty() {
+        DEFAULT_LABEL
+    } else {
+        label
+    };
+    let exists = |identifier: &str| {
+        data.entries
+            .iter()
+            .any(|entry| entry.identifier == identifier)
+    };
+    if !exists(label) {
+        return label.to_string();
+    }
+    (2..)
+        .map(|number| format!("{} ({})", label, number))
+        .find(|identifier| !exists(identifier))
+        .unwrap_or_default()
+}
+
+/// The path of the item of an identifier, which is hex-encoded to be a valid path element.
+fn item_path(identifier: &str) -> String {
+    let encoded: String = identifier
+        .bytes()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("{}/i{}", COLLECTION_PATH, encoded)
+}
+
+/// The password of an entry, as a secret of a `plain` session.
+fn secret(entry: &PasswordEntry, session: OwnedObjectPath) -> Secret {
+    Secret {
+        session,
+        parameters: Vec::new(),
+        value: entry.password.as_bytes().to_vec(),
+        content_type: "text/plain".to_string(),
+    }
+}
+
+/// Read a property of a new item.
+fn property<T: TryFrom<OwnedValue>>(
+    properties: &HashMap<String, OwnedValue>,
+    name: &str,
+) -> Option<T> {
+    T::try_from(properties.get(name)?.try_clone().ok()?).ok()
+}
+
+/// The path meaning that no prompt is needed.
+fn no_prompt() -> fdo::Result<OwnedObjectPath> {
+    object_path("/".to_string())
+}
+
+/// Build an object path.
+fn object_path(path: String) -> fdo::Result<OwnedObjectPath> {
+    OwnedObjectPath::try_from(path).map_err(|e| fdo::Error::Failed(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    /// Start a private session bus, if `dbus-daemon` is installed.
+    fn start_bus() -> Option<(Child, String)> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some((daemon, address.trim().to_string()))
+    }
+
+    fn connect(address: &str) -> blocking::Connection {
+        blocking::connection::Builder::address(address)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_attributes_mapping() {
+        let mut entry = PasswordEntry {
+            identifier: "id".to_string(),
+            tags: vec!["plain".to_string(), "old=1".to_string()],
+            ..Default::default()
+        };
+        let attributes = HashMap::from([
+            ("username".to_string(), "me".to_string()),
+            ("service".to_string(), "mail".to_string()),
+        ]);
+        apply_attributes(&mut entry, &attributes);
+        assert_eq!(entry.username, "me");
+        assert_eq!(entry.tags, vec!["plain", "service=mail"]);
+        assert_eq!(super::attributes(&entry), attributes);
+        assert!(item_path("work/db").ends_with("/i776f726b2f6462"));
+    }
+
+    #[test]
+    fn test_service_on_private_bus() {
+        let Some((mut daemon, address)) = start_bus() else {
+            eprintln!("dbus-daemon not found, skipping");
+            return;
+        };
+        let path = tmp_path("secret_service_test");
+        let args = GeneralArgs::new(path.clone(), "S3cureP@ssword".to_string());
+        crate::commands::create::create(true, crate::crypto::Cipher::Aes256Gcm, args.clone())
+            .expect("create failed");
+
+        let server = connect(&address);
+        register(&server, args.clone()).expect("register failed");
+        let client = connect(&address);
+        let service = blocking::Proxy::new(
+            &client,
+            BUS_NAME,
+            SERVICE_PATH,
+            "org.freedesktop.Secret.Service",
+        )
+        .unwrap();
+        let (_, session): (OwnedValue, OwnedObjectPath) = service
+            .call("OpenSession", &("plain", Value::from("")))
+            .unwrap();
+
+        let collection: OwnedObjectPath = service.call("ReadAlias", &("default",)).unwrap();
+        let collection = blocking::Proxy::new(
+            &client,
+            BUS_NAME,
+            collection,
+            "org.freedesktop.Secret.Collection",
+        )
+        .unwrap();
+        let properties = HashMap::from([
+            (LABEL_PROPERTY, Value::from("mail/work")),
+            (
+                ATTRIBUTES_PROPERTY,
+                Value::from(HashMap::from([("service", "mail"), ("username", "me")])),
+            ),
+        ]);
+        let secret = Secret {
+            session: session.clone(),
+            parameters: Vec::new(),
+            value: b"hunter2".to_vec(),
+            content_type: "text/plain".to_string(),
+        };
+        let (item, _): (OwnedObjectPath, OwnedObjectPath) = collection
+            .call("CreateItem", &(properties, secret, true))
+            .unwrap();
+
+        let data = helpers::load_vault(args.clone()).unwrap();
+        assert_eq!(data.entries[0].identifier, "mail/work");
+        assert_eq!(data.entries[0].username, "me");
+
+        let (found, _): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = service
+            .call("SearchItems", &(HashMap::from([("service", "mail")]),))
+            .unwrap();
+        assert_eq!(found, vec![item.clone()]);
+        let secrets: HashMap<OwnedObjectPath, Secret> =
+            service.call("GetSecrets", &(found, session)).unwrap();
+        assert_eq!(secrets[&item].value, b"hunter2");
+
+        let item =
+            blocking::Proxy::new(&client, BUS_NAME, item, "org.freedesktop.Secret.Item").unwrap();
+        let _: OwnedObjectPath = item.call("Delete", &()).unwrap();
+        let data = helpers::load_vault(args).unwrap();
+        assert!(data.entries.is_empty());
+        assert_eq!(data.trash.len(), 1);
+
+        let _ = daemon.kill();
+        let _ = daemon.wait();
+        cleanup(&path);
+    }
+}
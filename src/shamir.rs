@@ -0,0 +1,166 @@
+//! Shamir's secret sharing over GF(256), byte per byte.
+//!
+//! A secret is split in shares so that any `threshold` of them give it back,
+//! while fewer give no information about it. The field arithmetic is branch-free.
+use crate::helpers::errors::FortressError;
+use rand::RngCore;
+use zeroize::Zeroize;
+
+/// A share of a secret.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Share {
+    /// The point where the polynomial is evaluated, never 0.
+    pub index: u8,
+    /// The number of shares needed to combine the secret.
+    pub threshold: u8,
+    /// The value of the polynomial of each byte of the secret.
+    pub value: Vec<u8>,
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+/// Split a secret in shares.
+/// ## Parameters:
+/// - `secret`: The secret
+/// - `threshold`: The number of shares needed to combine the secret, at least 2
+/// - `shares`: The number of shares, at least `threshold`
+/// ## Returns:
+/// A result of the shares or a [`FortressError`]
+pub fn split(secret: &[u8], threshold: u8, shares: u8) -> Result<Vec<Share>, FortressError> {
+    if threshold < 2 || shares < threshold {
+        return Err(FortressError::Recovery(format!(
+            "cannot split in {} shares with a threshold of {}",
+            shares, threshold
+        )));
+    }
+    let mut coefficients = vec![0u8; secret.len() * (threshold as usize - 1)];
+    rand::rng().fill_bytes(&mut coefficients);
+
+    let result = (1..=shares)
+        .map(|index| Share {
+            index,
+            threshold,
+            value: secret
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| {
+                    let row =
+                        &coefficients[i * (threshold as usize - 1)..][..threshold as usize - 1];
+                    row.iter()
+                        .rev()
+                        .chain(std::iter::once(byte))
+                        .fold(0u8, |acc, coefficient| mul(acc, index) ^ coefficient)
+                })
+                .collect(),
+        })
+        .collect();
+    coefficients.zeroize();
+    Ok(result)
+}
+
+/// Combine the secret from its shares.
+/// ## Parameters:
+/// - `shares`: At least `threshold` distinct shares of the same secret
+/// ## Returns:
+/// A result of the secret or a [`FortressError`]
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>, FortressError> {
+    let Some(first) = shares.first() else {
+        return Err(FortressError::Recovery("no share given".to_string()));
+    };
+    if shares.len() < first.threshold as usize {
+        return Err(FortressError::Recovery(format!(
+            "{} shares are needed, {} given",
+            first.threshold,
+            shares.len()
+        )));
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0
+            || share.threshold != first.threshold
+            || share.value.len() != first.value.len()
+            || shares[..i].iter().any(|other| other.index == share.index)
+        {
+            return Err(FortressError::Recovery(format!(
+                "share {} does not match the others",
+                share.index
+            )));
+        }
+    }
+
+    let shares = &shares[..first.threshold as usize];
+    let mut secret = vec![0u8; first.value.len()];
+    for (i, share) in shares.iter().enumerate() {
+        let basis = shares
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != i)
+            .fold(1u8, |acc, (_, other)| {
+                mul(acc, mul(other.index, inv(other.index ^ share.index)))
+            });
+        secret
+            .iter_mut()
+            .zip(&share.value)
+            .for_each(|(byte, value)| *byte ^= mul(basis, *value));
+    }
+    Ok(secret)
+}
+
+/// Multiply in GF(256), modulo x^8 + x^4 + x^3 + x + 1.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = a >> 7;
+        a = (a << 1) ^ (0x1b & 0u8.wrapping_sub(carry));
+        b >>= 1;
+    }
+    product
+}
+
+/// Invert in GF(256), as `a^254`.
+fn inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut power = a;
+    for _ in 0..7 {
+        power = mul(power, power);
+        result = mul(result, power);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_combine() {
+        assert!((1..=255u8).all(|a| mul(a, inv(a)) == 1));
+
+        let secret: Vec<u8> = (0..32).collect();
+        let shares = split(&secret, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert!(shares.iter().all(|share| share.value != secret));
+
+        assert_eq!(combine(&shares[..3]).unwrap(), secret);
+        assert_eq!(
+            combine(&[shares[4].clone(), shares[1].clone(), shares[3].clone()]).unwrap(),
+            secret
+        );
+        assert!(matches!(
+            combine(&shares[..2]),
+            Err(FortressError::Recovery(_))
+        ));
+        assert!(matches!(
+            combine(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]),
+            Err(FortressError::Recovery(_))
+        ));
+        assert!(matches!(
+            split(&secret, 4, 3),
+            Err(FortressError::Recovery(_))
+        ));
+    }
+}
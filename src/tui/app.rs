@@ -0,0 +1,600 @@
+//! State of the terminal UI and handling of the key presses.
+use crate::commands::{copy, mv, remove, search};
+use crate::helpers::errors::FortressError;
+use crate::helpers::structs::{GeneralArgs, PasswordEntry, VaultData};
+use crate::helpers::{self, fuzzy};
+use crate::vault::{EntryUpdate, Vault};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use zeroize::Zeroize;
+
+/// Labels of the fields of the entry form.
+pub const FORM_LABELS: [&str; 5] = ["Identifier", "Username", "Password", "URL", "Tags"];
+
+/// Index of the password in [`Form::fields`].
+const PASSWORD_FIELD: usize = 2;
+
+/// Length of the passwords generated from the form.
+const GENERATED_LENGTH: usize = 32;
+
+/// The form used to add or edit an entry.
+pub struct Form {
+    /// The identifier of the edited entry, `None` when adding an entry.
+    pub original: Option<String>,
+    /// The values of the fields, in the order of [`FORM_LABELS`].
+    pub fields: [String; 5],
+    /// Index of the focused field.
+    pub focus: usize,
+}
+
+/// What the keys currently do.
+pub enum Mode {
+    /// Move in the tree and act on the selected entry.
+    Browse,
+    /// Type the search query.
+    Search,
+    /// Fill the form to add or edit an entry.
+    Form(Form),
+    /// Confirm the removal of the selected entry.
+    ConfirmDelete,
+    /// The vault is locked, type the master password.
+    Locked(String),
+}
+
+/// A line of the tree.
+#[derive(Debug, PartialEq)]
+pub enum Row {
+    /// A folder, with the number of entries it contains.
+    Folder {
+        name: String,
+        depth: usize,
+        count: usize,
+    },
+    /// An entry, with its index in [`VaultData::entries`].
+    Entry {
+        index: usize,
+        name: String,
+        depth: usize,
+    },
+}
+
+/// The state of the terminal UI.
+pub struct App {
+    /// The context of the program, with the key derived once. The master password is not kept,
+    /// and the key is wiped when locked.
+    pub args: GeneralArgs,
+    /// The unlocked content of the vault, empty when locked.
+    pub data: VaultData,
+    /// What the keys currently do.
+    pub mode: Mode,
+    /// The search query, the tree is shown when empty.
+    pub query: String,
+    /// Index of the selected row.
+    pub selected: usize,
+    /// If true, the password of the selected entry is displayed.
+    pub revealed: bool,
+    /// The last message for the user.
+    pub status: String,
+    /// Set when the user wants to leave.
+    pub quit: bool,
+}
+
+impl App {
+    /// Create the UI state over an unlocked vault.
+    pub fn new(args: GeneralArgs, data: VaultData) -> Self {
+        let mut app = App {
+            args,
+            data,
+            mode: Mode::Browse,
+            query: String::new(),
+            selected: 0,
+            revealed: false,
+            status: String::new(),
+            quit: false,
+        };
+        app.fix_selection();
+        app
+    }
+
+    /// The lines to display: the tree of identifiers, or the search results when searching.
+    pub fn rows(&self) -> Vec<Row> {
+        let entries = &self.data.entries;
+        if !self.query.is_empty() {
+            return search::rank(&self.query, entries)
+                .into_iter()
+                .filter_map(|entry| {
+                    entries
+                        .iter()
+                        .position(|item| std::ptr::eq(item, entry))
+                        .map(|index| Row::Entry {
+                            index,
+                            name: entry.identifier.clone(),
+                            depth: 0,
+                        })
+                })
+                .collect();
+        }
+
+        let mut sorted: Vec<usize> = (0..entries.len()).collect();
+        sorted.sort_by(|a, b| entries[*a].identifier.cmp(&entries[*b].identifier));
+        let mut rows = Vec::new();
+        let mut previous: Vec<&str> = Vec::new();
+        for index in sorted {
+            let parts: Vec<&str> = entries[index].identifier.split('/').collect();
+            let (name, folders) = parts.split_last().unwrap_or((&"", &[]));
+            let common = previous
+                .iter()
+                .zip(folders.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            for depth in common..folders.len() {
+                let path = folders[..=depth].join("/");
+                rows.push(Row::Folder {
+                    name: folders[depth].to_string(),
+                    depth,
+                    count: entries
+                        .iter()
+                        .filter(|item| helpers::in_folder(&item.identifier, &path))
+                        .count(),
+                });
+            }
+            rows.push(Row::Entry {
+                index,
+                name: name.to_string(),
+                depth: folders.len(),
+            });
+            previous = folders.to_vec();
+        }
+        rows
+    }
+
+    /// The selected entry, if any.
+    pub fn selected_entry(&self) -> Option<&PasswordEntry> {
+        match self.rows().get(self.selected) {
+            Some(Row::Entry { index, .. }) => self.data.entries.get(*index),
+            _ => None,
+        }
+    }
+
+    /// True if the vault has been locked.
+    pub fn is_locked(&self) -> bool {
+        matches!(self.mode, Mode::Locked(_))
+    }
+
+    /// Forget the decrypted data, the master password and the key derived from it.
+    pub fn lock(&mut self) {
+        self.data = VaultData::default();
+        self.args.password.zeroize();
+        self.args.key = None;
+        self.query.clear();
+        self.revealed = false;
+        self.mode = Mode::Locked(String::new());
+        self.status = "Vault locked".to_string();
+        log::info!("TUI locked");
+    }
+
+    /// Derive the key again from the typed password, which is then wiped, and decrypt the vault.
+    fn unlock(&mut self, mut password: String) {
+        self.args.password = std::mem::take(&mut password);
+        let key = helpers::unlock_vault(&self.args);
+        self.args.password.zeroize();
+        let loaded = key.and_then(|key| {
+            self.args.key = Some(key);
+            helpers::load_vault(self.args.clone())
+        });
+        match loaded {
+            Ok(data) => {
+                self.data = data;
+                self.mode = Mode::Browse;
+                self.status = "Vault unlocked".to_string();
+                self.fix_selection();
+            }
+            Err(e) => {
+                self.args.key = None;
+                self.mode = Mode::Locked(String::new());
+                self.status = e.to_string();
+            }
+        }
+    }
+
+    /// Update the state from a key press.
+    pub fn handle(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            self.quit = true;
+            return;
+        }
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => self.handle_browse(key),
+            Mode::Search => self.handle_search(key),
+            Mode::Form(form) => self.handle_form(form, key),
+            Mode::ConfirmDelete => self.handle_delete(key),
+            Mode::Locked(input) => self.handle_locked(input, key),
+        }
+    }
+
+    /// Keys of [`Mode::Browse`].
+    fn handle_browse(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Enter | KeyCode::Char('r') => self.revealed = !self.revealed,
+            KeyCode::Char('l') => self.lock(),
+            KeyCode::Char('a') => {
+                self.mode = Mode::Form(Form {
+                    original: None,
+                    fields: Default::default(),
+                    focus: 0,
+                })
+            }
+            KeyCode::Char('c') => {
+                if let Some(entry) = self.selected_entry() {
+                    self.status = match copy::copy_password(entry) {
+                        Ok(()) => format!("Password of '{}' copied", entry.identifier),
+                        Err(e) => e.to_string(),
+                    };
+                }
+            }
+            KeyCode::Char('e') => {
+                if let Some(entry) = self.selected_entry() {
+                    self.mode = Mode::Form(Form {
+                        original: Some(entry.identifier.clone()),
+                        fields: [
+                            entry.identifier.clone(),
+                            entry.username.clone(),
+                            entry.password.clone(),
+                            entry.url.clone().unwrap_or_default(),
+                            entry.tags.join(", "),
+                        ],
+                        focus: 0,
+                    });
+                }
+            }
+            KeyCode::Char('d') if self.selected_entry().is_some() => {
+                self.mode = Mode::ConfirmDelete
+            }
+            _ => {}
+        }
+    }
+
+    /// Keys of [`Mode::Search`].
+    fn handle_search(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.query.clear();
+                self.fix_selection();
+            }
+            KeyCode::Enter => {}
+            KeyCode::Up => {
+                self.move_selection(-1);
+                self.mode = Mode::Search;
+            }
+            KeyCode::Down => {
+                self.move_selection(1);
+                self.mode = Mode::Search;
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+                self.fix_selection();
+                self.mode = Mode::Search;
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.selected = 0;
+                self.fix_selection();
+                self.mode = Mode::Search;
+            }
+            _ => self.mode = Mode::Search,
+        }
+    }
+
+    /// Keys of [`Mode::Form`].
+    fn handle_form(&mut self, mut form: Form, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => return,
+            KeyCode::Enter if form.fields[0].is_empty() => {
+                self.status = "The identifier cannot be empty".to_string();
+            }
+            KeyCode::Enter => {
+                match self.submit(&form) {
+                    Ok(identifier) => {
+                        self.status = format!("Entry '{}' saved", identifier);
+                        self.query.clear();
+                        self.select_identifier(&identifier);
+                    }
+                    Err(e) => {
+                        self.status = e.to_string();
+                        self.mode = Mode::Form(form);
+                    }
+                }
+                return;
+            }
+            KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                form.fields[PASSWORD_FIELD] = helpers::random_password(GENERATED_LENGTH);
+            }
+            KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % FORM_LABELS.len(),
+            KeyCode::BackTab | KeyCode::Up => {
+                form.focus = (form.focus + FORM_LABELS.len() - 1) % FORM_LABELS.len()
+            }
+            KeyCode::Backspace => {
+                form.fields[form.focus].pop();
+            }
+            KeyCode::Char(c) => form.fields[form.focus].push(c),
+            _ => {}
+        }
+        self.mode = Mode::Form(form);
+    }
+
+    /// Keys of [`Mode::ConfirmDelete`].
+    fn handle_delete(&mut self, key: KeyEvent) {
+        if key.code != KeyCode::Char('y') {
+            return;
+        }
+        let identifier = match self.selected_entry() {
+            Some(entry) => entry.identifier.clone(),
+            None => return,
+        };
+        self.status = match remove::remove_entry(&mut self.data, identifier.clone(), false)
+            .and_then(|_| helpers::save_vault(self.args.clone(), &self.data))
+        {
+            Ok(()) => format!("Entry '{}' moved to the trash", identifier),
+            Err(e) => e.to_string(),
+        };
+        self.fix_selection();
+    }
+
+    /// Keys of [`Mode::Locked`].
+    fn handle_locked(&mut self, mut input: String, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Enter => return self.unlock(input),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+        self.mode = Mode::Locked(input);
+    }
+
+    /// Save the content of the form in the vault, through the [`Vault`] API.
+    /// ## Returns:
+    /// A result of the identifier of the saved entry or a [`FortressError`]
+    fn submit(&mut self, form: &Form) -> Result<String, FortressError> {
+        let [identifier, username, password, url, tags] = form.fields.clone();
+        let url = Some(url).filter(|url| !url.is_empty());
+        let tags: Vec<String> = tags
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+
+        let mut vault = Vault::with_args(self.args.clone())?;
+        match &form.original {
+            Some(original) => {
+                if *original != identifier {
+                    mv::rename(&mut vault.data_mut().entries, original, &identifier)?;
+                }
+                // The form shows every field: an emptied URL or tag list is removed.
+                if let Some(entry) = vault
+                    .data_mut()
+                    .entries
+                    .iter_mut()
+                    .find(|item| item.identifier == identifier)
+                {
+                    if url.is_none() {
+                        entry.url = None;
+                    }
+                    if tags.is_empty() {
+                        entry.tags.clear();
+                    }
+                }
+                vault.update(
+                    &identifier,
+                    EntryUpdate {
+                        username: Some(username),
+                        password: Some(password),
+                        url,
+                        tags,
+                    },
+                )?;
+            }
+            None => vault.insert(PasswordEntry {
+                identifier: identifier.clone(),
+                username,
+                password,
+                url,
+                tags,
+                ..Default::default()
+            })?,
+        }
+
+        vault.save()?;
+        self.data = vault.data().clone();
+        log::info!("TUI saved entry {}", identifier);
+        Ok(identifier)
+    }
+
+    /// Select the row of an identifier.
+    fn select_identifier(&mut self, identifier: &str) {
+        let rows = self.rows();
+        self.selected = rows
+            .iter()
+            .position(|row| {
+                matches!(row, Row::Entry { index, .. }
+                    if self.data.entries[*index].identifier == identifier)
+            })
+            .unwrap_or(0);
+        self.fix_selection();
+    }
+
+    /// Move the selection to the next entry in the direction, skipping folders.
+    fn move_selection(&mut self, direction: isize) {
+        let rows = self.rows();
+        let mut index = self.selected as isize + direction;
+        while index >= 0 && (index as usize) < rows.len() {
+            if matches!(rows[index as usize], Row::Entry { .. }) {
+                self.selected = index as usize;
+                self.revealed = false;
+                return;
+            }
+            index += direction;
+        }
+    }
+
+    /// Make sure an entry is selected when there is one.
+    fn fix_selection(&mut self) {
+        let rows = self.rows();
+        if rows.is_empty() {
+            self.selected = 0;
+            return;
+        }
+        self.selected = self.selected.min(rows.len() - 1);
+        if !matches!(rows[self.selected], Row::Entry { .. }) {
+            self.move_selection(1);
+        }
+        if !matches!(rows[self.selected], Row::Entry { .. }) {
+            self.move_selection(-1);
+        }
+    }
+
+    /// The identifiers closest to the query, displayed when nothing matches.
+    pub fn suggestions(&self) -> Vec<String> {
+        fuzzy::closest(
+            &self.query,
+            self.data
+                .entries
+                .iter()
+                .map(|item| item.identifier.as_str()),
+            3,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        text.chars().for_each(|c| press(app, KeyCode::Char(c)));
+    }
+
+    fn app(path: &str, identifiers: &[&str]) -> App {
+        let mut args = GeneralArgs::new(path.to_string(), "S3cureP@ssword".to_string());
+        let data = VaultData {
+            entries: identifiers
+                .iter()
+                .map(|id| PasswordEntry {
+                    identifier: id.to_string(),
+                    password: format!("pw_{}", id),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        helpers::save_vault(args.clone(), &data).expect("save failed");
+        args.key = Some(helpers::unlock_vault(&args).unwrap());
+        args.password.zeroize();
+        App::new(args, data)
+    }
+
+    #[test]
+    fn test_rows_and_navigation() {
+        let path = tmp_path("tui_rows");
+        let mut app = app(&path, &["work/github", "home", "work/aws/prod"]);
+        let rows = app.rows();
+        assert_eq!(rows.len(), 5);
+        assert!(matches!(&rows[0], Row::Entry { name, .. } if name == "home"));
+        assert!(matches!(&rows[1], Row::Folder { name, count: 2, .. } if name == "work"));
+        assert!(matches!(&rows[2], Row::Folder { name, depth: 1, .. } if name == "aws"));
+
+        assert_eq!(app.selected_entry().unwrap().identifier, "home");
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.selected_entry().unwrap().identifier, "work/aws/prod");
+        press(&mut app, KeyCode::Up);
+        assert_eq!(app.selected_entry().unwrap().identifier, "home");
+
+        press(&mut app, KeyCode::Char('/'));
+        type_text(&mut app, "gh");
+        assert_eq!(app.rows().len(), 1);
+        assert_eq!(app.selected_entry().unwrap().identifier, "work/github");
+        press(&mut app, KeyCode::Esc);
+        assert!(app.query.is_empty());
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_add_edit_delete() {
+        let path = tmp_path("tui_edit");
+        let mut app = app(&path, &["home"]);
+
+        press(&mut app, KeyCode::Char('a'));
+        type_text(&mut app, "work/github");
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, "dev");
+        press(&mut app, KeyCode::Tab);
+        type_text(&mut app, "secret");
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.selected_entry().unwrap().identifier, "work/github");
+
+        press(&mut app, KeyCode::Char('e'));
+        for _ in 0.."github".len() {
+            press(&mut app, KeyCode::Backspace);
+        }
+        type_text(&mut app, "gitlab");
+        press(&mut app, KeyCode::Enter);
+        let saved = helpers::load_vault(app.args.clone()).unwrap();
+        assert!(saved.entries.iter().any(|e| e.identifier == "work/gitlab"));
+
+        press(&mut app, KeyCode::Char('d'));
+        press(&mut app, KeyCode::Char('y'));
+        let saved = helpers::load_vault(app.args.clone()).unwrap();
+        assert_eq!(saved.entries.len(), 1);
+        assert_eq!(saved.trash[0].entry.identifier, "work/gitlab");
+        cleanup(&path);
+    }
+
+    #[test]
+    fn test_lock_and_unlock() {
+        let path = tmp_path("tui_lock");
+        let mut app = app(&path, &["home"]);
+        app.lock();
+        assert!(app.is_locked());
+        assert!(app.data.entries.is_empty());
+        assert!(app.args.password.is_empty());
+        assert!(app.args.key.is_none());
+
+        type_text(&mut app, "wrong");
+        press(&mut app, KeyCode::Enter);
+        assert!(app.is_locked());
+
+        type_text(&mut app, "S3cureP@ssword");
+        press(&mut app, KeyCode::Enter);
+        assert!(!app.is_locked());
+        assert!(app.args.password.is_empty());
+        assert!(app.args.key.is_some());
+        assert_eq!(app.selected_entry().unwrap().identifier, "home");
+        cleanup(&path);
+    }
+}
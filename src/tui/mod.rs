@@ -0,0 +1,53 @@
+//! Full-screen terminal UI over an unlocked vault.
+//!
+//! The state and the key handling live in [`app`], the drawing in [`ui`].
+//! All the changes go through the same helpers as the commands.
+mod app;
+mod ui;
+
+pub use app::App;
+
+use crate::helpers::errors::FortressError;
+use crossterm::event::{self, Event, KeyEventKind};
+use ratatui::DefaultTerminal;
+use std::time::{Duration, Instant};
+
+/// How often the screen is refreshed while waiting for a key.
+const TICK: Duration = Duration::from_millis(250);
+
+/// Run the terminal UI until the user leaves.
+/// ## Parameters:
+/// - `app`: The state of the UI
+/// - `lock_after`: The inactivity after which the vault is locked. Zero never locks
+/// ## Returns:
+/// A result of nothing or a [`FortressError`]
+pub fn run(mut app: App, lock_after: Duration) -> Result<(), FortressError> {
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut app, lock_after);
+    ratatui::try_restore()?;
+    result
+}
+
+/// Draw the screen and handle the key presses, locking the vault on inactivity.
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    lock_after: Duration,
+) -> Result<(), FortressError> {
+    let mut last_activity = Instant::now();
+    while !app.quit {
+        terminal.draw(|frame| ui::draw(frame, app))?;
+        if event::poll(TICK)? {
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                app.handle(key);
+                last_activity = Instant::now();
+            }
+        } else if !lock_after.is_zero() && !app.is_locked() && last_activity.elapsed() >= lock_after
+        {
+            app.lock();
+        }
+    }
+    Ok(())
+}
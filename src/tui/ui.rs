@@ -0,0 +1,182 @@
+//! Drawing of the terminal UI.
+use super::app::{App, FORM_LABELS, Form, Mode, Row};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph},
+};
+
+/// Draw the whole screen.
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, status, help] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    if let Mode::Locked(input) = &app.mode {
+        draw_locked(frame, main, input);
+    } else {
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+        draw_tree(frame, left, app);
+        draw_entry(frame, right, app);
+        if let Mode::Form(form) = &app.mode {
+            draw_form(frame, main, form);
+        }
+    }
+
+    let status_line = match (&app.mode, app.selected_entry()) {
+        (Mode::ConfirmDelete, Some(entry)) => {
+            format!("Move '{}' to the trash? (y/n)", entry.identifier)
+        }
+        _ => app.status.clone(),
+    };
+    frame.render_widget(Paragraph::new(status_line).bold(), status);
+    frame.render_widget(Paragraph::new(help_line(&app.mode)).dark_gray(), help);
+}
+
+/// The keys available in the mode.
+fn help_line(mode: &Mode) -> &'static str {
+    match mode {
+        Mode::Browse => {
+            "↑/↓ move  / search  r reveal  c copy  a add  e edit  d delete  l lock  q quit"
+        }
+        Mode::Search => "type to search  ↑/↓ move  Enter keep results  Esc clear",
+        Mode::Form(_) => "Tab next field  Ctrl-g generate password  Enter save  Esc cancel",
+        Mode::ConfirmDelete => "y confirm  any other key cancels",
+        Mode::Locked(_) => "Enter unlock  Esc quit",
+    }
+}
+
+/// Draw the tree of identifiers, or the search results.
+fn draw_tree(frame: &mut Frame, area: Rect, app: &App) {
+    let rows = app.rows();
+    let title = if app.query.is_empty() && !matches!(app.mode, Mode::Search) {
+        format!(" {} ", app.args.file)
+    } else {
+        format!(" Search: {} ", app.query)
+    };
+    let block = Block::bordered().title(title);
+
+    if rows.is_empty() && !app.query.is_empty() {
+        let suggestions = app.suggestions();
+        let text = if suggestions.is_empty() {
+            "No match".to_string()
+        } else {
+            format!("No match. Did you mean {}?", suggestions.join(", "))
+        };
+        frame.render_widget(Paragraph::new(text).block(block), area);
+        return;
+    }
+
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|row| match row {
+            Row::Folder { name, depth, count } => {
+                ListItem::new(format!("{}{}/ ({})", "  ".repeat(*depth), name, count))
+                    .style(Style::new().add_modifier(Modifier::DIM))
+            }
+            Row::Entry { name, depth, .. } => {
+                ListItem::new(format!("{}{}", "  ".repeat(*depth), name))
+            }
+        })
+        .collect();
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(app.selected_entry().map(|_| app.selected));
+    frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// Draw the details of the selected entry.
+fn draw_entry(frame: &mut Frame, area: Rect, app: &App) {
+    let block = Block::bordered().title(" Entry ");
+    let Some(entry) = app.selected_entry() else {
+        frame.render_widget(Paragraph::new("No entry").block(block), area);
+        return;
+    };
+    let password = if app.revealed {
+        entry.password.clone()
+    } else {
+        "*".repeat(8)
+    };
+    let mut lines = vec![
+        Line::from(format!("Identifier: {}", entry.identifier)),
+        Line::from(format!("Username:   {}", entry.username)),
+        Line::from(format!("Password:   {}", password)),
+    ];
+    if let Some(url) = &entry.url {
+        lines.push(Line::from(format!("URL:        {}", url)));
+    }
+    if !entry.tags.is_empty() {
+        lines.push(Line::from(format!("Tags:       {}", entry.tags.join(", "))));
+    }
+    if !entry.history.is_empty() {
+        lines.push(Line::from(format!(
+            "History:    {} previous password(s)",
+            entry.history.len()
+        )));
+    }
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// Draw the add or edit form over the main area.
+fn draw_form(frame: &mut Frame, area: Rect, form: &Form) {
+    let area = centered(area, 60, FORM_LABELS.len() as u16 + 2);
+    let title = match &form.original {
+        Some(identifier) => format!(" Edit {} ", identifier),
+        None => " Add entry ".to_string(),
+    };
+    let lines: Vec<Line> = FORM_LABELS
+        .iter()
+        .zip(form.fields.iter())
+        .enumerate()
+        .map(|(index, (label, value))| {
+            let line = Line::from(format!("{:<11}{}", format!("{}:", label), value));
+            if index == form.focus {
+                line.bold()
+            } else {
+                line
+            }
+        })
+        .collect();
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title)),
+        area,
+    );
+
+    let focused = &form.fields[form.focus];
+    frame.set_cursor_position((
+        area.x + 12 + focused.chars().count() as u16,
+        area.y + 1 + form.focus as u16,
+    ));
+}
+
+/// Draw the prompt of the master password.
+fn draw_locked(frame: &mut Frame, area: Rect, input: &str) {
+    let area = centered(area, 50, 3);
+    let prompt = format!("Master password: {}", "*".repeat(input.chars().count()));
+    frame.set_cursor_position((area.x + 1 + prompt.chars().count() as u16, area.y + 1));
+    frame.render_widget(
+        Paragraph::new(prompt).block(Block::bordered().title(" Vault locked ")),
+        area,
+    );
+}
+
+/// A rectangle of the size centered in the area.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
@@ -0,0 +1,308 @@
+//! Public API of a vault, for the programs embedding fortress.
+//!
+//! A [`Vault`] is an unlocked vault file: the key is derived once when it is opened,
+//! and the changes stay in memory until [`Vault::save`]. Nothing is printed.
+use crate::commands::{edit, remove};
+use crate::crypto::{self, Cipher, KeySlot, VaultKey};
+use crate::helpers::structs::{GeneralArgs, PasswordEntry, VaultData};
+use crate::helpers::{self, errors::FortressError};
+use password_strength::estimate_strength;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use zeroize::Zeroize;
+
+/// Minimum estimated strength of a master password.
+pub(crate) const MIN_STRENGTH: f32 = 0.7;
+
+/// An unlocked vault.
+pub struct Vault {
+    args: GeneralArgs,
+    data: VaultData,
+}
+
+/// The changes of an entry. Only the given fields are changed.
+#[derive(Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntryUpdate {
+    /// If provided, the new username.
+    pub username: Option<String>,
+    /// If provided, the new password. The replaced one is kept in the history.
+    pub password: Option<String>,
+    /// If provided, the new URL of the service.
+    pub url: Option<String>,
+    /// If not empty, the new tags of the entry.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Vault {
+    /// Open an existing vault.
+    /// ## Parameters:
+    /// - `path`: The path of the vault file
+    /// - `password`: The master password
+    /// ## Returns:
+    /// A result of the [`Vault`] or a [`FortressError`]
+    pub fn open(path: &str, password: &str) -> Result<Vault, FortressError> {
+        let args = GeneralArgs::new(path.to_string(), password.to_string());
+        let key = helpers::unlock_vault(&args)?;
+        Vault::open_with_key(path, key)
+    }
+
+    /// Open an existing vault with a key already derived, as kept by the agent.
+    /// ## Parameters:
+    /// - `path`: The path of the vault file
+    /// - `key`: The key of the vault
+    /// ## Returns:
+    /// A result of the [`Vault`] or a [`FortressError`]
+    pub fn open_with_key(path: &str, key: VaultKey) -> Result<Vault, FortressError> {
+        let mut args = GeneralArgs::new(path.to_string(), String::new());
+        args.key = Some(key);
+        Vault::with_args(args)
+    }
+
+    /// Open the vault of the context of the program, with its key or else its password.
+    /// ## Parameters:
+    /// - `args`: The context of the program
+    /// ## Returns:
+    /// A result of the [`Vault`] or a [`FortressError`]
+    pub fn with_args(args: GeneralArgs) -> Result<Vault, FortressError> {
+        let data = helpers::load_vault(args.clone())?;
+        Ok(Vault { args, data })
+    }
+
+    /// Create an empty vault and save it.
+    /// ## Parameters:
+    /// - `path`: The path of the vault file
+    /// - `password`: The master password, which must be strong enough
+    /// - `force`: If true, overwrite an existing vault
+    /// ## Returns:
+    /// A result of the [`Vault`] or a [`FortressError`]
+    pub fn create(path: &str, password: &str, force: bool) -> Result<Vault, FortressError> {
+        Vault::create_with_cipher(path, password, force, Cipher::default())
+    }
+
+    /// Create an empty vault encrypted with a cipher and save it.
+    /// With AES-256-GCM the key is derived from the master password. With another cipher,
+    /// a random data key is wrapped in a password slot, since the cipher is recorded next to the slots.
+    /// ## Parameters:
+    /// - `path`: The path of the vault file
+    /// - `password`: The master password, which must be strong enough
+    /// - `force`: If true, overwrite an existing vault
+    /// - `cipher`: The cipher encrypting the vault
+    /// ## Returns:
+    /// A result of the [`Vault`] or a [`FortressError`]
+    pub fn create_with_cipher(
+        path: &str,
+        password: &str,
+        force: bool,
+        cipher: Cipher,
+    ) -> Result<Vault, FortressError> {
+        if Path::new(path).exists() && !force {
+            return Err(FortressError::VaultAlreadyExists);
+        }
+        if estimate_strength(password) <= MIN_STRENGTH {
+            return Err(FortressError::WeakPassword);
+        }
+        let mut args = GeneralArgs::new(path.to_string(), String::new());
+        let data = VaultData::default();
+        let key = match cipher {
+            Cipher::Aes256Gcm => VaultKey::generate(password)?,
+            _ => {
+                let key = VaultKey::random();
+                let slot = KeySlot::password(&key, password)?;
+                let encrypted = crypto::encrypt_database_with_slots(&data, &key, cipher, &[slot])?;
+                fs::write(path, encrypted).map_err(FortressError::IoError)?;
+                key
+            }
+        };
+        args.key = Some(key);
+        let vault = Vault { args, data };
+        vault.save()?;
+        Ok(vault)
+    }
+
+    /// The path of the vault file.
+    pub fn path(&self) -> &str {
+        &self.args.file
+    }
+
+    /// The entries of the vault.
+    pub fn entries(&self) -> &[PasswordEntry] {
+        &self.data.entries
+    }
+
+    /// The whole content of the vault, with its trash.
+    pub fn data(&self) -> &VaultData {
+        &self.data
+    }
+
+    /// The whole content of the vault, to change it.
+    pub fn data_mut(&mut self) -> &mut VaultData {
+        &mut self.data
+    }
+
+    /// Get an entry.
+    /// ## Parameters:
+    /// - `identifier`: The identifier of the entry
+    /// ## Returns:
+    /// A result of the entry or a [`FortressError::IdNotFound`]
+    pub fn get(&self, identifier: &str) -> Result<&PasswordEntry, FortressError> {
+        self.position(identifier)
+            .map(|index| &self.data.entries[index])
+    }
+
+    /// Add an entry.
+    /// ## Parameters:
+    /// - `entry`: The new entry, whose identifier must be unused
+    /// ## Returns:
+    /// A result of nothing or a [`FortressError::IdAlreadyExists`]
+    pub fn insert(&mut self, entry: PasswordEntry) -> Result<(), FortressError> {
+        if self.position(&entry.identifier).is_ok() {
+            return Err(FortressError::IdAlreadyExists(entry.identifier));
+        }
+        log::info!("Added entry {}", entry.identifier);
+        self.data.entries.push(entry);
+        Ok(())
+    }
+
+    /// Change the given fields of an entry.
+    /// ## Parameters:
+    /// - `identifier`: The identifier of the entry
+    /// - `update`: The changes
+    /// ## Returns:
+    /// A result of the changed entry or a [`FortressError::IdNotFound`]
+    pub fn update(
+        &mut self,
+        identifier: &str,
+        update: EntryUpdate,
+    ) -> Result<&PasswordEntry, FortressError> {
+        let index = self.position(identifier)?;
+        let entry = &mut self.data.entries[index];
+        edit::apply(
+            entry,
+            update.username,
+            update.password,
+            update.url,
+            update.tags,
+        );
+        log::info!("Edited entry {}", identifier);
+        Ok(entry)
+    }
+
+    /// Remove an entry.
+    /// ## Parameters:
+    /// - `identifier`: The identifier of the entry
+    /// - `permanent`: If true, drop the entry instead of moving it to the trash
+    /// ## Returns:
+    /// A result of the removed entry or a [`FortressError::IdNotFound`]
+    pub fn remove(
+        &mut self,
+        identifier: &str,
+        permanent: bool,
+    ) -> Result<PasswordEntry, FortressError> {
+        remove::remove_entry(&mut self.data, identifier.to_string(), permanent)
+    }
+
+    /// Encrypt the vault and write it to its file.
+    /// ## Returns:
+    /// A result of nothing or a [`FortressError`]
+    pub fn save(&self) -> Result<(), FortressError> {
+        helpers::save_vault(self.args.clone(), &self.data)
+    }
+
+    /// The index of an entry.
+    fn position(&self, identifier: &str) -> Result<usize, FortressError> {
+        self.data
+            .entries
+            .iter()
+            .position(|entry| entry.identifier == identifier)
+            .ok_or_else(|| {
+                FortressError::id_not_found(
+                    identifier.to_string(),
+                    self.data
+                        .entries
+                        .iter()
+                        .map(|entry| entry.identifier.as_str()),
+                )
+            })
+    }
+}
+
+impl Drop for Vault {
+    fn drop(&mut self) {
+        self.args.password.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn tmp_path(name: &str) -> String {
+        let mut p = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("fortress_test_{}_{}.enc", name, nanos));
+        p.to_str().unwrap().to_string()
+    }
+
+    fn cleanup(path: &str) {
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_vault_lifecycle() {
+        let path = tmp_path("vault_test");
+        cleanup(&path);
+        assert!(matches!(
+            Vault::create(&path, "weak", false),
+            Err(FortressError::WeakPassword)
+        ));
+        let mut vault = Vault::create(&path, "S3cureP@ssword", false).unwrap();
+        assert!(matches!(
+            Vault::create(&path, "S3cureP@ssword", false),
+            Err(FortressError::VaultAlreadyExists)
+        ));
+
+        let entry = PasswordEntry {
+            identifier: "github".to_string(),
+            username: "me".to_string(),
+            password: "pw1".to_string(),
+            ..Default::default()
+        };
+        vault.insert(entry.clone()).unwrap();
+        assert!(matches!(
+            vault.insert(entry),
+            Err(FortressError::IdAlreadyExists(_))
+        ));
+        let updated = vault
+            .update(
+                "github",
+                EntryUpdate {
+                    password: Some("pw2".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.history[0].password, "pw1");
+        vault.save().unwrap();
+
+        let mut vault = Vault::open(&path, "S3cureP@ssword").unwrap();
+        assert_eq!(vault.get("github").unwrap().password, "pw2");
+        assert!(matches!(
+            vault.get("gihtub"),
+            Err(FortressError::IdNotFound(_, suggestions)) if suggestions == ["github"]
+        ));
+        vault.remove("github", false).unwrap();
+        assert!(vault.entries().is_empty());
+        assert_eq!(vault.data().trash.len(), 1);
+
+        assert!(Vault::open(&path, "wrong").is_err());
+        cleanup(&path);
+    }
+}